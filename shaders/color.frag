@@ -1,8 +1,17 @@
 #version 330 core
 
+// if nonzero, back faces are drawn magenta.
+uniform float show_backfaces;
+
 in vec4 color;
 out vec4 frag_color;
 
+$dither$
+
 void main() {
-  frag_color = color;
+  if(show_backfaces != 0 && !gl_FrontFacing) {
+    frag_color = vec4(1, 0, 1, 1);
+  } else {
+    frag_color = vec4(dithered(color.rgb), color.a);
+  }
 }
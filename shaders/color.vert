@@ -1,6 +1,8 @@
 #version 330 core
 
 uniform mat4 projection_matrix;
+// if positive, alpha fades linearly to 0 at this distance from the camera.
+uniform float fade_distance;
 
 in vec3 position;
 in vec4 in_color;
@@ -10,4 +12,8 @@ out vec4 color;
 void main() {
   gl_Position = projection_matrix * vec4(position, 1.0);
   color = in_color;
+  if(fade_distance > 0) {
+    // w is the distance in front of the camera.
+    color.a *= clamp(1 - gl_Position.w / fade_distance, 0, 1);
+  }
 }
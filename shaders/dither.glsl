@@ -0,0 +1,25 @@
+// if nonzero, colors are dithered to hide banding.
+uniform float dither;
+
+// A threshold in (-0.5, 0.5) for this pixel, from a 4x4 ordered (Bayer)
+// dither matrix. Adding a fraction of a color step of this breaks smooth
+// gradients' bands up into a fine, regular pattern.
+float dither_threshold() {
+  const float bayer[16] = float[16](
+     0,  8,  2, 10,
+    12,  4, 14,  6,
+     3, 11,  1,  9,
+    15,  7, 13,  5
+  );
+  int x = int(mod(gl_FragCoord.x, 4));
+  int y = int(mod(gl_FragCoord.y, 4));
+  return (bayer[y * 4 + x] + 0.5) / 16 - 0.5;
+}
+
+// `color`, dithered by up to half an 8-bit step if `dither` is on.
+vec3 dithered(vec3 color) {
+  if(dither == 0) {
+    return color;
+  }
+  return color + dither_threshold() / 255;
+}
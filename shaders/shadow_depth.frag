@@ -0,0 +1,5 @@
+#version 330 core
+
+// Nothing to do; the depth is all the shadow map needs.
+void main() {
+}
@@ -0,0 +1,19 @@
+#version 330 core
+
+// Draws terrain from the sun's point of view into the shadow map. Only depth
+// is written.
+
+// the sun's view, from `shadow::light_matrix`
+uniform mat4 projection_matrix;
+
+uniform samplerBuffer positions;
+
+void main() {
+  int position_id = gl_VertexID * 3;
+  vec3 world_position;
+  world_position.x = texelFetch(positions, position_id).r;
+  world_position.y = texelFetch(positions, position_id + 1).r;
+  world_position.z = texelFetch(positions, position_id + 2).r;
+
+  gl_Position = projection_matrix * vec4(world_position, 1.0);
+}
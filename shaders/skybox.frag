@@ -0,0 +1,11 @@
+#version 330 core
+
+uniform samplerCube skybox;
+
+in vec3 direction;
+
+out vec4 frag_color;
+
+void main() {
+  frag_color = texture(skybox, direction);
+}
@@ -0,0 +1,13 @@
+#version 330 core
+
+uniform mat4 projection_matrix;
+
+in vec3 position;
+
+out vec3 direction;
+
+void main() {
+  direction = position;
+  // z = w puts every vertex on the far plane, whatever the cube's size.
+  gl_Position = (projection_matrix * vec4(position, 1.0)).xyww;
+}
@@ -7,14 +7,46 @@ uniform struct Light {
 
 uniform vec3 ambient_light;
 
+// indexed by terrain type
+uniform vec4 terrain_colors[$max_terrain_types$];
+// indexed by terrain type; if nonzero, that type ignores lighting and
+// ambient occlusion and is always drawn at full brightness.
+uniform float terrain_emissive[$max_terrain_types$];
+
+// how much each face's shade is allowed to vary; 0 disables the variation.
+uniform float color_jitter;
+
+// if nonzero, colors are blended across faces using `type_weight`.
+uniform float smooth_colors;
+
+// if nonzero, back faces are drawn magenta.
+uniform float show_backfaces;
+
+// if nonzero, fragments the sun can't see lose their direct light.
+uniform float shadows;
+// the sun's view, from `shadow::light_matrix`
+uniform mat4 light_matrix;
+// depth from the sun's view; see shadow.rs.
+uniform sampler2D shadow_map;
+// how much nearer the sun something has to be to cast a shadow, to stop
+// surfaces from shadowing themselves.
+uniform float shadow_bias;
+
 uniform samplerBuffer positions;
 uniform samplerBuffer normals;
 uniform isamplerBuffer terrain_types;
+uniform samplerBuffer color_noise;
+// per face; newly-placed terrain flashes white, fading from 1 to 0.
+uniform samplerBuffer flash;
 
 flat in int vertex_id;
+in float occlusion;
+in float type_weight[$terrain_type_count$];
 
 out vec4 frag_color;
 
+$dither$
+
 void main() {
   int face_id = vertex_id / 3;
 
@@ -37,23 +69,51 @@ void main() {
     // length(normal) = 1, so don't bother dividing.
     float brightness = dot(normal, light_path) / length(light_path);
     brightness = clamp(brightness, 0, 1);
+
+    if(shadows != 0) {
+      // from [-1, 1] to the shadow map's [0, 1].
+      vec3 light_space = (light_matrix * vec4(world_position, 1)).xyz * 0.5 + 0.5;
+      bool in_map =
+        all(greaterThanEqual(light_space, vec3(0))) && all(lessThanEqual(light_space, vec3(1)));
+      if(in_map && light_space.z - shadow_bias > texture(shadow_map, light_space.xy).r) {
+        brightness = 0;
+      }
+    }
   #endif
 
   vec4 base_color;
-  if(terrain_type == uint(0)) {
-    base_color = vec4(0, 0.5, 0, 1);
-  } else if(terrain_type == uint(1)) {
-    base_color = vec4(0.5, 0.4, 0.2, 1);
-  } else if(terrain_type == uint(2)) {
-    base_color = vec4(0.5, 0.5, 0.5, 1);
+  if(terrain_type < uint($terrain_type_count$) && smooth_colors != 0) {
+    base_color = vec4(0);
+    for(int i = 0; i < $terrain_type_count$; ++i) {
+      base_color += type_weight[i] * terrain_colors[i];
+    }
+  } else if(terrain_type < uint($max_terrain_types$)) {
+    base_color = terrain_colors[int(terrain_type)];
   } else {
     base_color = vec4(float(terrain_type) / 65535, 0, 0, 1);
   }
+  float noise = texelFetch(color_noise, face_id).r;
+  base_color.rgb = clamp(base_color.rgb * (1 + color_jitter * noise), 0, 1);
 
-  #if $lighting$
-    vec3 lighting = brightness * light.intensity + ambient_light;
-    frag_color = vec4(clamp(lighting, 0, 1), 1) * base_color;
-  #else
+  bool emissive =
+    terrain_type < uint($max_terrain_types$) && terrain_emissive[int(terrain_type)] != 0;
+  if(emissive) {
     frag_color = base_color;
-  #endif
+  } else {
+    base_color.rgb *= occlusion;
+    #if $lighting$
+      vec3 lighting = brightness * light.intensity + ambient_light;
+      frag_color = vec4(clamp(lighting, 0, 1), 1) * base_color;
+    #else
+      frag_color = base_color;
+    #endif
+  }
+
+  frag_color.rgb = mix(frag_color.rgb, vec3(1), texelFetch(flash, face_id).r);
+
+  frag_color.rgb = dithered(frag_color.rgb);
+
+  if(show_backfaces != 0 && !gl_FrontFacing) {
+    frag_color = vec4(1, 0, 1, 1);
+  }
 }
@@ -3,8 +3,15 @@
 uniform mat4 projection_matrix;
 
 uniform samplerBuffer positions;
+uniform samplerBuffer ambient_occlusion;
+uniform samplerBuffer type_weights;
 
 flat out int vertex_id;
+// brightness multiplier, interpolated across the face.
+out float occlusion;
+// how much of each terrain type's color to use when smoothing colors,
+// interpolated across the face.
+out float type_weight[$terrain_type_count$];
 
 void main() {
   int position_id = gl_VertexID * 3;
@@ -13,6 +20,11 @@ void main() {
   world_position.y = texelFetch(positions, position_id + 1).r;
   world_position.z = texelFetch(positions, position_id + 2).r;
   vertex_id = gl_VertexID;
+  occlusion = texelFetch(ambient_occlusion, gl_VertexID).r;
+  int weight_id = gl_VertexID * $terrain_type_count$;
+  for(int i = 0; i < $terrain_type_count$; ++i) {
+    type_weight[i] = texelFetch(type_weights, weight_id + i).r;
+  }
 
   gl_Position = projection_matrix * vec4(world_position, 1.0);
 }
@@ -0,0 +1,117 @@
+//! Bookkeeping for GPU buffers that hold one slot of data per id, and stay
+//! packed by moving the last slot into any slot that's removed.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/// Which slot each id's data is in. This doesn't own any buffers; whoever
+/// does moves their data around to match what `push` and `swap_remove` say.
+pub struct BufferIndex<Id> {
+  id_to_index: HashMap<Id, uint>,
+  index_to_id: Vec<Id>,
+}
+
+impl<Id: Copy + Eq + Hash> BufferIndex<Id> {
+  pub fn new() -> BufferIndex<Id> {
+    BufferIndex {
+      id_to_index: HashMap::new(),
+      index_to_id: Vec::new(),
+    }
+  }
+
+  /// Add `id` in a new slot at the end, returning the slot. `id` mustn't be
+  /// present already.
+  pub fn push(&mut self, id: Id) -> uint {
+    assert!(!self.contains(id));
+    let idx = self.index_to_id.len();
+    self.id_to_index.insert(id, idx);
+    self.index_to_id.push(id);
+    idx
+  }
+
+  pub fn contains(&self, id: Id) -> bool {
+    self.id_to_index.contains_key(&id)
+  }
+
+  /// The slot `id` is in.
+  pub fn find(&self, id: Id) -> Option<uint> {
+    self.id_to_index.find(&id).map(|&idx| idx)
+  }
+
+  pub fn len(&self) -> uint {
+    self.index_to_id.len()
+  }
+
+  /// Remove `id`, and move the id in the last slot into its slot, the same
+  /// way `Vec::swap_remove` does. Returns the slot `id` was in, which the
+  /// buffers should `swap_remove` too, or None if `id` wasn't present.
+  pub fn swap_remove(&mut self, id: Id) -> Option<uint> {
+    let idx =
+      match self.id_to_index.pop(&id) {
+        None => return None,
+        Some(idx) => idx,
+      };
+    self.index_to_id.swap_remove(idx).unwrap();
+    if idx < self.index_to_id.len() {
+      let swapped_id = self.index_to_id[idx];
+      self.id_to_index.insert(swapped_id, idx);
+    }
+    Some(idx)
+  }
+}
+
+#[test]
+fn slots_follow_swap_removes() {
+  // `data` stands in for a buffer: slot i holds the id that's in slot i.
+  let mut index = BufferIndex::new();
+  let mut data = Vec::new();
+  for id in range(0u, 6) {
+    let idx = index.push(id * 10);
+    assert_eq!(idx, data.len());
+    data.push(id * 10);
+  }
+
+  let check = |index: &BufferIndex<uint>, data: &Vec<uint>, present: &[uint]| {
+    assert_eq!(index.len(), present.len());
+    assert_eq!(data.len(), present.len());
+    for &id in present.iter() {
+      let idx = index.find(id).unwrap();
+      assert_eq!(data[idx], id);
+    }
+  };
+  check(&index, &data, &[0, 10, 20, 30, 40, 50]);
+
+  // from the front, the middle and the end.
+  for &id in [0u, 30, 20].iter() {
+    let idx = index.swap_remove(id).unwrap();
+    data.swap_remove(idx);
+  }
+  check(&index, &data, &[10, 40, 50]);
+  assert!(!index.contains(0));
+  assert_eq!(index.find(30), None);
+
+  let last = data[data.len() - 1];
+  let idx = index.swap_remove(last).unwrap();
+  assert_eq!(idx, index.len());
+  data.swap_remove(idx);
+  let remaining: Vec<uint> = [10u, 40, 50].iter().map(|&id| id).filter(|&id| id != last).collect();
+  check(&index, &data, remaining.as_slice());
+}
+
+#[test]
+fn removing_everything_empties_it() {
+  let mut index = BufferIndex::new();
+  for id in range(0u, 4) {
+    index.push(id);
+  }
+  assert_eq!(index.swap_remove(7), None);
+  for &id in [2u, 0, 3, 1].iter() {
+    assert!(index.swap_remove(id).is_some());
+    assert_eq!(index.swap_remove(id), None);
+  }
+  assert_eq!(index.len(), 0);
+
+  // slots are reused from the start.
+  assert_eq!(index.push(5), 0);
+  assert_eq!(index.find(5), Some(0));
+}
@@ -0,0 +1,378 @@
+//! A bounding volume hierarchy: a binary tree of AABBs, for picking and
+//! overlap queries over boxes of any size.
+//!
+//! Unlike the octree, nothing is split: each thing is a single leaf, and
+//! each branch's bounds are the union of its two children's. Insertions and
+//! removals touch only the path from the edited leaf up to the root, so the
+//! tree can be kept up to date as terrain is placed and removed.
+
+use nalgebra::{Pnt3, Vec3};
+use ncollide::bounding_volume::aabb::AABB;
+use ncollide::bounding_volume::BoundingVolume;
+use ncollide::math::Scalar;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::num::Float;
+
+/// A side of an AABB.
+#[deriving(Show, Copy, Clone, PartialEq, Eq)]
+pub enum Face {
+  MinX,
+  MaxX,
+  MinY,
+  MaxY,
+  MinZ,
+  MaxZ,
+}
+
+enum Contents<T> {
+  Leaf(T),
+  // the indices of the two children
+  Branch(uint, uint),
+  // unused, and on the free list
+  Free,
+}
+
+struct Node<T> {
+  bounds: AABB,
+  parent: Option<uint>,
+  contents: Contents<T>,
+}
+
+pub struct Bvh<T> {
+  nodes: Vec<Node<T>>,
+  // indices of `Free` nodes, for reuse
+  free: Vec<uint>,
+  root: Option<uint>,
+  // the leaf holding each thing
+  leaves: HashMap<T, uint>,
+}
+
+fn volume(bounds: &AABB) -> Scalar {
+  let d = *bounds.maxs() - *bounds.mins();
+  d.x * d.y * d.z
+}
+
+// How much `bounds` would have to grow to hold `extra` as well.
+fn growth(bounds: &AABB, extra: &AABB) -> Scalar {
+  volume(&bounds.merged(extra)) - volume(bounds)
+}
+
+/// Where the ray from `origin` along `direction` first enters `bounds`, as a
+/// multiple of `direction`, and through which face. A ray starting inside
+/// `bounds` hits it at 0.
+pub fn cast_ray(origin: &Pnt3<Scalar>, direction: &Vec3<Scalar>, bounds: &AABB) -> Option<(Scalar, Face)> {
+  let origin = [origin.x, origin.y, origin.z];
+  let direction = [direction.x, direction.y, direction.z];
+  let mins = [bounds.mins().x, bounds.mins().y, bounds.mins().z];
+  let maxs = [bounds.maxs().x, bounds.maxs().y, bounds.maxs().z];
+  let faces = [(MinX, MaxX), (MinY, MaxY), (MinZ, MaxZ)];
+
+  let mut enter: Scalar = Float::neg_infinity();
+  let mut exit: Scalar = Float::infinity();
+  let mut face = MinX;
+  for axis in range(0, 3u) {
+    let (o, d) = (origin[axis], direction[axis]);
+    if d == 0.0 {
+      // parallel to this pair of faces, so it has to start between them.
+      if o < mins[axis] || o > maxs[axis] {
+        return None;
+      }
+      continue;
+    }
+
+    let (min_face, max_face) = faces[axis];
+    let (near, far, near_face) =
+      if d > 0.0 {
+        ((mins[axis] - o) / d, (maxs[axis] - o) / d, min_face)
+      } else {
+        ((maxs[axis] - o) / d, (mins[axis] - o) / d, max_face)
+      };
+    if near > enter {
+      enter = near;
+      face = near_face;
+    }
+    exit = exit.min(far);
+    if enter > exit {
+      return None;
+    }
+  }
+
+  if exit < 0.0 {
+    None
+  } else {
+    Some((enter.max(0.0), face))
+  }
+}
+
+impl<T: Copy + Eq + Hash> Bvh<T> {
+  pub fn new() -> Bvh<T> {
+    Bvh {
+      nodes: Vec::new(),
+      free: Vec::new(),
+      root: None,
+      leaves: HashMap::new(),
+    }
+  }
+
+  fn alloc(&mut self, node: Node<T>) -> uint {
+    match self.free.pop() {
+      None => {
+        self.nodes.push(node);
+        self.nodes.len() - 1
+      },
+      Some(i) => {
+        *self.nodes.get_mut(i) = node;
+        i
+      },
+    }
+  }
+
+  fn release(&mut self, i: uint) {
+    self.nodes.get_mut(i).contents = Free;
+    self.free.push(i);
+  }
+
+  // In `parent`, replace the child `old` with `new`.
+  fn replace_child(&mut self, parent: uint, old: uint, new: uint) {
+    let contents =
+      match self.nodes[parent].contents {
+        Branch(a, b) if a == old => Branch(new, b),
+        Branch(a, b) if b == old => Branch(a, new),
+        _ => fail!("BVH node {} isn't a child of {}", old, parent),
+      };
+    self.nodes.get_mut(parent).contents = contents;
+  }
+
+  // Recompute the bounds of `i` and all its ancestors.
+  fn refit(&mut self, mut i: Option<uint>) {
+    loop {
+      let n =
+        match i {
+          None => break,
+          Some(n) => n,
+        };
+      let children =
+        match self.nodes[n].contents {
+          Branch(a, b) => Some((a, b)),
+          _ => None,
+        };
+      match children {
+        None => {},
+        Some((a, b)) => {
+          let bounds = self.nodes[a].bounds.merged(&self.nodes[b].bounds);
+          self.nodes.get_mut(n).bounds = bounds;
+        },
+      }
+      i = self.nodes[n].parent;
+    }
+  }
+
+  /// Add `t`, replacing it if it's already here.
+  pub fn insert(&mut self, t: T, bounds: &AABB) {
+    self.remove(t);
+
+    let leaf = self.alloc(Node { bounds: bounds.clone(), parent: None, contents: Leaf(t) });
+    self.leaves.insert(t, leaf);
+
+    let mut sibling =
+      match self.root {
+        None => {
+          self.root = Some(leaf);
+          return;
+        },
+        Some(root) => root,
+      };
+
+    // walk down to the leaf whose bounds grow least by taking `bounds` in.
+    loop {
+      match self.nodes[sibling].contents {
+        Branch(a, b) => {
+          let grow_a = growth(&self.nodes[a].bounds, bounds);
+          let grow_b = growth(&self.nodes[b].bounds, bounds);
+          sibling = if grow_a <= grow_b { a } else { b };
+        },
+        _ => break,
+      }
+    }
+
+    // replace that leaf with a branch holding both it and the new one.
+    let old_parent = self.nodes[sibling].parent;
+    let merged = self.nodes[sibling].bounds.merged(bounds);
+    let branch = self.alloc(Node { bounds: merged, parent: old_parent, contents: Branch(sibling, leaf) });
+    self.nodes.get_mut(sibling).parent = Some(branch);
+    self.nodes.get_mut(leaf).parent = Some(branch);
+    match old_parent {
+      None => self.root = Some(branch),
+      Some(p) => self.replace_child(p, sibling, branch),
+    }
+    self.refit(old_parent);
+  }
+
+  /// Remove `t`. Returns false if it wasn't here.
+  pub fn remove(&mut self, t: T) -> bool {
+    let leaf =
+      match self.leaves.pop(&t) {
+        None => return false,
+        Some(leaf) => leaf,
+      };
+    let parent = self.nodes[leaf].parent;
+    self.release(leaf);
+
+    let parent =
+      match parent {
+        None => {
+          self.root = None;
+          return true;
+        },
+        Some(parent) => parent,
+      };
+
+    // the leaf's sibling takes its parent's place.
+    let sibling =
+      match self.nodes[parent].contents {
+        Branch(a, b) => if a == leaf { b } else { a },
+        _ => fail!("BVH leaf {} has a non-branch parent", leaf),
+      };
+    let grandparent = self.nodes[parent].parent;
+    self.release(parent);
+    self.nodes.get_mut(sibling).parent = grandparent;
+    match grandparent {
+      None => self.root = Some(sibling),
+      Some(g) => {
+        self.replace_child(g, parent, sibling);
+        self.refit(grandparent);
+      },
+    }
+    true
+  }
+
+  /// Everything whose bounds intersect `bounds`, in no particular order.
+  pub fn overlaps(&self, bounds: &AABB) -> Vec<T> {
+    let mut found = Vec::new();
+    let mut stack: Vec<uint> = self.root.into_iter().collect();
+    loop {
+      let i =
+        match stack.pop() {
+          None => break,
+          Some(i) => i,
+        };
+      let node = &self.nodes[i];
+      if !node.bounds.intersects(bounds) {
+        continue;
+      }
+      match node.contents {
+        Leaf(t) => found.push(t),
+        Branch(a, b) => {
+          stack.push(a);
+          stack.push(b);
+        },
+        Free => fail!("BVH node {} is reachable but free", i),
+      }
+    }
+    found
+  }
+
+  /// The first thing other than `except` hit by the ray from `origin` along
+  /// `direction`, which face of its bounds was hit, and where, as a multiple
+  /// of `direction`.
+  pub fn raycast(&self, origin: &Pnt3<Scalar>, direction: &Vec3<Scalar>, except: Option<T>) -> Option<(T, Face, Scalar)> {
+    let mut best: Option<(T, Face, Scalar)> = None;
+    let mut stack: Vec<uint> = self.root.into_iter().collect();
+    loop {
+      let i =
+        match stack.pop() {
+          None => break,
+          Some(i) => i,
+        };
+      let node = &self.nodes[i];
+      let (t, face) =
+        match cast_ray(origin, direction, &node.bounds) {
+          None => continue,
+          Some(hit) => hit,
+        };
+      // nothing in here can beat what's already been hit.
+      match best {
+        Some((_, _, best_t)) if best_t <= t => continue,
+        _ => {},
+      }
+      match node.contents {
+        Leaf(id) if Some(id) == except => {},
+        Leaf(id) => best = Some((id, face, t)),
+        Branch(a, b) => {
+          stack.push(a);
+          stack.push(b);
+        },
+        Free => fail!("BVH node {} is reachable but free", i),
+      }
+    }
+    best
+  }
+}
+
+#[test]
+fn ray_hits_the_near_face() {
+  let bounds = AABB::new(Pnt3::new(1.0, -1.0, -1.0), Pnt3::new(2.0, 1.0, 1.0));
+  let origin = Pnt3::new(0.0, 0.0, 0.0);
+  assert_eq!(cast_ray(&origin, &Vec3::new(1.0, 0.0, 0.0), &bounds), Some((1.0, MinX)));
+  assert_eq!(cast_ray(&origin, &Vec3::new(-1.0, 0.0, 0.0), &bounds), None);
+  assert_eq!(cast_ray(&origin, &Vec3::new(0.0, 1.0, 0.0), &bounds), None);
+  let inside = Pnt3::new(1.5, 0.0, 0.0);
+  assert_eq!(cast_ray(&inside, &Vec3::new(0.0, 0.0, 1.0), &bounds).map(|(t, _)| t), Some(0.0));
+  let above = Pnt3::new(1.5, 3.0, 0.0);
+  assert_eq!(cast_ray(&above, &Vec3::new(0.0, -2.0, 0.0), &bounds), Some((1.0, MaxY)));
+}
+
+#[test]
+fn overlaps_match_brute_force() {
+  let mut rng = ::rng::Rng::new(1);
+  let mut bvh = Bvh::new();
+  let mut boxes = Vec::new();
+  let random_box = |rng: &mut ::rng::Rng| {
+    let min = Pnt3::new(rng.range_f32(-10.0, 10.0), rng.range_f32(-10.0, 10.0), rng.range_f32(-10.0, 10.0));
+    let size = Vec3::new(rng.range_f32(0.1, 3.0), rng.range_f32(0.1, 3.0), rng.range_f32(0.1, 3.0));
+    AABB::new(min, min + size)
+  };
+  for i in range(0, 200u) {
+    let bounds = random_box(&mut rng);
+    bvh.insert(i, &bounds);
+    boxes.push((i, bounds));
+  }
+  // remove every third one, so removal is covered too.
+  for i in range(0, 200u).filter(|i| i % 3 == 0) {
+    assert!(bvh.remove(i));
+  }
+  assert!(!bvh.remove(0));
+  boxes.retain(|&(i, _)| i % 3 != 0);
+
+  for _ in range(0, 50u) {
+    let query = random_box(&mut rng);
+    let mut found = bvh.overlaps(&query);
+    found.sort();
+    let expected: Vec<uint> =
+      boxes.iter()
+        .filter(|&&(_, ref bounds)| bounds.intersects(&query))
+        .map(|&(i, _)| i)
+        .collect();
+    assert_eq!(found, expected);
+  }
+}
+
+#[test]
+fn raycast_finds_the_nearest() {
+  let mut bvh = Bvh::new();
+  let unit_box = |x: Scalar| AABB::new(Pnt3::new(x, 0.0, 0.0), Pnt3::new(x + 1.0, 1.0, 1.0));
+  for &x in [5.0, 2.0, 8.0, -3.0].iter() {
+    bvh.insert(x as int, &unit_box(x));
+  }
+  let origin = Pnt3::new(0.0, 0.5, 0.5);
+  assert_eq!(bvh.raycast(&origin, &Vec3::new(1.0, 0.0, 0.0), None), Some((2, MinX, 2.0)));
+  assert_eq!(bvh.raycast(&origin, &Vec3::new(-1.0, 0.0, 0.0), None), Some((-3, MaxX, 2.0)));
+  assert_eq!(bvh.raycast(&origin, &Vec3::new(0.0, 1.0, 0.0), None), None);
+  // whatever's excepted is looked through.
+  assert_eq!(bvh.raycast(&origin, &Vec3::new(1.0, 0.0, 0.0), Some(2)), Some((5, MinX, 5.0)));
+
+  // moving the nearest one out of the way exposes the next.
+  bvh.insert(2, &unit_box(20.0));
+  assert_eq!(bvh.raycast(&origin, &Vec3::new(1.0, 0.0, 0.0), None), Some((5, MinX, 5.0)));
+}
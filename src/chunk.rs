@@ -0,0 +1,74 @@
+//! Dividing the world into a grid of fixed-size cubes ("chunks"), for tools
+//! and debug views that want to talk about regions of the world.
+//!
+//! Nothing is stored per chunk: a chunk is just the cube of space
+//! `[c * CHUNK_SIZE, (c + 1) * CHUNK_SIZE)` on each axis, for chunk
+//! coordinates `c`.
+
+use gl::types::*;
+use nalgebra::Pnt3;
+use ncollide::bounding_volume::aabb::AABB;
+use std::iter::range_inclusive;
+
+/// The width of a chunk on every axis, in world units.
+pub static CHUNK_SIZE: GLfloat = 8.0;
+
+/// A chunk's position in the grid of chunks.
+pub type ChunkCoords = (i32, i32, i32);
+
+/// The chunk containing `p`. Points on a boundary belong to the chunk on
+/// their positive side.
+pub fn chunk_of(p: &Pnt3<GLfloat>) -> ChunkCoords {
+  let coord = |x: GLfloat| (x / CHUNK_SIZE).floor() as i32;
+  (coord(p.x), coord(p.y), coord(p.z))
+}
+
+/// The space covered by the chunk at `c`.
+pub fn chunk_bounds(c: ChunkCoords) -> AABB {
+  let (x, y, z) = c;
+  let min =
+    Pnt3::new(
+      x as GLfloat * CHUNK_SIZE,
+      y as GLfloat * CHUNK_SIZE,
+      z as GLfloat * CHUNK_SIZE,
+    );
+  AABB::new(min, Pnt3::new(min.x + CHUNK_SIZE, min.y + CHUNK_SIZE, min.z + CHUNK_SIZE))
+}
+
+/// The chunks at most `radius` chunks away from `c` on every axis, including
+/// `c` itself.
+pub fn chunks_around(c: ChunkCoords, radius: i32) -> Vec<ChunkCoords> {
+  let (x, y, z) = c;
+  let mut chunks = Vec::new();
+  for dx in range_inclusive(-radius, radius) {
+    for dy in range_inclusive(-radius, radius) {
+      for dz in range_inclusive(-radius, radius) {
+        chunks.push((x + dx, y + dy, z + dz));
+      }
+    }
+  }
+  chunks
+}
+
+#[test]
+fn points_map_to_the_chunks_containing_them() {
+  assert_eq!(chunk_of(&Pnt3::new(0.0, 0.0, 0.0)), (0, 0, 0));
+  assert_eq!(chunk_of(&Pnt3::new(7.9, 8.0, 16.5)), (0, 1, 2));
+  assert_eq!(chunk_of(&Pnt3::new(-0.1, -8.0, -8.1)), (-1, -1, -2));
+
+  for &c in [(0, 0, 0), (3, -2, 1), (-5, 7, -1)].iter() {
+    let bounds = chunk_bounds(c);
+    assert_eq!(chunk_of(bounds.mins()), c);
+    assert_eq!(chunk_of(&::common::center(&bounds)), c);
+  }
+}
+
+#[test]
+fn chunks_around_form_a_cube() {
+  assert_eq!(chunks_around((1, 2, 3), 0), vec!((1, 2, 3)));
+  let chunks = chunks_around((1, 2, 3), 1);
+  assert_eq!(chunks.len(), 27);
+  assert!(chunks.contains(&(0, 1, 2)));
+  assert!(chunks.contains(&(2, 3, 4)));
+  assert!(!chunks.contains(&(3, 2, 3)));
+}
@@ -3,12 +3,16 @@
 use gl::types::*;
 use glw::color::Color4;
 use glw::vertex::{ColoredVertex};
-use nalgebra::Pnt3;
+use nalgebra::{Pnt2, Pnt3, Vec3};
+use std::f32::consts::PI;
 use ncollide::bounding_volume::aabb::AABB;
 
 pub const WINDOW_WIDTH:  uint = 800;
 pub const WINDOW_HEIGHT: uint = 600;
 
+/// The size of one window pixel in HUD coordinates.
+pub const HUD_PIXEL: GLfloat = 2.0 / WINDOW_HEIGHT as GLfloat;
+
 pub const TRIANGLES_PER_BOX: uint = 12;
 pub const VERTICES_PER_TRIANGLE: uint = 3;
 pub const TRIANGLE_VERTICES_PER_BOX: uint = TRIANGLES_PER_BOX * VERTICES_PER_TRIANGLE;
@@ -21,6 +25,120 @@ pub const USE_LIGHTING: bool = true;
 
 pub const MAX_WORLD_SIZE: uint = 800000;
 
+/// Convert a position in window pixels (origin at the top left) to HUD
+/// coordinates (origin in the middle, y increasing upward).
+pub fn window_to_hud(x: f64, y: f64) -> Pnt2<GLfloat> {
+  let aspect = WINDOW_WIDTH as GLfloat / WINDOW_HEIGHT as GLfloat;
+  Pnt2::new(
+    (2.0 * x as GLfloat / WINDOW_WIDTH as GLfloat - 1.0) * aspect,
+    1.0 - 2.0 * y as GLfloat / WINDOW_HEIGHT as GLfloat,
+  )
+}
+
+/// The box spanned by two opposite corners, given in any order.
+pub fn aabb_of_corners(a: &Pnt3<GLfloat>, b: &Pnt3<GLfloat>) -> AABB {
+  AABB::new(
+    Pnt3::new(partial_min(a.x, b.x), partial_min(a.y, b.y), partial_min(a.z, b.z)),
+    Pnt3::new(partial_max(a.x, b.x), partial_max(a.y, b.y), partial_max(a.z, b.z)),
+  )
+}
+
+/// The smallest box containing both `a` and `b`.
+pub fn aabb_union(a: &AABB, b: &AABB) -> AABB {
+  aabb_of_corners(
+    &Pnt3::new(
+      partial_min(a.mins().x, b.mins().x),
+      partial_min(a.mins().y, b.mins().y),
+      partial_min(a.mins().z, b.mins().z),
+    ),
+    &Pnt3::new(
+      partial_max(a.maxs().x, b.maxs().x),
+      partial_max(a.maxs().y, b.maxs().y),
+      partial_max(a.maxs().z, b.maxs().z),
+    ),
+  )
+}
+
+/// The smallest box containing every point, or None if there are no points.
+pub fn aabb_of_points(points: &[Pnt3<GLfloat>]) -> Option<AABB> {
+  points.iter().fold(None, |bounds, p| {
+    let point = aabb_of_corners(p, p);
+    match bounds {
+      None => Some(point),
+      Some(bounds) => Some(aabb_union(&bounds, &point)),
+    }
+  })
+}
+
+fn partial_min(x: GLfloat, y: GLfloat) -> GLfloat {
+  if x < y { x } else { y }
+}
+
+fn partial_max(x: GLfloat, y: GLfloat) -> GLfloat {
+  if x < y { y } else { x }
+}
+
+pub fn center(bounds: &AABB) -> Pnt3<GLfloat> {
+  (bounds.mins() + bounds.maxs().to_vec()) / (2.0 as GLfloat)
+}
+
+/// Whether a box is flat (or empty) along any axis.
+pub fn has_zero_volume(bounds: &AABB) -> bool {
+  bounds.mins().x >= bounds.maxs().x ||
+  bounds.mins().y >= bounds.maxs().y ||
+  bounds.mins().z >= bounds.maxs().z
+}
+
+#[test]
+fn swapped_corners_are_normalized() {
+  let bounds = aabb_of_corners(&Pnt3::new(1.0, 0.0, 3.0), &Pnt3::new(0.0, 2.0, -1.0));
+  assert_eq!(*bounds.mins(), Pnt3::new(0.0, 0.0, -1.0));
+  assert_eq!(*bounds.maxs(), Pnt3::new(1.0, 2.0, 3.0));
+  assert!(!has_zero_volume(&bounds));
+}
+
+#[test]
+fn flat_boxes_have_zero_volume() {
+  let bounds = aabb_of_corners(&Pnt3::new(1.0, 2.0, 3.0), &Pnt3::new(0.0, 2.0, -1.0));
+  assert!(has_zero_volume(&bounds));
+}
+
+#[test]
+fn union_of_disjoint_boxes() {
+  let a = aabb_of_corners(&Pnt3::new(0.0, 0.0, 0.0), &Pnt3::new(1.0, 1.0, 1.0));
+  let b = aabb_of_corners(&Pnt3::new(2.0, -3.0, 4.0), &Pnt3::new(3.0, -2.0, 5.0));
+  let u = aabb_union(&a, &b);
+  assert_eq!(*u.mins(), Pnt3::new(0.0, -3.0, 0.0));
+  assert_eq!(*u.maxs(), Pnt3::new(3.0, 1.0, 5.0));
+}
+
+#[test]
+fn union_of_overlapping_boxes() {
+  let a = aabb_of_corners(&Pnt3::new(0.0, 0.0, 0.0), &Pnt3::new(2.0, 2.0, 2.0));
+  let b = aabb_of_corners(&Pnt3::new(1.0, 1.0, -1.0), &Pnt3::new(3.0, 1.5, 1.0));
+  let u = aabb_union(&a, &b);
+  assert_eq!(*u.mins(), Pnt3::new(0.0, 0.0, -1.0));
+  assert_eq!(*u.maxs(), Pnt3::new(3.0, 2.0, 2.0));
+  // a box contained in another doesn't change it.
+  let inner = aabb_of_corners(&Pnt3::new(0.5, 0.5, 0.5), &Pnt3::new(1.0, 1.0, 1.0));
+  let u = aabb_union(&a, &inner);
+  assert_eq!(*u.mins(), *a.mins());
+  assert_eq!(*u.maxs(), *a.maxs());
+}
+
+#[test]
+fn box_of_points() {
+  assert!(aabb_of_points([]).is_none());
+  let bounds =
+    aabb_of_points([
+      Pnt3::new(1.0, 0.0, 0.0),
+      Pnt3::new(-1.0, 2.0, 0.5),
+      Pnt3::new(0.0, 1.0, -4.0),
+    ]).unwrap();
+  assert_eq!(*bounds.mins(), Pnt3::new(-1.0, 0.0, -4.0));
+  assert_eq!(*bounds.maxs(), Pnt3::new(1.0, 2.0, 0.5));
+}
+
 pub fn partial_min_by<A: Copy, T: Iterator<A>, B: PartialOrd>(t: T, f: |A| -> B) -> Vec<A> {
   let mut t = t;
   let mut min_a = Vec::new();
@@ -47,10 +165,10 @@ pub fn partial_min_by<A: Copy, T: Iterator<A>, B: PartialOrd>(t: T, f: |A| -> B)
   min_a
 }
 
-pub fn to_outlines<'a>(bounds: &AABB) -> [ColoredVertex, ..LINE_VERTICES_PER_BOX] {
+pub fn to_outlines<'a>(bounds: &AABB, c: &Color4<GLfloat>) -> [ColoredVertex, ..LINE_VERTICES_PER_BOX] {
   let (x1, y1, z1) = (bounds.mins().x, bounds.mins().y, bounds.mins().z);
   let (x2, y2, z2) = (bounds.maxs().x, bounds.maxs().y, bounds.maxs().z);
-  let c = Color4::of_rgba(0.0, 0.0, 0.0, 0.1);
+  let c = *c;
 
   let vtx = |x: f32, y: f32, z: f32| -> ColoredVertex {
     ColoredVertex {
@@ -111,3 +229,48 @@ pub fn to_triangles(bounds: &AABB, c: &Color4<GLfloat>) -> [ColoredVertex, ..VER
     vtx(x1, y1, z1), vtx(x2, y1, z1), vtx(x2, y1, z2),
   ]
 }
+
+/// Lines around a circle of `radius` centered on `center`, in the plane
+/// spanned by the unit vectors `u` and `v`, split into `segments` lines.
+pub fn ring_lines(
+  center: &Pnt3<GLfloat>,
+  u: Vec3<GLfloat>,
+  v: Vec3<GLfloat>,
+  radius: GLfloat,
+  segments: uint,
+  c: &Color4<GLfloat>,
+) -> Vec<ColoredVertex> {
+  let at = |i: uint| {
+    let angle = 2.0 * PI * i as GLfloat / segments as GLfloat;
+    ColoredVertex {
+      position: *center + (u * angle.cos() + v * angle.sin()) * radius,
+      color: *c,
+    }
+  };
+  let mut vs = Vec::new();
+  for i in range(0, segments) {
+    vs.push(at(i));
+    vs.push(at(i + 1));
+  }
+  vs
+}
+
+#[test]
+fn rings_are_closed_and_round() {
+  let center = Pnt3::new(1.0, 2.0, 3.0);
+  let color = Color4::of_rgba(1.0, 1.0, 1.0, 1.0);
+  let vs = ring_lines(&center, Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 1.0), 2.0, 8, &color);
+  assert_eq!(vs.len(), 8 * VERTICES_PER_LINE);
+  for v in vs.iter() {
+    let d = v.position - center;
+    assert!((::nalgebra::Norm::norm(&d) - 2.0).abs() < 0.0001);
+    // it's flat in the plane it was given.
+    assert_eq!(d.y, 0.0);
+  }
+  // each line starts where the last one ended, and the last ends at the start.
+  for i in range(1, 8u) {
+    assert_eq!(vs[2 * i].position, vs[2 * i - 1].position);
+  }
+  let (first, last) = (vs[0].position, vs[vs.len() - 1].position);
+  assert!(::nalgebra::Norm::norm(&(last - first)) < 0.0001);
+}
@@ -0,0 +1,279 @@
+//! A text console, toggled with backtick, for typing commands at runtime.
+
+use gl::types::*;
+use glw::color::Color4;
+use input::keyboard;
+use nalgebra::{Pnt3, Vec3};
+use terrain::TerrainType;
+use terrain_types::TerrainTypes;
+use text_overlay::TextLineId;
+
+/// How long the console's caret stays shown, and then hidden, for.
+pub static CARET_BLINK_SECONDS: f64 = 0.5;
+
+/// The caret to show `time` seconds into the game: an underscore, or a space
+/// while it's blinked off.
+pub fn caret(time: f64) -> &'static str {
+  if (time / CARET_BLINK_SECONDS) as u64 % 2 == 0 { "_" } else { " " }
+}
+
+/// A parsed console command.
+#[deriving(Show, PartialEq)]
+pub enum Command {
+  /// `tp x y z`: move the player to a point.
+  Teleport(Pnt3<GLfloat>),
+  /// `top x z`: move the player onto the highest terrain at x and z.
+  TeleportToTop(GLfloat, GLfloat),
+  /// `clear type`: remove all terrain of a type.
+  Clear(TerrainType),
+  /// `recolor type r g b`: change the color a terrain type is drawn with.
+  Recolor(TerrainType, Color4<GLfloat>),
+  /// `size width height`: resize the player.
+  Size(GLfloat, GLfloat),
+  /// `clip near far`: set the clip plane distances.
+  Clip(GLfloat, GLfloat),
+  /// `gravity x y z`: set the player's gravity.
+  Gravity(Vec3<GLfloat>),
+  /// `stress n`: place n random terrain pieces, and report how it went.
+  Stress(uint),
+  /// `explode x y z radius`: remove the terrain around a point, and knock the
+  /// player back.
+  Explode(Pnt3<GLfloat>, GLfloat),
+  /// `settle`: drop floating structures left by removed terrain onto whatever
+  /// is under them.
+  Settle,
+  /// `path speed x y z yaw pitch [x y z yaw pitch ...]`: move the player
+  /// through waypoints, at `speed` units per update.
+  FollowPath(Vec<(Pnt3<GLfloat>, GLfloat, GLfloat)>, GLfloat),
+  /// `step n`: run n steps of the simulation straight away, without
+  /// rendering, even while paused.
+  Step(uint),
+}
+
+fn parse_floats(args: &[&str], count: uint) -> Result<Vec<GLfloat>, String> {
+  if args.len() != count {
+    return Err(format!("expected {} numbers, got {}", count, args.len()));
+  }
+  let mut floats = Vec::new();
+  for arg in args.iter() {
+    match from_str::<GLfloat>(*arg) {
+      None => return Err(format!("\"{}\" isn't a number", arg)),
+      Some(x) => floats.push(x),
+    }
+  }
+  Ok(floats)
+}
+
+fn parse_type(types: &TerrainTypes, arg: Option<&&str>) -> Result<TerrainType, String> {
+  match arg {
+    None => Err(String::from_str("expected a terrain type")),
+    Some(name) =>
+      match types.by_name(*name) {
+        None => Err(format!("unknown terrain type \"{}\"", name)),
+        Some(typ) => Ok(typ),
+      },
+  }
+}
+
+/// Parse a line of console input into a command, or describe what's wrong
+/// with it. Terrain types are looked up by name in `types`.
+pub fn parse(line: &str, types: &TerrainTypes) -> Result<Command, String> {
+  let words: Vec<&str> = line.words().collect();
+  if words.is_empty() {
+    return Err(String::from_str("no command"));
+  }
+
+  let args = words.slice_from(1);
+  match words[0] {
+    "tp" => {
+      let xyz = try!(parse_floats(args, 3));
+      Ok(Teleport(Pnt3::new(xyz[0], xyz[1], xyz[2])))
+    },
+    "top" => {
+      let xz = try!(parse_floats(args, 2));
+      Ok(TeleportToTop(xz[0], xz[1]))
+    },
+    "clear" => {
+      if args.len() != 1 {
+        return Err(String::from_str("usage: clear <type>"));
+      }
+      parse_type(types, args.get(0)).map(|typ| Clear(typ))
+    },
+    "recolor" => {
+      let typ = try!(parse_type(types, args.get(0)));
+      let rgb = try!(parse_floats(args.slice_from(1), 3));
+      Ok(Recolor(typ, Color4::of_rgba(rgb[0], rgb[1], rgb[2], 1.0)))
+    },
+    "size" => {
+      let wh = try!(parse_floats(args, 2));
+      Ok(Size(wh[0], wh[1]))
+    },
+    "clip" => {
+      let planes = try!(parse_floats(args, 2));
+      Ok(Clip(planes[0], planes[1]))
+    },
+    "gravity" => {
+      let xyz = try!(parse_floats(args, 3));
+      Ok(Gravity(Vec3::new(xyz[0], xyz[1], xyz[2])))
+    },
+    "stress" => {
+      if args.len() != 1 {
+        return Err(String::from_str("usage: stress <count>"));
+      }
+      match from_str::<uint>(args[0]) {
+        None => Err(format!("\"{}\" isn't a count", args[0])),
+        Some(n) => Ok(Stress(n)),
+      }
+    },
+    "explode" => {
+      let args = try!(parse_floats(args, 4));
+      if args[3] <= 0.0 {
+        return Err(String::from_str("the radius has to be positive"));
+      }
+      Ok(Explode(Pnt3::new(args[0], args[1], args[2]), args[3]))
+    },
+    "settle" => {
+      if !args.is_empty() {
+        return Err(String::from_str("usage: settle"));
+      }
+      Ok(Settle)
+    },
+    "path" => {
+      if args.len() < 6 || (args.len() - 1) % 5 != 0 {
+        return Err(String::from_str("usage: path <speed> <x y z yaw pitch>..."));
+      }
+      let args = try!(parse_floats(args, args.len()));
+      if args[0] <= 0.0 {
+        return Err(String::from_str("the speed has to be positive"));
+      }
+      let waypoints =
+        args.slice_from(1).chunks(5)
+          .map(|w| (Pnt3::new(w[0], w[1], w[2]), w[3], w[4]))
+          .collect();
+      Ok(FollowPath(waypoints, args[0]))
+    },
+    "step" => {
+      if args.len() != 1 {
+        return Err(String::from_str("usage: step <count>"));
+      }
+      match from_str::<uint>(args[0]) {
+        None => Err(format!("\"{}\" isn't a count", args[0])),
+        Some(n) => Ok(Step(n)),
+      }
+    },
+    command => Err(format!("unknown command \"{}\"", command)),
+  }
+}
+
+/// The character typed by a key in the console, if any.
+pub fn key_to_char(key: keyboard::Key) -> Option<char> {
+  let c =
+    match key {
+      keyboard::A => 'a', keyboard::B => 'b', keyboard::C => 'c',
+      keyboard::D => 'd', keyboard::E => 'e', keyboard::F => 'f',
+      keyboard::G => 'g', keyboard::H => 'h', keyboard::I => 'i',
+      keyboard::J => 'j', keyboard::K => 'k', keyboard::L => 'l',
+      keyboard::M => 'm', keyboard::N => 'n', keyboard::O => 'o',
+      keyboard::P => 'p', keyboard::Q => 'q', keyboard::R => 'r',
+      keyboard::S => 's', keyboard::T => 't', keyboard::U => 'u',
+      keyboard::V => 'v', keyboard::W => 'w', keyboard::X => 'x',
+      keyboard::Y => 'y', keyboard::Z => 'z',
+      keyboard::D0 => '0', keyboard::D1 => '1', keyboard::D2 => '2',
+      keyboard::D3 => '3', keyboard::D4 => '4', keyboard::D5 => '5',
+      keyboard::D6 => '6', keyboard::D7 => '7', keyboard::D8 => '8',
+      keyboard::D9 => '9',
+      keyboard::Space => ' ',
+      keyboard::Minus => '-',
+      keyboard::Period => '.',
+      _ => return None,
+    };
+  Some(c)
+}
+
+pub struct Console {
+  pub is_open: bool,
+  /// The command being typed.
+  pub input: String,
+  /// Shows the input being typed.
+  pub input_line: TextLineId,
+  /// Shows the result of the last command.
+  pub output_line: TextLineId,
+}
+
+impl Console {
+  pub fn new(input_line: TextLineId, output_line: TextLineId) -> Console {
+    Console {
+      is_open: false,
+      input: String::new(),
+      input_line: input_line,
+      output_line: output_line,
+    }
+  }
+
+  /// The text to show on the input line `time` seconds into the game. The
+  /// caret blinks every `CARET_BLINK_SECONDS`.
+  pub fn input_text(&self, time: f64) -> String {
+    if !self.is_open {
+      return String::new();
+    }
+    format!("> {}{}", self.input, caret(time))
+  }
+}
+
+#[test]
+fn parse_teleport() {
+  let types = TerrainTypes::defaults();
+  let parse = |line: &str| parse(line, &types);
+  assert_eq!(parse("tp 1 -2.5 3"), Ok(Teleport(Pnt3::new(1.0, -2.5, 3.0))));
+  assert!(parse("tp 1 2").is_err());
+  assert!(parse("tp 1 two 3").is_err());
+  assert_eq!(parse("top 4 -0.5"), Ok(TeleportToTop(4.0, -0.5)));
+  assert!(parse("top 4").is_err());
+}
+
+#[test]
+fn parse_terrain_commands() {
+  let types = TerrainTypes::defaults();
+  let parse = |line: &str| parse(line, &types);
+  assert_eq!(parse("clear stone"), Ok(Clear(::terrain::STONE)));
+  assert!(parse("clear lava").is_err());
+  assert_eq!(
+    parse("  recolor grass 0 1 0.5 "),
+    Ok(Recolor(::terrain::GRASS, Color4::of_rgba(0.0, 1.0, 0.5, 1.0))),
+  );
+  assert!(parse("recolor grass 0 1").is_err());
+}
+
+#[test]
+fn parse_errors() {
+  let types = TerrainTypes::defaults();
+  let parse = |line: &str| parse(line, &types);
+  assert!(parse("").is_err());
+  assert!(parse("fly 1 2").is_err());
+  assert_eq!(parse("size 1 2"), Ok(Size(1.0, 2.0)));
+  assert_eq!(parse("gravity 0.1 0 0"), Ok(Gravity(Vec3::new(0.1, 0.0, 0.0))));
+  assert_eq!(parse("stress 1000"), Ok(Stress(1000)));
+  assert!(parse("stress -1").is_err());
+  assert!(parse("stress").is_err());
+  assert_eq!(parse("settle"), Ok(Settle));
+  assert_eq!(parse("explode 1 2 3 4"), Ok(Explode(Pnt3::new(1.0, 2.0, 3.0), 4.0)));
+  assert!(parse("explode 1 2 3 0").is_err());
+  assert!(parse("settle now").is_err());
+  assert_eq!(
+    parse("path 0.5 1 2 3 0 0 4 5 6 1.5 -0.5"),
+    Ok(FollowPath(vec!((Pnt3::new(1.0, 2.0, 3.0), 0.0, 0.0), (Pnt3::new(4.0, 5.0, 6.0), 1.5, -0.5)), 0.5)),
+  );
+  assert!(parse("path 0.5").is_err());
+  assert!(parse("path 0.5 1 2 3 0").is_err());
+  assert!(parse("path 0 1 2 3 0 0").is_err());
+  assert_eq!(parse("step 10"), Ok(Step(10)));
+  assert!(parse("step 1.5").is_err());
+}
+
+#[test]
+fn caret_blinks() {
+  assert_eq!(caret(0.0), "_");
+  assert_eq!(caret(CARET_BLINK_SECONDS * 0.9), "_");
+  assert_eq!(caret(CARET_BLINK_SECONDS * 1.5), " ");
+  assert_eq!(caret(CARET_BLINK_SECONDS * 2.5), "_");
+}
@@ -0,0 +1,155 @@
+//! Crosshair shapes for the center of the HUD.
+
+use gl::types::*;
+use glw::color::Color4;
+use glw::vertex::ColoredVertex;
+use nalgebra::{Pnt2, Pnt3};
+use std::default::Default;
+use std::f32::consts::PI;
+
+// half the width of the dot, and of the cross' bars
+static DOT_RADIUS: GLfloat = 0.02;
+static CROSS_THICKNESS: GLfloat = 0.004;
+static CROSS_RADIUS: GLfloat = 0.03;
+static CIRCLE_INNER_RADIUS: GLfloat = 0.015;
+static CIRCLE_OUTER_RADIUS: GLfloat = 0.025;
+// quads around the circle
+static CIRCLE_SEGMENTS: uint = 16;
+
+// the progress bar shown under the crosshair, e.g. while digging
+static PROGRESS_BAR_WIDTH: GLfloat = 0.16;
+static PROGRESS_BAR_HEIGHT: GLfloat = 0.015;
+static PROGRESS_BAR_TOP: GLfloat = -0.05;
+
+#[deriving(Show, Copy, Clone, PartialEq, Eq)]
+pub enum CrosshairStyle {
+  /// a small filled square
+  Dot,
+  /// a thin plus sign
+  Cross,
+  /// a ring
+  Circle,
+}
+
+impl Default for CrosshairStyle {
+  fn default() -> CrosshairStyle {
+    Dot
+  }
+}
+
+impl CrosshairStyle {
+  pub fn from_name(name: &str) -> Option<CrosshairStyle> {
+    match name {
+      "dot" => Some(Dot),
+      "cross" => Some(Cross),
+      "circle" => Some(Circle),
+      _ => None,
+    }
+  }
+
+  pub fn name(&self) -> &'static str {
+    match *self {
+      Dot => "dot",
+      Cross => "cross",
+      Circle => "circle",
+    }
+  }
+}
+
+/// Triangles for a crosshair centered on the origin, in HUD coordinates.
+pub fn vertices(style: CrosshairStyle, color: Color4<GLfloat>) -> Vec<ColoredVertex> {
+  let mut vs = Vec::new();
+  match style {
+    Dot => {
+      vs.push_all(ColoredVertex::square(
+        Pnt2::new(-DOT_RADIUS, -DOT_RADIUS),
+        Pnt2::new(DOT_RADIUS, DOT_RADIUS),
+        color,
+      ));
+    },
+    Cross => {
+      let (t, r) = (CROSS_THICKNESS, CROSS_RADIUS);
+      // the horizontal bar, then the vertical bar without the middle, so the
+      // overlap isn't drawn twice.
+      vs.push_all(ColoredVertex::square(Pnt2::new(-r, -t), Pnt2::new(r, t), color));
+      vs.push_all(ColoredVertex::square(Pnt2::new(-t, t), Pnt2::new(t, r), color));
+      vs.push_all(ColoredVertex::square(Pnt2::new(-t, -r), Pnt2::new(t, -t), color));
+    },
+    Circle => {
+      let at = |i: uint, r: GLfloat| {
+        let angle = 2.0 * PI * i as GLfloat / CIRCLE_SEGMENTS as GLfloat;
+        ColoredVertex {
+          position: Pnt3::new(r * angle.cos(), r * angle.sin(), 0.0),
+          color: color,
+        }
+      };
+      for i in range(0, CIRCLE_SEGMENTS) {
+        let (inner1, inner2) = (at(i, CIRCLE_INNER_RADIUS), at(i + 1, CIRCLE_INNER_RADIUS));
+        let (outer1, outer2) = (at(i, CIRCLE_OUTER_RADIUS), at(i + 1, CIRCLE_OUTER_RADIUS));
+        vs.push_all([inner1, outer1, outer2, inner1, outer2, inner2]);
+      }
+    },
+  }
+  vs
+}
+
+/// Triangles for a progress bar just under the crosshair, filled in from the
+/// left by `progress`, which is clamped to [0, 1].
+pub fn progress_bar(
+  progress: GLfloat,
+  background: Color4<GLfloat>,
+  fill: Color4<GLfloat>,
+) -> Vec<ColoredVertex> {
+  let progress = progress.max(0.0).min(1.0);
+  let left = -PROGRESS_BAR_WIDTH / 2.0;
+  let min = Pnt2::new(left, PROGRESS_BAR_TOP - PROGRESS_BAR_HEIGHT);
+  let max = Pnt2::new(left + PROGRESS_BAR_WIDTH, PROGRESS_BAR_TOP);
+  let filled = Pnt2::new(left + progress * PROGRESS_BAR_WIDTH, PROGRESS_BAR_TOP);
+
+  let mut vs = Vec::new();
+  vs.push_all(ColoredVertex::square(min, max, background));
+  if progress > 0.0 {
+    vs.push_all(ColoredVertex::square(min, filled, fill));
+  }
+  vs
+}
+
+#[test]
+fn crosshairs_are_counterclockwise_triangles() {
+  let color = Color4::of_rgba(0.0, 0.0, 0.0, 1.0);
+  for &style in [Dot, Cross, Circle].iter() {
+    let vs = vertices(style, color);
+    assert!(!vs.is_empty());
+    assert_eq!(vs.len() % 3, 0);
+    // culling is on, so clockwise triangles would be invisible.
+    for t in vs.as_slice().chunks(3) {
+      let (a, b, c) = (t[0].position, t[1].position, t[2].position);
+      let z = (b.x - a.x) * (c.y - a.y) - (b.y - a.y) * (c.x - a.x);
+      assert!(z > 0.0, "{} has a clockwise triangle", style);
+    }
+  }
+}
+
+#[test]
+fn crosshair_names_round_trip() {
+  for &style in [Dot, Cross, Circle].iter() {
+    assert_eq!(CrosshairStyle::from_name(style.name()), Some(style));
+  }
+  assert_eq!(CrosshairStyle::from_name("star"), None);
+}
+
+#[test]
+fn progress_bar_fills_from_the_left() {
+  let background = Color4::of_rgba(0.0, 0.0, 0.0, 1.0);
+  let fill = Color4::of_rgba(1.0, 1.0, 1.0, 1.0);
+  assert_eq!(progress_bar(0.0, background, fill).len(), 6);
+
+  let right_edge = |progress: GLfloat| {
+    progress_bar(progress, background, fill).slice_from(6).iter()
+      .map(|v| v.position.x)
+      .fold(-1.0, |a: GLfloat, b| a.max(b))
+  };
+  let left = -PROGRESS_BAR_WIDTH / 2.0;
+  assert!((right_edge(0.5) - (left + PROGRESS_BAR_WIDTH / 2.0)).abs() < 0.0001);
+  assert!((right_edge(2.0) - (left + PROGRESS_BAR_WIDTH)).abs() < 0.0001);
+}
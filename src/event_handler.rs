@@ -1,18 +1,27 @@
 use common::*;
+use console;
 use event::{Event, Update, Input, Render};
+use gl::types::*;
 use glw::color::Color4;
 use glw::vertex::ColoredVertex;
 use input;
-use input::{Press,Release,Move,Keyboard,Mouse,MouseCursor};
+use input::{Press,Release,Move,Focus,Keyboard,Mouse,MouseCursor};
+use menu;
 use nalgebra::Vec3;
+use player::Player;
+use profiler;
 use render::render;
 use sdl2_game_window::{WindowSDL2};
 use sdl2::mouse;
-use state::App;
+use state::{App, AMBIENT_LIGHT_STEP};
 use stopwatch;
 use stopwatch::*;
+use std::collections::HashSet;
 use std::f32::consts::PI;
-use update::update;
+use update::{entities_in_front, update};
+
+// how far each press moves the camera in nudge mode
+static NUDGE_STEP: GLfloat = 0.1;
 
 #[inline]
 fn swap_remove_first<T: PartialEq + Copy>(v: &mut Vec<T>, t: T) {
@@ -25,13 +34,17 @@ fn swap_remove_first<T: PartialEq + Copy>(v: &mut Vec<T>, t: T) {
 pub fn handle_event<'a>(app: &mut App<'a>, game_window: &mut WindowSDL2, event: Event) {
   match event {
     Render(_) => render(app),
-    Update(_) => update(app),
+    Update(_) => {
+      update(app);
+      profiler::sample(app);
+    },
     Input(ref i) => match *i {
       Press(Keyboard(key)) => key_press(app, key),
       Release(Keyboard(key)) => key_release(app, key),
       Press(Mouse(button)) => mouse_press(app, button),
       Release(Mouse(button)) => mouse_release(app, button),
       Move(MouseCursor(x, y)) => mouse_move(app, game_window, x, y),
+      Focus(has_focus) => app.set_focus(has_focus),
       _ => {},
     },
   }
@@ -39,7 +52,42 @@ pub fn handle_event<'a>(app: &mut App<'a>, game_window: &mut WindowSDL2, event:
 
 fn key_press<'a>(app: &mut App<'a>, key: input::keyboard::Key) {
   time!(app.timers.deref(), "event.key_press", || {
+    if app.menu.is_open() {
+      menu_key_press(app, key);
+      return;
+    }
+
+    if app.console.is_open {
+      console_key_press(app, key);
+      return;
+    }
+
+    if app.nudge_mode {
+      match nudge_direction(key) {
+        None => {},
+        Some(v) => {
+          app.player.nudge(&mut app.physics, v * NUDGE_STEP);
+          return;
+        },
+      }
+    }
+
+    match app.player2 {
+      None => {},
+      Some(ref mut player2) => {
+        if player2_key_press(player2, key) {
+          return;
+        }
+      },
+    }
+
     match key {
+      input::keyboard::Escape => {
+        app.open_menu();
+      },
+      input::keyboard::Backquote => {
+        app.toggle_console();
+      },
       input::keyboard::A => {
         app.player.walk(Vec3::new(-1.0, 0.0, 0.0));
       },
@@ -47,11 +95,7 @@ fn key_press<'a>(app: &mut App<'a>, key: input::keyboard::Key) {
         app.player.walk(Vec3::new(1.0, 0.0, 0.0));
       },
       input::keyboard::Space => {
-        if !app.player.is_jumping {
-          app.player.is_jumping = true;
-          // this 0.3 is duplicated in a few places
-          app.player.accel.y = app.player.accel.y + 0.3;
-        }
+        app.player.start_jump();
       },
       input::keyboard::W => {
         app.player.walk(Vec3::new(0.0, 0.0, -1.0));
@@ -67,6 +111,10 @@ fn key_press<'a>(app: &mut App<'a>, key: input::keyboard::Key) {
         app.player.rotate_vertical(PI / 12.0),
       input::keyboard::Down =>
         app.player.rotate_vertical(-PI / 12.0),
+      input::keyboard::Q =>
+        app.player.rotate_roll(PI / 12.0),
+      input::keyboard::E =>
+        app.player.rotate_roll(-PI / 12.0),
       input::keyboard::M => {
         let updates = [
           ColoredVertex {
@@ -86,13 +134,159 @@ fn key_press<'a>(app: &mut App<'a>, key: input::keyboard::Key) {
       input::keyboard::L => {
         app.render_outlines = !app.render_outlines;
       }
+      input::keyboard::J => {
+        app.toggle_color_jitter();
+      }
+      input::keyboard::F2 => {
+        app.toggle_split_screen();
+      }
+      input::keyboard::F3 => {
+        app.show_debug = !app.show_debug;
+      }
+      input::keyboard::F11 => {
+        app.toggle_timelapse();
+      }
+      input::keyboard::F12 => {
+        app.request_screenshot();
+      }
+      input::keyboard::F4 => {
+        profiler::toggle(app);
+      }
+      input::keyboard::R => {
+        app.toggle_measure();
+      }
+      input::keyboard::N => {
+        app.toggle_nudge_mode();
+      }
+      input::keyboard::H => {
+        app.show_help = !app.show_help;
+      }
+      input::keyboard::B => {
+        app.toggle_backfaces();
+      }
+      input::keyboard::K => {
+        app.toggle_chunk_outlines();
+      }
+      input::keyboard::V => {
+        app.toggle_motion_blur();
+      }
+      input::keyboard::G => {
+        app.toggle_target_highlight();
+      }
+      input::keyboard::P => {
+        app.toggle_physics_outlines();
+      }
+      input::keyboard::F => {
+        match entities_in_front(app).into_iter().next() {
+          None => app.set_selection(HashSet::new()),
+          Some(id) => {
+            app.select_connected(id);
+          },
+        }
+      }
+      input::keyboard::Delete => {
+        app.remove_selection();
+      }
+      input::keyboard::T => {
+        app.tour_next();
+      }
+      input::keyboard::LCtrl => {
+        app.ctrl_held = true;
+      }
+      input::keyboard::Equals => {
+        app.adjust_ambient(AMBIENT_LIGHT_STEP);
+      }
+      input::keyboard::Minus => {
+        app.adjust_ambient(-AMBIENT_LIGHT_STEP);
+      }
       _ => {},
     }
   })
 }
 
+/// In nudge mode, the arrow keys move the camera horizontally and page
+/// up/down move it vertically. Returns the player-relative direction to move
+/// in, or None if `key` isn't a nudge key.
+fn nudge_direction(key: input::keyboard::Key) -> Option<Vec3<GLfloat>> {
+  match key {
+    input::keyboard::Left => Some(Vec3::new(-1.0, 0.0, 0.0)),
+    input::keyboard::Right => Some(Vec3::new(1.0, 0.0, 0.0)),
+    input::keyboard::Up => Some(Vec3::new(0.0, 0.0, -1.0)),
+    input::keyboard::Down => Some(Vec3::new(0.0, 0.0, 1.0)),
+    input::keyboard::PageUp => Some(Vec3::new(0.0, 1.0, 0.0)),
+    input::keyboard::PageDown => Some(Vec3::new(0.0, -1.0, 0.0)),
+    _ => None,
+  }
+}
+
+fn menu_key_press<'a>(app: &mut App<'a>, key: input::keyboard::Key) {
+  match key {
+    input::keyboard::Escape => {
+      app.close_menu();
+    },
+    input::keyboard::Up => {
+      app.menu.move_highlight(-1);
+    },
+    input::keyboard::Down => {
+      app.menu.move_highlight(1);
+    },
+    input::keyboard::Return => {
+      app.menu.selected().map(|item| menu_select(app, item));
+    },
+    _ => {},
+  }
+}
+
+fn console_key_press<'a>(app: &mut App<'a>, key: input::keyboard::Key) {
+  match key {
+    input::keyboard::Backquote | input::keyboard::Escape => {
+      app.toggle_console();
+    },
+    input::keyboard::Return => {
+      app.submit_console();
+    },
+    input::keyboard::Backspace => {
+      app.console.input.pop();
+      app.refresh_console();
+    },
+    key => {
+      match console::key_to_char(key) {
+        None => {},
+        Some(c) => {
+          app.console.input.push(c);
+          app.refresh_console();
+        },
+      }
+    },
+  }
+}
+
+fn menu_select<'a>(app: &mut App<'a>, item: menu::MenuItem) {
+  match item {
+    menu::Resume => app.close_menu(),
+    menu::Quit => app.quit = true,
+  }
+}
+
 fn key_release<'a>(app: &mut App<'a>, key: input::keyboard::Key) {
   time!(app.timers.deref(), "event.key_release", || {
+    if app.menu.is_open() || app.console.is_open {
+      return;
+    }
+
+    if app.nudge_mode && nudge_direction(key).is_some() {
+      return;
+    }
+
+    match app.player2 {
+      None => {},
+      Some(ref mut player2) => {
+        if player2_key_release(player2, key) {
+          return;
+        }
+      },
+    }
+
     match key {
       // accelerations are negated from those in key_press.
       input::keyboard::A => {
@@ -102,11 +296,7 @@ fn key_release<'a>(app: &mut App<'a>, key: input::keyboard::Key) {
         app.player.walk(Vec3::new(-1.0, 0.0, 0.0));
       },
       input::keyboard::Space => {
-        if app.player.is_jumping {
-          app.player.is_jumping = false;
-          // this 0.3 is duplicated in a few places
-          app.player.accel.y = app.player.accel.y - 0.3;
-        }
+        app.player.stop_jump();
       },
       input::keyboard::W => {
         app.player.walk(Vec3::new(0.0, 0.0, 1.0));
@@ -114,21 +304,66 @@ fn key_release<'a>(app: &mut App<'a>, key: input::keyboard::Key) {
       input::keyboard::S => {
         app.player.walk(Vec3::new(0.0, 0.0, -1.0));
       },
+      input::keyboard::LCtrl => {
+        app.ctrl_held = false;
+      },
       _ => { }
     }
   })
 }
 
+/// Handle a key press for the second split-screen player, who walks with Up
+/// and Down, turns with Left and Right, and jumps with right shift.
+/// Returns false if `key` isn't one of theirs.
+fn player2_key_press(player2: &mut Player, key: input::keyboard::Key) -> bool {
+  match key {
+    input::keyboard::Up => player2.walk(Vec3::new(0.0, 0.0, -1.0)),
+    input::keyboard::Down => player2.walk(Vec3::new(0.0, 0.0, 1.0)),
+    input::keyboard::Left => player2.rotate_lateral(PI / 12.0),
+    input::keyboard::Right => player2.rotate_lateral(-PI / 12.0),
+    input::keyboard::RShift => player2.start_jump(),
+    _ => return false,
+  }
+  true
+}
+
+fn player2_key_release(player2: &mut Player, key: input::keyboard::Key) -> bool {
+  match key {
+    // accelerations are negated from those in player2_key_press.
+    input::keyboard::Up => player2.walk(Vec3::new(0.0, 0.0, 1.0)),
+    input::keyboard::Down => player2.walk(Vec3::new(0.0, 0.0, -1.0)),
+    input::keyboard::Left | input::keyboard::Right => {},
+    input::keyboard::RShift => player2.stop_jump(),
+    _ => return false,
+  }
+  true
+}
+
 fn mouse_move<'a>(app: &mut App<'a>, w: &mut WindowSDL2, x: f64, y: f64) {
   time!(app.timers.deref(), "event.mouse_move", || {
+    app.cursor_position = window_to_hud(x, y);
+    if app.menu.is_open() {
+      app.menu.hover(app.cursor_position);
+      return;
+    }
+
+    if !app.has_focus {
+      return;
+    }
+
     let (cx, cy) = (WINDOW_WIDTH as f32 / 2.0, WINDOW_HEIGHT as f32 / 2.0);
     // args.y = h - args.y;
     // dy = args.y - cy;
     //  => dy = cy - args.y;
-    let (dx, dy) = (x as f32 - cx, cy - y as f32);
-    let (rx, ry) = (dx * -3.14 / 2048.0, dy * 3.14 / 1600.0);
-    app.player.rotate_lateral(rx);
-    app.player.rotate_vertical(ry);
+    if app.ignore_next_mouse_move {
+      // just recenter the cursor.
+      app.ignore_next_mouse_move = false;
+    } else {
+      let (dx, dy) = (x as f32 - cx, cy - y as f32);
+      let (rx, ry) = (-dx * app.settings.sensitivity_x, dy * app.settings.sensitivity_y);
+      app.player.rotate_lateral(rx);
+      app.player.rotate_vertical(ry);
+    }
 
     mouse::warp_mouse_in_window(
       &w.window,
@@ -140,6 +375,25 @@ fn mouse_move<'a>(app: &mut App<'a>, w: &mut WindowSDL2, x: f64, y: f64) {
 
 fn mouse_press<'a>(app: &mut App<'a>, button: input::mouse::Button) {
   time!(app.timers.deref(), "event.mouse_press", || {
+    if app.menu.is_open() {
+      if button == input::mouse::Left {
+        app.menu.item_at(app.cursor_position).map(|item| menu_select(app, item));
+      }
+      return;
+    }
+
+    // while measuring, clicks pick points rather than removing terrain.
+    if app.measure.is_active() && button == input::mouse::Left {
+      entities_in_front(app).into_iter().next().map(|id| app.measure_pick(id));
+      return;
+    }
+
+    // control-click flies to the clicked terrain instead of removing it.
+    if app.ctrl_held && button == input::mouse::Left {
+      entities_in_front(app).into_iter().next().map(|id| app.fly_to(id));
+      return;
+    }
+
     app.mouse_buttons_pressed.push(button);
   })
 }
@@ -1,16 +1,50 @@
 use std::path::Path;
 use ttf;
 
+static SANS_PATH: &'static str = "fonts/Open_Sans/OpenSans-Regular.ttf";
+static MONO_PATH: &'static str = "fonts/Ubuntu_Mono/UbuntuMono-Regular.ttf";
+
+/// The fonts text can be drawn in.
+#[deriving(Show, Clone, PartialEq, Eq)]
+pub enum FontId {
+  /// the body text of menus
+  Sans,
+  /// readouts, the console and anything else that should line up
+  Mono,
+  /// headings
+  Title,
+}
+
+// Each font's file and point size, in the order of `FontId`.
+static FONTS: [(FontId, &'static str, uint), ..3] = [
+  (Sans, SANS_PATH, 11),
+  (Mono, MONO_PATH, 11),
+  (Title, SANS_PATH, 18),
+];
+
 pub struct FontLoader {
-  pub sans : ttf::Font,
-  pub mono : ttf::Font,
+  // indexed by `FontId`
+  fonts: Vec<ttf::Font>,
 }
 
 impl FontLoader {
   pub fn new() -> FontLoader {
     FontLoader {
-      sans : ttf::Font::new(&Path::new("fonts/Open_Sans/OpenSans-Regular.ttf"), 11),
-      mono : ttf::Font::new(&Path::new("fonts/Ubuntu_Mono/UbuntuMono-Regular.ttf"), 11),
+      fonts:
+        FONTS.iter()
+          .map(|&(_, path, size)| ttf::Font::new(&Path::new(path), size))
+          .collect(),
     }
   }
+
+  pub fn get(&self, id: FontId) -> &ttf::Font {
+    &self.fonts[id as uint]
+  }
+}
+
+#[test]
+fn fonts_are_listed_in_order() {
+  for (i, &(id, _, _)) in FONTS.iter().enumerate() {
+    assert_eq!(id as uint, i);
+  }
 }
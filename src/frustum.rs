@@ -0,0 +1,72 @@
+//! The camera's view frustum, for finding what it can see.
+
+use gl::types::*;
+use nalgebra::{Mat4, Vec3};
+use ncollide::bounding_volume::aabb::AABB;
+
+/// The six planes bounding what a projection matrix maps into view.
+pub struct Frustum {
+  // (normal, offset) for each plane; a point p is on the inside of a plane
+  // when `normal . p + offset >= 0`.
+  planes: [(Vec3<GLfloat>, GLfloat), ..6],
+}
+
+impl Frustum {
+  /// The frustum of a combined projection and view matrix: everything it maps
+  /// into [-1, 1] on every axis.
+  pub fn from_matrix(m: &Mat4<GLfloat>) -> Frustum {
+    let rows = [
+      (m.m11, m.m12, m.m13, m.m14),
+      (m.m21, m.m22, m.m23, m.m24),
+      (m.m31, m.m32, m.m33, m.m34),
+    ];
+    let (wa, wb, wc, wd) = (m.m41, m.m42, m.m43, m.m44);
+    // each axis gives two planes: -w <= x and x <= w.
+    let plane = |i: uint, sign: GLfloat| {
+      let (a, b, c, d) = rows[i];
+      (Vec3::new(wa + sign * a, wb + sign * b, wc + sign * c), wd + sign * d)
+    };
+    Frustum {
+      planes: [
+        plane(0, 1.0), plane(0, -1.0),
+        plane(1, 1.0), plane(1, -1.0),
+        plane(2, 1.0), plane(2, -1.0),
+      ],
+    }
+  }
+
+  /// Whether any of `bounds` might be inside the frustum. Boxes near the
+  /// corners can be let through, but nothing inside is ever rejected.
+  pub fn intersects(&self, bounds: &AABB) -> bool {
+    let (mins, maxs) = (bounds.mins(), bounds.maxs());
+    self.planes.iter().all(|&(ref n, d)| {
+      // the corner furthest to the inside of this plane.
+      let x = if n.x >= 0.0 { maxs.x } else { mins.x };
+      let y = if n.y >= 0.0 { maxs.y } else { mins.y };
+      let z = if n.z >= 0.0 { maxs.z } else { mins.z };
+      n.x * x + n.y * y + n.z * z + d >= 0.0
+    })
+  }
+}
+
+#[test]
+fn boxes_outside_are_rejected() {
+  use nalgebra::{Eye, Pnt3};
+
+  let cube = |min: GLfloat, max: GLfloat| AABB::new(Pnt3::new(min, min, min), Pnt3::new(max, max, max));
+  let identity: Mat4<GLfloat> = Eye::new_identity(4);
+  let frustum = Frustum::from_matrix(&identity);
+  assert!(frustum.intersects(&cube(-0.5, 0.5)));
+  assert!(frustum.intersects(&cube(0.5, 2.0)));
+  assert!(frustum.intersects(&cube(-5.0, 5.0)));
+  assert!(!frustum.intersects(&cube(2.0, 3.0)));
+  assert!(!frustum.intersects(&cube(-3.0, -2.0)));
+
+  // a perspective camera looks down -z.
+  let frustum = Frustum::from_matrix(&::glw::camera::perspective(1.0, 1.0, 0.1, 100.0));
+  let at = |x: GLfloat, z: GLfloat| AABB::new(Pnt3::new(x - 0.5, -0.5, z - 0.5), Pnt3::new(x + 0.5, 0.5, z + 0.5));
+  assert!(frustum.intersects(&at(0.0, -10.0)));
+  assert!(!frustum.intersects(&at(0.0, 10.0)));
+  assert!(!frustum.intersects(&at(50.0, -10.0)));
+  assert!(!frustum.intersects(&at(0.0, -200.0)));
+}
@@ -46,7 +46,14 @@ pub fn from_axis_angle4(axis: Vec3<GLfloat>, angle: GLfloat) -> Mat4<GLfloat> {
 }
 
 /// Create a 3D perspective initialization matrix.
+/// A non-positive aspect ratio (e.g. a zero-width window) or `near >= far`
+/// would produce NaNs or an inverted depth range, so those give the identity.
 pub fn perspective(fovy: GLfloat, aspect: GLfloat, near: GLfloat, far: GLfloat) -> Mat4<GLfloat> {
+  // written so that NaNs fail the check too.
+  if !(aspect > 0.0) || !(near < far) {
+    return Eye::new_identity(4);
+  }
+
   Mat4 {
     m11: fovy / aspect, m12: 0.0,   m13: 0.0,                         m14: 0.0,
     m21: 0.0,           m22: fovy,  m23: 0.0,                         m24: 0.0,
@@ -104,3 +111,21 @@ impl Camera {
     self.rotation = self.rotation * from_axis_angle4(v, -r);
   }
 }
+
+#[test]
+fn degenerate_perspective_is_identity() {
+  let identity: Mat4<GLfloat> = Eye::new_identity(4);
+  assert!(perspective(1.0, 0.0, 0.1, 100.0) == identity);
+  assert!(perspective(1.0, -1.0, 0.1, 100.0) == identity);
+  assert!(perspective(1.0, 1.0, 100.0, 0.1) == identity);
+  assert!(perspective(1.0, 1.0, 1.0, 1.0) == identity);
+  assert!(perspective(1.0, 0.0 / 0.0, 0.1, 100.0) == identity);
+}
+
+#[test]
+fn valid_perspective_is_finite() {
+  let m = perspective(1.0, 4.0 / 3.0, 0.1, 100.0);
+  for x in [m.m11, m.m22, m.m33, m.m34, m.m43].iter() {
+    assert!(x.is_finite());
+  }
+}
@@ -1,5 +1,7 @@
 //! RGBA color.
 
+use gl::types::GLfloat;
+
 #[deriving(Show, Clone, Copy, PartialEq)]
 /// A color with red, green, blue, and alpha channels.
 pub struct Color4<T> {
@@ -20,3 +22,17 @@ impl<T: Copy> Color4<T> {
   }
 }
 
+impl Color4<GLfloat> {
+  /// Saturate each channel into [0, 1], e.g. for colors computed by math
+  /// that may over- or undershoot.
+  pub fn clamp01(&self) -> Color4<GLfloat> {
+    let clamp = |x: GLfloat| if x < 0.0 { 0.0 } else if x > 1.0 { 1.0 } else { x };
+    Color4::of_rgba(clamp(self.r), clamp(self.g), clamp(self.b), clamp(self.a))
+  }
+}
+
+#[test]
+fn clamp01_saturates_each_channel() {
+  let c = Color4::of_rgba(-0.5, 0.25, 1.5, 1.0).clamp01();
+  assert_eq!(c, Color4::of_rgba(0.0, 0.25, 1.0, 1.0));
+}
@@ -3,6 +3,7 @@ use gl::types::*;
 use gl_context::*;
 use shader::*;
 use std::cell::RefCell;
+use std::default::Default;
 use std::mem;
 use std::ptr;
 use std::rc::Rc;
@@ -16,6 +17,32 @@ pub fn glGetAttribLocation(shader_program: GLuint, name: &str) -> GLint {
   name.with_c_str(|ptr| unsafe { gl::GetAttribLocation(shader_program, ptr) })
 }
 
+/// A hint to the driver about how a buffer's contents will be used.
+pub enum BufferUsage {
+  /// Set once and drawn many times.
+  StaticDraw,
+  /// Changed repeatedly and drawn many times.
+  DynamicDraw,
+  /// Set once and drawn a few times.
+  StreamDraw,
+}
+
+impl BufferUsage {
+  fn to_enum(&self) -> GLenum {
+    match self {
+      &StaticDraw  => gl::STATIC_DRAW,
+      &DynamicDraw => gl::DYNAMIC_DRAW,
+      &StreamDraw  => gl::STREAM_DRAW,
+    }
+  }
+}
+
+impl Default for BufferUsage {
+  fn default() -> BufferUsage {
+    DynamicDraw
+  }
+}
+
 /// Fixed-size VRAM buffer for individual bytes.
 pub struct GLByteBuffer {
   pub gl_id: u32,
@@ -28,25 +55,37 @@ pub struct GLByteBuffer {
 impl GLByteBuffer {
   /// Creates a new array of objects on the GPU.
   /// capacity is provided in units of size slice_span.
-  pub fn new(capacity: uint) -> GLByteBuffer {
-    let mut gl_id = 0;
-
+  pub fn new(capacity: uint, usage: BufferUsage) -> GLByteBuffer {
     unsafe {
-      gl::GenBuffers(1, &mut gl_id);
+      GLByteBuffer::allocate(capacity, ptr::null(), 0, usage)
     }
+  }
+
+  /// Creates a buffer holding exactly the `count` bytes at `vs`. The driver is
+  /// told the contents won't change.
+  pub unsafe fn from_bytes(vs: *const u8, count: uint) -> GLByteBuffer {
+    GLByteBuffer::allocate(count, vs, count, StaticDraw)
+  }
+
+  /// Allocates `capacity` bytes on the GPU, filling the first `length` from
+  /// `vs` (which may be null if `length` is zero).
+  unsafe fn allocate(capacity: uint, vs: *const u8, length: uint, usage: BufferUsage) -> GLByteBuffer {
+    assert!(length <= capacity);
+
+    let mut gl_id = 0;
+
+    gl::GenBuffers(1, &mut gl_id);
 
     assert!(gl_id != 0);
 
     gl::BindBuffer(gl::ARRAY_BUFFER, gl_id);
 
-    unsafe {
-      gl::BufferData(
-        gl::ARRAY_BUFFER,
-        capacity as GLsizeiptr,
-        ptr::null(),
-        gl::DYNAMIC_DRAW,
-      );
-    }
+    gl::BufferData(
+      gl::ARRAY_BUFFER,
+      capacity as GLsizeiptr,
+      mem::transmute(vs),
+      usage.to_enum(),
+    );
 
     match gl::GetError() {
       gl::NO_ERROR => {},
@@ -56,13 +95,17 @@ impl GLByteBuffer {
 
     GLByteBuffer {
       gl_id: gl_id,
-      length: 0,
+      length: length,
       capacity: capacity,
     }
   }
 
-  /// Add more data into this buffer.
+  /// Add more data into this buffer. Pushing nothing doesn't touch OpenGL.
   pub unsafe fn push(&mut self, vs: *const u8, count: uint) {
+    if count == 0 {
+      return;
+    }
+
     assert!(
       self.length + count <= self.capacity,
       "GLByteBuffer::push {} into a {}/{} full GLByteBuffer",
@@ -164,13 +207,27 @@ pub struct GLBuffer<T> {
 }
 
 impl<T> GLBuffer<T> {
-  pub fn new(capacity: uint) -> GLBuffer<T> {
+  pub fn new(capacity: uint, usage: BufferUsage) -> GLBuffer<T> {
     GLBuffer {
-      byte_buffer: GLByteBuffer::new(capacity * mem::size_of::<T>()),
+      byte_buffer: GLByteBuffer::new(capacity * mem::size_of::<T>(), usage),
       length: 0,
     }
   }
 
+  /// Creates a buffer holding exactly the contents of `vs`, for data that
+  /// won't change.
+  pub fn from_slice(vs: &[T]) -> GLBuffer<T> {
+    GLBuffer {
+      byte_buffer: unsafe {
+        GLByteBuffer::from_bytes(
+          mem::transmute(vs.as_ptr()),
+          mem::size_of::<T>() * vs.len(),
+        )
+      },
+      length: vs.len(),
+    }
+  }
+
   pub fn push(&mut self, vs: &[T]) {
     unsafe {
       self.byte_buffer.push(
@@ -200,6 +257,21 @@ impl<T> GLBuffer<T> {
   }
 }
 
+#[test]
+fn empty_push_is_a_no_op() {
+  // This buffer was never allocated, so any GL call on it would fail
+  // (there's no GL context in tests either).
+  let mut buffer: GLBuffer<f32> = GLBuffer {
+    byte_buffer: GLByteBuffer { gl_id: 0, length: 0, capacity: 0 },
+    length: 0,
+  };
+  buffer.push(&[]);
+  assert_eq!(buffer.length, 0);
+  assert_eq!(buffer.byte_buffer.length, 0);
+  // don't run the destructor, which calls into GL.
+  unsafe { mem::forget(buffer); }
+}
+
 pub enum DrawMode {
   Lines,
   Triangles,
@@ -291,13 +363,14 @@ impl<T> GLArray<T> {
     }
 
     assert!(mem::size_of::<T>() % attrib_span == 0);
+    let attrib_span = mem::size_of::<T>() / attrib_span;
 
     GLArray {
+      length: buffer.length * attrib_span,
       buffer: buffer,
       gl_id: gl_id,
       mode: mode.to_enum(),
-      attrib_span: mem::size_of::<T>() / attrib_span,
-      length: 0,
+      attrib_span: attrib_span,
     }
   }
 
@@ -318,13 +391,29 @@ impl<T> GLArray<T> {
   }
 
   /// Draw some subset of the triangle array.
-  pub fn draw_slice(&self, _gl: &GLContext, start: uint, len: uint) {
+  pub fn draw_slice(&self, gl: &GLContext, start: uint, len: uint) {
+    let mode = self.mode;
+    self.draw_slice_as(gl, start, len, mode);
+  }
+
+  /// Draw everything, as `mode` rather than the mode this array was made
+  /// with, e.g. to draw triangles' vertices as lines.
+  pub fn draw_with(&self, gl: &GLContext, mode: DrawMode) {
+    self.draw_slice_with(gl, 0, self.buffer.length, mode);
+  }
+
+  /// Draw some subset of the array as `mode`.
+  pub fn draw_slice_with(&self, gl: &GLContext, start: uint, len: uint, mode: DrawMode) {
+    self.draw_slice_as(gl, start, len, mode.to_enum());
+  }
+
+  fn draw_slice_as(&self, _gl: &GLContext, start: uint, len: uint, mode: GLenum) {
     assert!(start + len <= self.length);
 
     gl::BindVertexArray(self.gl_id);
     gl::BindBuffer(gl::ARRAY_BUFFER, self.buffer.byte_buffer.gl_id);
 
-    gl::DrawArrays(self.mode, (start * self.attrib_span) as i32, (len * self.attrib_span) as i32);
+    gl::DrawArrays(mode, (start * self.attrib_span) as i32, (len * self.attrib_span) as i32);
 
     match gl::GetError() {
       gl::NO_ERROR => {},
@@ -3,6 +3,7 @@ use cstr_cache;
 use gl;
 use gl::types::*;
 use shader::Shader;
+use std::default::Default;
 use std::raw;
 use std::mem;
 use std::ptr;
@@ -27,6 +28,55 @@ unsafe fn from_c_str<'a>(s: *const u8) -> &'a str {
   str::raw::from_utf8(mem::transmute(as_slice))
 }
 
+/// The order in which a triangle's vertices appear on screen.
+pub enum Winding {
+  /// Clockwise.
+  CW,
+  /// Counterclockwise.
+  CCW,
+}
+
+impl Winding {
+  fn to_enum(&self) -> GLenum {
+    match self {
+      &CW  => gl::CW,
+      &CCW => gl::CCW,
+    }
+  }
+}
+
+impl Default for Winding {
+  fn default() -> Winding {
+    CCW
+  }
+}
+
+/// Which faces of triangles are culled.
+pub enum CullFace {
+  #[allow(missing_doc)]
+  Front,
+  #[allow(missing_doc)]
+  Back,
+  /// Cull every triangle (points and lines are still drawn).
+  FrontAndBack,
+}
+
+impl CullFace {
+  fn to_enum(&self) -> GLenum {
+    match self {
+      &Front        => gl::FRONT,
+      &Back         => gl::BACK,
+      &FrontAndBack => gl::FRONT_AND_BACK,
+    }
+  }
+}
+
+impl Default for CullFace {
+  fn default() -> CullFace {
+    Back
+  }
+}
+
 /// A handle to an OpenGL context. Only create one of these per thread.
 #[deriving(Send)]
 pub struct GLContext {
@@ -44,12 +94,19 @@ impl GLContext {
   }
 
   /// Stops the processing of any triangles hidden from view when rendering.
-  pub fn enable_culling(&self) {
-    gl::FrontFace(gl::CCW);
-    gl::CullFace(gl::BACK);
+  /// Triangles wound in the `front` direction are front faces; `cull` picks
+  /// which faces are discarded.
+  pub fn enable_culling(&self, front: Winding, cull: CullFace) {
+    gl::FrontFace(front.to_enum());
+    gl::CullFace(cull.to_enum());
     gl::Enable(gl::CULL_FACE);
   }
 
+  /// Draw both front and back faces.
+  pub fn disable_culling(&self) {
+    gl::Disable(gl::CULL_FACE);
+  }
+
   #[allow(missing_doc)]
   pub fn enable_alpha_blending(&self) {
     gl::Enable(gl::BLEND);
@@ -82,6 +139,32 @@ impl GLContext {
     );
   }
 
+  /// Set the region of the window, in pixels from the bottom left, that
+  /// rendering maps to.
+  pub fn set_viewport(&self, x: uint, y: uint, width: uint, height: uint) {
+    gl::Viewport(x as GLint, y as GLint, width as GLsizei, height as GLsizei);
+  }
+
+  /// Clip rendering to a `width` by `height` rectangle of the window, with its
+  /// bottom left corner at (x, y) pixels, while running the closure. The
+  /// scissor test and box are put back the way they were afterwards, so calls
+  /// can be nested.
+  pub fn with_scissor<T>(&self, x: uint, y: uint, width: uint, height: uint, f: |&GLContext| -> T) -> T {
+    let was_enabled = gl::IsEnabled(gl::SCISSOR_TEST) == gl::TRUE;
+    let mut old_box: [GLint, ..4] = [0, 0, 0, 0];
+    unsafe {
+      gl::GetIntegerv(gl::SCISSOR_BOX, old_box.as_mut_ptr());
+    }
+
+    gl::Enable(gl::SCISSOR_TEST);
+    gl::Scissor(x as GLint, y as GLint, width as GLsizei, height as GLsizei);
+    let r = f(self);
+
+    gl::Scissor(old_box[0], old_box[1], old_box[2], old_box[3]);
+    if !was_enabled { gl::Disable(gl::SCISSOR_TEST); }
+    r
+  }
+
   /// Replace the current OpenGL buffer with all pixels of the
   /// "background color", as set with `set_background_color`.
   pub fn clear_buffer(&self) {
@@ -147,6 +230,27 @@ impl GLContext {
     }
   }
 
+  /// Returns the RGB bytes of a `width` by `height` rectangle of the window,
+  /// with its bottom left corner at (x, y). Rows are packed bottom row first,
+  /// the way OpenGL numbers them.
+  pub fn read_pixels_rect(&self, x: uint, y: uint, width: uint, height: uint) -> Vec<u8> {
+    let mut pixels = Vec::from_elem(3 * width * height, 0u8);
+    gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+    unsafe {
+      gl::ReadPixels(
+        x as i32, y as i32, width as i32, height as i32,
+        gl::RGB, gl::UNSIGNED_BYTE, mem::transmute(pixels.as_mut_ptr()),
+      );
+    }
+
+    match gl::GetError() {
+      gl::NO_ERROR => {},
+      err => fail!("OpenGL error 0x{:x} reading pixels", err),
+    }
+
+    pixels
+  }
+
   /// Prints opengl version information.
   pub fn print_stats(&self) {
     let opengl_version = gl::GetString(gl::VERSION);
@@ -1,4 +1,5 @@
 use camera::Camera;
+use color::Color4;
 use gl;
 use gl::types::*;
 use gl_context::GLContext;
@@ -110,9 +111,34 @@ impl Shader {
     });
   }
 
+  /// Sets the `vec4` color uniform `name` in some shader.
+  pub fn set_color(&mut self, gl: &mut GLContext, name: &'static str, color: Color4<GLfloat>) {
+    self.with_uniform_location(gl, name, |loc| {
+      gl::Uniform4f(loc, color.r, color.g, color.b, color.a);
+    });
+  }
+
+  /// Sets the `float` uniform `name` in some shader.
+  pub fn set_float(&mut self, gl: &mut GLContext, name: &'static str, value: GLfloat) {
+    self.with_uniform_location(gl, name, |loc| {
+      gl::Uniform1f(loc, value);
+    });
+  }
+
+  /// Turns dithering on or off in a shader with a `float dither` uniform,
+  /// which it should treat as a flag.
+  pub fn set_dither(&mut self, gl: &mut GLContext, enabled: bool) {
+    self.set_float(gl, "dither", if enabled { 1.0 } else { 0.0 });
+  }
+
   /// Sets the variable `projection_matrix` in some shader.
   pub fn set_projection_matrix(&mut self, gl: &mut GLContext, m: &Mat4<GLfloat>) {
-    self.with_uniform_location(gl, "projection_matrix", |loc| {
+    self.set_matrix(gl, "projection_matrix", m);
+  }
+
+  /// Sets the `mat4` uniform `name` in some shader.
+  pub fn set_matrix(&mut self, gl: &mut GLContext, name: &'static str, m: &Mat4<GLfloat>) {
+    self.with_uniform_location(gl, name, |loc| {
       unsafe {
         let p = mem::transmute(m);
         gl::UniformMatrix4fv(loc, 1, 0, p);
@@ -1,6 +1,6 @@
 use gl;
 use gl::types::*;
-use gl_buffer::GLBuffer;
+use gl_buffer::{GLBuffer, DynamicDraw};
 use gl_context::GLContext;
 use std::default::Default;
 
@@ -40,7 +40,52 @@ pub struct Texture {
   pub gl_id: GLuint,
 }
 
+/// The order cubemap faces are given in: +x, -x, +y, -y, +z, -z.
+pub static CUBE_FACES: [GLenum, ..6] = [
+  gl::TEXTURE_CUBE_MAP_POSITIVE_X,
+  gl::TEXTURE_CUBE_MAP_NEGATIVE_X,
+  gl::TEXTURE_CUBE_MAP_POSITIVE_Y,
+  gl::TEXTURE_CUBE_MAP_NEGATIVE_Y,
+  gl::TEXTURE_CUBE_MAP_POSITIVE_Z,
+  gl::TEXTURE_CUBE_MAP_NEGATIVE_Z,
+];
+
 impl Texture {
+  /// Make a cubemap from six square `size` by `size` images, in the order of
+  /// `CUBE_FACES`, each packed top row first as `format` (`gl::RGB` or
+  /// `gl::RGBA`) bytes. It's left bound to the active texture unit.
+  pub fn new_cube(_gl: &GLContext, size: uint, format: GLenum, faces: &[Vec<u8>]) -> Texture {
+    assert!(faces.len() == CUBE_FACES.len(), "a cubemap needs {} faces, got {}", CUBE_FACES.len(), faces.len());
+
+    let mut gl_id = 0;
+    unsafe {
+      gl::GenTextures(1, &mut gl_id);
+    }
+    gl::BindTexture(gl::TEXTURE_CUBE_MAP, gl_id);
+    gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+    for (&target, face) in CUBE_FACES.iter().zip(faces.iter()) {
+      unsafe {
+        gl::TexImage2D(
+          target, 0, format as GLint, size as GLsizei, size as GLsizei, 0,
+          format, gl::UNSIGNED_BYTE, face.as_ptr() as *const GLvoid,
+        );
+      }
+    }
+    gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_MIN_FILTER, gl::LINEAR as GLint);
+    gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_MAG_FILTER, gl::LINEAR as GLint);
+    // clamp, so the seams between faces don't show.
+    gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as GLint);
+    gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as GLint);
+    gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_WRAP_R, gl::CLAMP_TO_EDGE as GLint);
+
+    match gl::GetError() {
+      gl::NO_ERROR => {},
+      err => fail!("OpenGL error 0x{:x} making a cubemap", err),
+    }
+
+    Texture { gl_id: gl_id }
+  }
+
   pub fn bind_2d(&self, _gl: &GLContext) {
     gl::BindTexture(gl::TEXTURE_2D, self.gl_id);
 
@@ -59,6 +104,15 @@ impl Texture {
       err => fail!("OpenGL error 0x{:x}", err),
     }
   }
+
+  pub fn bind_cube(&self, _gl: &GLContext) {
+    gl::BindTexture(gl::TEXTURE_CUBE_MAP, self.gl_id);
+
+    match gl::GetError() {
+      gl::NO_ERROR => {},
+      err => fail!("OpenGL error 0x{:x}", err),
+    }
+  }
 }
 
 impl Drop for Texture {
@@ -77,7 +131,7 @@ impl<T> BufferTexture<T> {
   pub fn new(_gl: &GLContext, format: GLenum, capacity: uint) -> BufferTexture<T> {
     // TODO: enforce that `format` matches T.
 
-    let buffer = GLBuffer::new(capacity);
+    let buffer = GLBuffer::new(capacity, DynamicDraw);
 
     let mut gl_id = 0;
     unsafe {
@@ -0,0 +1,99 @@
+//! The help screen: every key binding, listed over the scene.
+
+use common::*;
+use fontloader;
+use fontloader::FontLoader;
+use gl::types::*;
+use glw::color::Color4;
+use glw::gl_context::GLContext;
+use glw::shader::Shader;
+use nalgebra::Pnt2;
+use std::cell::RefCell;
+use std::rc::Rc;
+use text_overlay::{TextOverlay, TEXT_PANEL_COLOR};
+
+/// Each key binding, and what it does. Keep this in sync with
+/// `event_handler`.
+pub static BINDINGS: &'static [(&'static str, &'static str)] = &[
+  ("W/A/S/D", "move"),
+  ("Space", "jump"),
+  ("Mouse", "look around"),
+  ("Arrows", "turn"),
+  ("Left-click", "dig out a face (hold)"),
+  ("Ctrl + click", "fly to a face"),
+  ("F", "select the connected faces of the same type (nothing: deselect)"),
+  ("Delete", "remove the selected faces"),
+  ("T", "fly to the next face, in order of id"),
+  ("H", "show/hide this help"),
+  ("Escape", "open/close the menu"),
+  ("`", "open/close the console"),
+  ("R", "measure between two clicked things"),
+  ("M", "save the line of sight"),
+  ("Q/E", "roll the camera"),
+  ("J", "toggle shade variation within each type"),
+  ("N", "toggle nudge mode (arrows and Page Up/Down move the camera)"),
+  ("O", "toggle octree rendering"),
+  ("L", "toggle outline rendering"),
+  ("G", "toggle filling in whatever's under the crosshair"),
+  ("K", "toggle chunk boundaries around you"),
+  ("P", "toggle outlining every physics box near you"),
+  ("B", "toggle drawing back faces in magenta"),
+  ("V", "toggle motion blur"),
+  ("= / -", "brighten/darken the ambient light"),
+  ("F2", "add/remove a split-screen player"),
+  ("F3", "toggle debug readouts"),
+  ("F4", "toggle the profiler"),
+  ("F11", "start/stop saving a timelapse"),
+  ("F12", "save a screenshot"),
+];
+
+// vertical distance between lines, in HUD coordinates
+static LINE_SPACING: GLfloat = 0.08;
+
+/// The help text, one line per binding, with the actions lined up.
+pub fn help_lines() -> Vec<String> {
+  let width = BINDINGS.iter().map(|&(key, _)| key.len()).max().unwrap_or(0);
+  BINDINGS.iter()
+    .map(|&(key, action)| {
+      let padding = String::from_char(width - key.len(), ' ');
+      format!("{}{}  {}", key, padding, action)
+    })
+    .collect()
+}
+
+/// Make a text overlay holding the help screen.
+pub fn make_overlay(
+  gl: &GLContext,
+  shader: Rc<RefCell<Shader>>,
+  background_shader: Rc<RefCell<Shader>>,
+  font: &FontLoader,
+) -> TextOverlay {
+  let lines = help_lines();
+  let mut overlay = TextOverlay::new(gl, shader, background_shader, lines.len() + 1);
+  let aspect = WINDOW_WIDTH as GLfloat / WINDOW_HEIGHT as GLfloat;
+  let white = Color4::of_rgba(0xFF, 0xFF, 0xFF, 0xFF);
+
+  let mut y = 0.8;
+  let panel = Some(TEXT_PANEL_COLOR);
+  let title = overlay.add_line(Pnt2::new(0.2 - aspect, y), white, panel);
+  overlay.set_text(font.get(fontloader::Title), title, "Key bindings");
+  for line in lines.iter() {
+    y -= LINE_SPACING;
+    let id = overlay.add_line(Pnt2::new(0.2 - aspect, y), white, panel);
+    overlay.set_text(font.get(fontloader::Mono), id, line.as_slice());
+  }
+  overlay
+}
+
+#[test]
+fn help_lines_are_aligned() {
+  let lines = help_lines();
+  assert_eq!(lines.len(), BINDINGS.len());
+  let width = BINDINGS.iter().map(|&(key, _)| key.len()).max().unwrap();
+  for (line, &(key, action)) in lines.iter().zip(BINDINGS.iter()) {
+    assert!(line.as_slice().starts_with(key));
+    assert!(line.as_slice().ends_with(action));
+    // every action starts in the same column.
+    assert_eq!(line.len() - action.len(), width + 2);
+  }
+}
@@ -0,0 +1,38 @@
+//! Hooks for scripting the world without changing the update loop itself.
+
+use glw::gl_context::GLContext;
+use glw::shader::Shader;
+use state::App;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Something run at the end of every update, with full access to the world.
+pub trait WorldHook {
+  fn on_update<'a>(&mut self, app: &mut App<'a>);
+}
+
+/// Something drawn over the HUD at the end of every frame, e.g. a mod's own
+/// overlay.
+///
+/// When `on_render` is called:
+///
+///   * the viewport is the whole window;
+///   * depth testing is off, so things are drawn in the order they're drawn,
+///     over everything else (including the menu);
+///   * alpha blending is on, with `SRC_ALPHA, ONE_MINUS_SRC_ALPHA`;
+///   * `hud_color_shader` (for `ColoredVertex`es) and `hud_texture_shader`
+///     (for `TextureVertex`es) have the HUD projection set: the origin is the
+///     center of the window, y runs from -1 at the bottom to 1 at the top, and
+///     x from -aspect to aspect, where aspect is the window's width over its
+///     height.
+///
+/// Make `GLArray`s with one of those shaders, and draw them inside
+/// `gl.use_shader`. Any other GL state that's changed has to be put back.
+pub trait RenderHook {
+  fn on_render(
+    &mut self,
+    gl: &mut GLContext,
+    hud_color_shader: &Rc<RefCell<Shader>>,
+    hud_texture_shader: &Rc<RefCell<Shader>>,
+  );
+}
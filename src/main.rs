@@ -1,13 +1,53 @@
 use common::*;
-use event::{WindowSettings, EventIterator, EventSettings};
+use event::{WindowSettings, EventIterator, EventSettings, Input, Update};
 use event_handler::handle_event;
+use replay::{Playback, Recorder};
 use sdl2_game_window::{WindowSDL2};
+use settings::SETTINGS_PATH;
 use shader_version::opengl::*;
 use state::App;
+use std::os;
+use std::path::Path;
+
+/// Command-line options: `--record <path>` saves this session's input as a
+/// replay, and `--replay <path>` plays one back instead of taking input.
+struct Options {
+  record: Option<Path>,
+  replay: Option<Path>,
+}
+
+fn parse_args(args: &[String]) -> Options {
+  let mut options = Options { record: None, replay: None };
+  let mut args = args.iter();
+  loop {
+    match args.next().map(|arg| arg.as_slice()) {
+      None => break,
+      Some("--record") => options.record = args.next().map(|path| Path::new(path.as_slice())),
+      Some("--replay") => options.replay = args.next().map(|path| Path::new(path.as_slice())),
+      Some(arg) => warn!("Ignoring unknown argument \"{}\"", arg),
+    }
+  }
+  options
+}
 
 pub fn main() {
   debug!("starting");
 
+  let options = parse_args(os::args().slice_from(1));
+  let mut recorder = options.record.as_ref().map(|_| Recorder::new());
+  let mut playback =
+    options.replay.as_ref().and_then(|path| {
+      match Playback::load(path) {
+        Ok(playback) => Some(playback),
+        Err(e) => {
+          warn!("{}", e);
+          None
+        },
+      }
+    });
+  // the number of updates so far; replays are timed by this.
+  let mut tick: u64 = 0;
+
   let mut window = WindowSDL2::new(
     OpenGL_3_3,
     WindowSettings {
@@ -24,15 +64,59 @@ pub fn main() {
     EventIterator::new(
       &mut window,
       &EventSettings {
-        updates_per_second: 30,
+        updates_per_second: app.settings.updates_per_second,
         max_frames_per_second: 30,
       });
 
   loop {
-    match game_iter.next() {
-      None => break,
-      Some(e) => handle_event(&mut app, game_iter.window, e)
+    if app.quit {
+      break;
+    }
+    let e =
+      match game_iter.next() {
+        None => break,
+        Some(e) => e,
+      };
+
+    match e {
+      Update(_) => {
+        let finished =
+          match playback {
+            None => false,
+            Some(ref mut playback) => {
+              for input in playback.due(tick).into_iter() {
+                handle_event(&mut app, game_iter.window, Input(input));
+              }
+              playback.is_done()
+            },
+          };
+        if finished {
+          // hand control back to the real input.
+          debug!("replay finished");
+          playback = None;
+        }
+        tick += 1;
+      },
+      Input(ref input) => {
+        // real input is ignored while a replay is playing.
+        if playback.is_some() {
+          continue;
+        }
+        match recorder {
+          None => {},
+          Some(ref mut recorder) => recorder.record(tick, input),
+        }
+      },
+      _ => {},
     }
+
+    handle_event(&mut app, game_iter.window, e);
+  }
+
+  app.settings.save(&Path::new(SETTINGS_PATH));
+  match (recorder, options.record) {
+    (Some(recorder), Some(path)) => recorder.save(&path),
+    _ => {},
   }
 
   debug!("finished!");
@@ -0,0 +1,61 @@
+//! A tool for measuring the distance between two things in the world: turn it
+//! on, click one thing, then click another.
+
+use common::*;
+use gl::types::*;
+use nalgebra::Norm;
+use physics::Physics;
+use state::EntityId;
+
+#[deriving(Copy, Clone, PartialEq, Eq, Show)]
+pub enum MeasureState {
+  Inactive,
+  /// Waiting for the first thing to be clicked.
+  PickingFirst,
+  /// Waiting for the second thing to be clicked.
+  PickingSecond(EntityId),
+}
+
+impl MeasureState {
+  pub fn is_active(&self) -> bool {
+    *self != Inactive
+  }
+
+  /// Turn the tool on (waiting for a first click) or off.
+  pub fn toggle(&self) -> MeasureState {
+    match *self {
+      Inactive => PickingFirst,
+      _ => Inactive,
+    }
+  }
+
+  /// Advance the tool when `id` is clicked. Returns the new state, and the two
+  /// measured ids once the second one has been clicked. After a measurement,
+  /// the tool waits for another pair.
+  pub fn pick(&self, id: EntityId) -> (MeasureState, Option<(EntityId, EntityId)>) {
+    match *self {
+      Inactive => (Inactive, None),
+      PickingFirst => (PickingSecond(id), None),
+      PickingSecond(first) => (PickingFirst, Some((first, id))),
+    }
+  }
+
+  /// Text prompting for whatever the tool is waiting for.
+  pub fn prompt(&self) -> &'static str {
+    match *self {
+      Inactive => "",
+      PickingFirst => "measure: click the first point",
+      PickingSecond(_) => "measure: click the second point",
+    }
+  }
+}
+
+/// Describe the distance between the centers of two entities' bounds.
+pub fn describe(physics: &Physics<EntityId>, a: EntityId, b: EntityId) -> String {
+  let delta = center(physics.get_bounds(b).unwrap()) - center(physics.get_bounds(a).unwrap());
+  let distance: GLfloat = delta.norm();
+  format!(
+    "measure: {:.2} (dx {:.2}, dy {:.2}, dz {:.2})",
+    distance, delta.x, delta.y, delta.z,
+  )
+}
@@ -0,0 +1,215 @@
+//! The in-game menu, toggled with escape.
+
+use common::*;
+use fontloader;
+use fontloader::FontLoader;
+use gl::types::*;
+use glw::color::Color4;
+use glw::gl_buffer::{GLArray, GLBuffer, DynamicDraw, StaticDraw, Triangles};
+use glw::gl_context::GLContext;
+use glw::shader::Shader;
+use glw::texture::Texture;
+use glw::vertex;
+use glw::vertex::{ColoredVertex, TextureVertex};
+use nalgebra::{Pnt2, Vec2};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+#[deriving(Show, Copy, Clone, PartialEq, Eq)]
+pub enum MenuItem {
+  Resume,
+  Quit,
+}
+
+static ITEMS: [MenuItem, ..2] = [Resume, Quit];
+
+impl MenuItem {
+  pub fn label(&self) -> &'static str {
+    match *self {
+      Resume => "Resume",
+      Quit => "Quit",
+    }
+  }
+}
+
+#[deriving(Show, Copy, Clone, PartialEq, Eq)]
+pub enum MenuState {
+  Closed,
+  /// Open, with the item at this index highlighted.
+  Open(uint),
+}
+
+const ITEM_WIDTH: GLfloat = 0.6;
+const ITEM_HEIGHT: GLfloat = 0.15;
+const ITEM_SPACING: GLfloat = 0.2;
+
+/// The HUD-space rectangle covered by the `i`th menu item.
+fn item_bounds(i: uint) -> (Pnt2<GLfloat>, Pnt2<GLfloat>) {
+  let top = (ITEMS.len() - 1) as GLfloat * ITEM_SPACING / 2.0 + ITEM_HEIGHT / 2.0;
+  let max_y = top - i as GLfloat * ITEM_SPACING;
+  (
+    Pnt2::new(-ITEM_WIDTH / 2.0, max_y - ITEM_HEIGHT),
+    Pnt2::new(ITEM_WIDTH / 2.0, max_y),
+  )
+}
+
+fn item_color(highlighted: bool) -> Color4<GLfloat> {
+  if highlighted {
+    Color4::of_rgba(0.5, 0.5, 0.5, 0.9)
+  } else {
+    Color4::of_rgba(0.2, 0.2, 0.2, 0.8)
+  }
+}
+
+pub struct Menu {
+  pub state: MenuState,
+
+  // A translucent backdrop over the whole screen, then one square per item.
+  quads: GLArray<ColoredVertex>,
+  labels: Vec<Texture>,
+  label_triangles: GLArray<TextureVertex>,
+}
+
+impl Menu {
+  pub fn new(
+    gl: &GLContext,
+    color_shader: Rc<RefCell<Shader>>,
+    texture_shader: Rc<RefCell<Shader>>,
+    font: &FontLoader,
+  ) -> Menu {
+    let mut quads =
+      GLArray::new(
+        gl,
+        color_shader,
+        [ vertex::AttribData { name: "position", size: 3, unit: vertex::Float },
+          vertex::AttribData { name: "in_color", size: 4, unit: vertex::Float },
+        ],
+        Triangles,
+        GLBuffer::new((1 + ITEMS.len()) * 6, DynamicDraw),
+      );
+    let mut label_triangles =
+      GLArray::new(
+        gl,
+        texture_shader,
+        [ vertex::AttribData { name: "position", size: 3, unit: vertex::Float },
+          vertex::AttribData { name: "texture_position", size: 2, unit: vertex::Float },
+        ],
+        Triangles,
+        GLBuffer::new(ITEMS.len() * 6, StaticDraw),
+      );
+
+    let aspect = WINDOW_WIDTH as GLfloat / WINDOW_HEIGHT as GLfloat;
+    quads.push(
+      ColoredVertex::square(
+        Pnt2::new(-aspect, -1.0),
+        Pnt2::new(aspect, 1.0),
+        Color4::of_rgba(0.0, 0.0, 0.0, 0.5),
+      )
+    );
+
+    let mut labels = Vec::new();
+    for (i, item) in ITEMS.iter().enumerate() {
+      let (min, max) = item_bounds(i);
+      quads.push(ColoredVertex::square(min, max, item_color(false)));
+
+      let label = item.label();
+      let (w, h) = font.get(fontloader::Sans).size_of(label);
+      let (w, h) = (w as GLfloat * HUD_PIXEL, h as GLfloat * HUD_PIXEL);
+      let center = Vec2::new((min.x + max.x) / 2.0, (min.y + max.y) / 2.0);
+      labels.push(font.get(fontloader::Sans).render(label, Color4::of_rgba(0xFF, 0xFF, 0xFF, 0xFF)));
+      label_triangles.push(
+        TextureVertex::square(
+          Vec2::new(center.x - w / 2.0, center.y - h / 2.0),
+          Vec2::new(center.x + w / 2.0, center.y + h / 2.0),
+        )
+      );
+    }
+
+    Menu {
+      state: Closed,
+      quads: quads,
+      labels: labels,
+      label_triangles: label_triangles,
+    }
+  }
+
+  pub fn is_open(&self) -> bool {
+    self.state != Closed
+  }
+
+  pub fn open(&mut self) {
+    self.state = Open(0);
+    self.highlight(0);
+  }
+
+  pub fn close(&mut self) {
+    self.state = Closed;
+  }
+
+  /// Highlight the `i`th item (and only that item).
+  fn highlight(&mut self, i: uint) {
+    self.state = Open(i);
+    for j in range(0, ITEMS.len()) {
+      let (min, max) = item_bounds(j);
+      self.quads.buffer.update(6 * (1 + j), ColoredVertex::square(min, max, item_color(i == j)));
+    }
+  }
+
+  /// Move the highlight by `delta` items, wrapping around.
+  pub fn move_highlight(&mut self, delta: int) {
+    match self.state {
+      Closed => {},
+      Open(i) => {
+        let n = ITEMS.len() as int;
+        let i = ((i as int + delta) % n + n) % n;
+        self.highlight(i as uint);
+      },
+    }
+  }
+
+  /// The index of the item, if any, under a HUD-space point.
+  fn index_at(&self, p: Pnt2<GLfloat>) -> Option<uint> {
+    range(0, ITEMS.len()).find(|&i| {
+      let (min, max) = item_bounds(i);
+      min.x <= p.x && p.x <= max.x && min.y <= p.y && p.y <= max.y
+    })
+  }
+
+  /// The item, if any, under a HUD-space point.
+  pub fn item_at(&self, p: Pnt2<GLfloat>) -> Option<MenuItem> {
+    self.index_at(p).map(|i| ITEMS[i])
+  }
+
+  /// Highlight whatever item is under a HUD-space point.
+  pub fn hover(&mut self, p: Pnt2<GLfloat>) {
+    match self.index_at(p) {
+      None => {},
+      Some(i) => {
+        if self.state != Open(i) {
+          self.highlight(i);
+        }
+      },
+    }
+  }
+
+  /// The currently-highlighted item.
+  pub fn selected(&self) -> Option<MenuItem> {
+    match self.state {
+      Closed => None,
+      Open(i) => Some(ITEMS[i]),
+    }
+  }
+
+  /// Draw the backdrop and item backgrounds. Use a HUD color shader.
+  pub fn draw_quads(&self, gl: &GLContext) {
+    self.quads.draw(gl);
+  }
+
+  /// Draw the item labels. Use a HUD texture shader.
+  pub fn draw_labels(&self, gl: &GLContext) {
+    for (i, label) in self.labels.iter().enumerate() {
+      label.bind_2d(gl);
+      self.label_triangles.draw_slice(gl, i * 6, 6);
+    }
+  }
+}
@@ -1,5 +1,6 @@
+use buffer_index::BufferIndex;
 use common::*;
-use glw::gl_buffer::{GLArray, GLBuffer, Triangles};
+use glw::gl_buffer::{GLArray, GLBuffer, DynamicDraw, Triangles};
 use glw::gl_context::GLContext;
 use glw::shader::Shader;
 use glw::vertex;
@@ -7,7 +8,6 @@ use nalgebra::Vec3;
 use state::App;
 use state::EntityId;
 use std::cell::RefCell;
-use std::collections::HashMap;
 use std::rc::Rc;
 
 // N.B.: Behaviors are unsafe because they take both a mutable and immutable
@@ -21,8 +21,7 @@ pub struct Mob {
 }
 
 pub struct MobBuffers {
-  id_to_index: HashMap<EntityId, uint>,
-  index_to_id: Vec<EntityId>,
+  index: BufferIndex<EntityId>,
 
   triangles: GLArray<vertex::ColoredVertex>,
 }
@@ -30,8 +29,7 @@ pub struct MobBuffers {
 impl MobBuffers {
   pub fn new(gl: &GLContext, color_shader: Rc<RefCell<Shader>>) -> MobBuffers {
     MobBuffers {
-      id_to_index: HashMap::new(),
-      index_to_id: Vec::new(),
+      index: BufferIndex::new(),
 
       triangles: GLArray::new(
         gl,
@@ -40,7 +38,7 @@ impl MobBuffers {
           vertex::AttribData { name: "in_color", size: 4, unit: vertex::Float },
         ],
         Triangles,
-        GLBuffer::new(32 * TRIANGLE_VERTICES_PER_BOX),
+        GLBuffer::new(32 * TRIANGLE_VERTICES_PER_BOX, DynamicDraw),
       ),
     }
   }
@@ -50,8 +48,7 @@ impl MobBuffers {
     id: EntityId,
     triangles: &[vertex::ColoredVertex]
   ) {
-    self.id_to_index.insert(id, self.index_to_id.len());
-    self.index_to_id.push(id);
+    self.index.push(id);
 
     self.triangles.push(triangles);
   }
@@ -61,7 +58,7 @@ impl MobBuffers {
     id: EntityId,
     triangles: &[vertex::ColoredVertex]
   ) {
-    let idx = *self.id_to_index.find(&id).unwrap();
+    let idx = self.index.find(id).unwrap();
     self.triangles.buffer.update(idx, triangles);
   }
 
@@ -0,0 +1,134 @@
+//! Motion blur: each frame is blended with a copy of the last one, so fast
+//! movement leaves a fading trail.
+//!
+//! There's no offscreen framebuffer; the back buffer is copied into a texture
+//! after the world is drawn, and that texture is drawn back over the next
+//! frame. Since each copy already has the ones before it blended in, older
+//! frames fade out geometrically.
+
+use common::*;
+use gl;
+use gl::types::*;
+use glw::gl_buffer::{GLArray, GLBuffer, Triangles};
+use glw::gl_context::GLContext;
+use glw::shader::Shader;
+use glw::texture::Texture;
+use glw::vertex;
+use glw::vertex::TextureVertex;
+use nalgebra::Vec2;
+use std::cell::RefCell;
+use std::num::Float;
+use std::ptr;
+use std::rc::Rc;
+
+/// The most of the last frame that can be blended in. Any more and trails
+/// never fade.
+pub static MAX_BLUR: GLfloat = 0.95;
+
+// the frame rate the `motion_blur` setting is tuned for.
+static BASE_FPS: f64 = 60.0;
+
+pub struct MotionBlur {
+  // the last frame, as drawn
+  last_frame: Texture,
+  // whether `last_frame` holds anything yet
+  has_frame: bool,
+  // a square covering the window
+  quad: GLArray<TextureVertex>,
+}
+
+impl MotionBlur {
+  /// Set up motion blur, to be drawn with a HUD texture shader.
+  pub fn new(gl: &GLContext, shader: Rc<RefCell<Shader>>) -> MotionBlur {
+    let mut gl_id = 0;
+    unsafe {
+      gl::GenTextures(1, &mut gl_id);
+      gl::BindTexture(gl::TEXTURE_2D, gl_id);
+      gl::TexImage2D(
+        gl::TEXTURE_2D, 0, gl::RGBA as i32,
+        WINDOW_WIDTH as i32, WINDOW_HEIGHT as i32,
+        0, gl::RGBA, gl::UNSIGNED_BYTE, ptr::null(),
+      );
+    }
+    gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as i32);
+    gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as i32);
+
+    // The HUD texture shader flips textures vertically (text is rendered top
+    // row first), but copies of the window are bottom row first, so flip them
+    // back.
+    let aspect = WINDOW_WIDTH as GLfloat / WINDOW_HEIGHT as GLfloat;
+    let mut vertices = TextureVertex::square(Vec2::new(-aspect, -1.0), Vec2::new(aspect, 1.0));
+    for v in vertices.iter_mut() {
+      v.texture_position.y = 1.0 - v.texture_position.y;
+    }
+
+    MotionBlur {
+      last_frame: Texture { gl_id: gl_id },
+      has_frame: false,
+      quad: GLArray::new(
+        gl,
+        shader,
+        [ vertex::AttribData { name: "position", size: 3, unit: vertex::Float },
+          vertex::AttribData { name: "texture_position", size: 2, unit: vertex::Float },
+        ],
+        Triangles,
+        GLBuffer::from_slice(vertices),
+      ),
+    }
+  }
+
+  /// Forget the last frame, so the next one isn't blended with anything,
+  /// e.g. because frames weren't being captured for a while.
+  pub fn reset(&mut self) {
+    self.has_frame = false;
+  }
+
+  /// Blend the last frame over whatever's been drawn, `weight` of the way
+  /// (clamped to [0, `MAX_BLUR`]). Use a HUD texture shader, with depth
+  /// testing off, and the texture unit its sampler reads from active.
+  pub fn blend(&self, gl: &GLContext, weight: GLfloat) {
+    if !self.has_frame {
+      return;
+    }
+    let weight = weight.max(0.0).min(MAX_BLUR);
+    gl::BlendColor(0.0, 0.0, 0.0, weight);
+    gl::BlendFunc(gl::CONSTANT_ALPHA, gl::ONE_MINUS_CONSTANT_ALPHA);
+    self.last_frame.bind_2d(gl);
+    self.quad.draw(gl);
+    gl.enable_alpha_blending();
+  }
+
+  /// Copy whatever's been drawn, to blend into the next frame. Use the same
+  /// texture unit as `blend`.
+  pub fn capture(&mut self, gl: &GLContext) {
+    self.last_frame.bind_2d(gl);
+    gl::CopyTexSubImage2D(
+      gl::TEXTURE_2D, 0, 0, 0, 0, 0,
+      WINDOW_WIDTH as i32, WINDOW_HEIGHT as i32,
+    );
+    self.has_frame = true;
+  }
+}
+
+/// How much of the last frame to blend into one that took `dt` seconds, to
+/// fade trails as fast as blending in `weight` at `BASE_FPS` would. The first
+/// frame (`dt` of 0) gets `weight`.
+pub fn frame_weight(weight: GLfloat, dt: f64) -> GLfloat {
+  if dt <= 0.0 {
+    return weight;
+  }
+  let weight = weight.max(0.0).min(MAX_BLUR) as f64;
+  weight.powf(dt * BASE_FPS) as GLfloat
+}
+
+#[test]
+fn trails_fade_at_the_same_rate_at_any_frame_rate() {
+  let close = |a: GLfloat, b: GLfloat| (a - b).abs() < 0.0001;
+  assert!(close(frame_weight(0.5, 1.0 / 60.0), 0.5));
+  // two frames at 120 fps fade as much as one at 60.
+  let w = frame_weight(0.5, 1.0 / 120.0);
+  assert!(close(w * w, 0.5));
+  assert!(close(frame_weight(0.5, 1.0 / 30.0), 0.25));
+  assert_eq!(frame_weight(0.5, 0.0), 0.5);
+  assert!(frame_weight(2.0, 1.0 / 60.0) <= MAX_BLUR);
+}
@@ -96,7 +96,7 @@ impl<V> OctreeBuffers<V> {
           vertex::AttribData { name: "in_color", size: 4, unit: vertex::Float },
         ],
         Lines,
-        GLBuffer::new(10 * MAX_WORLD_SIZE),
+        GLBuffer::new(10 * MAX_WORLD_SIZE, DynamicDraw),
       ),
     }
   }
@@ -308,12 +308,12 @@ impl<V: Copy + Eq + PartialOrd + Hash> Octree<V> {
   // Find details of objects overlapping the object & bounds provided in this
   // and child trees. Uses equality comparison on V to ignore "same" objects.
   // Only finds intersects in this and child trees.
-  pub fn intersect_details(&self, bounds: &AABB, self_v: V) -> HashSet<V> {
+  pub fn intersect_details(&self, bounds: &AABB, self_v: Option<V>) -> HashSet<V> {
     match self.contents {
       Leaf(ref vs) => {
         let mut r = HashSet::new();
         for &(bs, v) in vs.iter() {
-          if v != self_v {
+          if Some(v) != self_v {
             if bounds.intersects(&bs) {
               r.insert(v);
             }
@@ -344,7 +344,7 @@ impl<V: Copy + Eq + PartialOrd + Hash> Octree<V> {
   // Find the details of objects overlapping the object & bounds provided in
   // this tree, any children, or any relatives, starting the search from the
   // current tree. Uses equality comparison on V to ignore "same" objects.
-  pub fn intersect_details_from(&self, bounds: &AABB, self_v: V) -> HashSet<V> {
+  pub fn intersect_details_from(&self, bounds: &AABB, self_v: Option<V>) -> HashSet<V> {
     self.on_ancestor(bounds, |t| t.intersect_details(bounds, self_v))
   }
 
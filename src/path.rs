@@ -0,0 +1,209 @@
+//! Moving the camera along a fixed path of waypoints, e.g. for fly-throughs.
+
+use gl::types::*;
+use nalgebra::{Norm, Pnt3};
+use std::f32::consts::PI;
+
+#[deriving(Show, Copy, Clone, PartialEq)]
+pub struct Waypoint {
+  pub position: Pnt3<GLfloat>,
+  /// rotation around the y-axis, in radians
+  pub yaw: GLfloat,
+  /// in radians
+  pub pitch: GLfloat,
+}
+
+pub struct PathFollower {
+  waypoints: Vec<Waypoint>,
+  // index of the waypoint being moved toward
+  next: uint,
+  // distance moved per step
+  speed: GLfloat,
+}
+
+impl PathFollower {
+  pub fn new(waypoints: Vec<Waypoint>, speed: GLfloat) -> PathFollower {
+    assert!(speed > 0.0, "PathFollower speed must be positive, got {}", speed);
+    PathFollower {
+      waypoints: waypoints,
+      next: 0,
+      speed: speed,
+    }
+  }
+
+  /// Whether every waypoint has been reached.
+  pub fn is_done(&self) -> bool {
+    self.next >= self.waypoints.len()
+  }
+
+  /// Move `speed` from `at` toward the next waypoint, returning where to be
+  /// after this step. The look direction is interpolated by the same fraction
+  /// of the remaining distance. Reaching a waypoint moves on to the next one;
+  /// once the last has been reached, this returns None.
+  pub fn step(&mut self, at: &Waypoint) -> Option<Waypoint> {
+    if self.is_done() {
+      return None;
+    }
+
+    let target = self.waypoints[self.next];
+    let to_target = target.position - at.position;
+    let distance = to_target.norm();
+    if distance <= self.speed {
+      self.next += 1;
+      return Some(target);
+    }
+
+    let t = self.speed / distance;
+    Some(Waypoint {
+      position: at.position + to_target * t,
+      yaw: at.yaw + (target.yaw - at.yaw) * t,
+      pitch: at.pitch + (target.pitch - at.pitch) * t,
+    })
+  }
+}
+
+/// Smoothstep: 0 at 0, 1 at 1, with zero slope at both ends.
+pub fn ease_in_out(t: GLfloat) -> GLfloat {
+  let t = t.max(0.0).min(1.0);
+  t * t * (3.0 - 2.0 * t)
+}
+
+/// A smooth move from one waypoint to another over a fixed number of steps,
+/// easing in and out.
+pub struct Tween {
+  from: Waypoint,
+  to: Waypoint,
+  steps: uint,
+  // steps taken so far
+  step: uint,
+}
+
+impl Tween {
+  pub fn new(from: Waypoint, to: Waypoint, steps: uint) -> Tween {
+    assert!(steps > 0, "Tween needs at least one step");
+    // turn whichever way round is shorter.
+    let mut to = to;
+    while to.yaw - from.yaw > PI {
+      to.yaw = to.yaw - 2.0 * PI;
+    }
+    while to.yaw - from.yaw < -PI {
+      to.yaw = to.yaw + 2.0 * PI;
+    }
+    Tween {
+      from: from,
+      to: to,
+      steps: steps,
+      step: 0,
+    }
+  }
+
+  pub fn is_done(&self) -> bool {
+    self.step >= self.steps
+  }
+
+  /// Where to be after the next step, or None once the move is finished.
+  pub fn step(&mut self) -> Option<Waypoint> {
+    if self.is_done() {
+      return None;
+    }
+
+    self.step += 1;
+    let t = ease_in_out(self.step as GLfloat / self.steps as GLfloat);
+    let (from, to) = (self.from, self.to);
+    Some(Waypoint {
+      position: from.position + (to.position - from.position) * t,
+      yaw: from.yaw + (to.yaw - from.yaw) * t,
+      pitch: from.pitch + (to.pitch - from.pitch) * t,
+    })
+  }
+}
+
+/// The stop after `last` on a tour of `stops`, taken in increasing order and
+/// wrapping around at the end. `last` doesn't have to be one of the stops
+/// anymore (e.g. if it's been removed). None if there are no stops.
+pub fn next_stop<T: Copy + Ord>(stops: &[T], last: Option<T>) -> Option<T> {
+  let first = stops.iter().min().map(|&t| t);
+  match last {
+    None => first,
+    Some(last) =>
+      stops.iter()
+        .filter(|&&t| t > last)
+        .min()
+        .map(|&t| t)
+        .or(first),
+  }
+}
+
+#[test]
+fn steps_toward_and_through_waypoints() {
+  fn waypoint(x: GLfloat, yaw: GLfloat) -> Waypoint {
+    Waypoint { position: Pnt3::new(x, 0.0, 0.0), yaw: yaw, pitch: 0.0 }
+  }
+
+  let mut path = PathFollower::new(vec!(waypoint(2.0, 1.0), waypoint(3.0, 1.0)), 1.0);
+  let start = waypoint(0.0, 0.0);
+
+  let p = path.step(&start).unwrap();
+  assert_eq!(p, Waypoint { position: Pnt3::new(1.0, 0.0, 0.0), yaw: 0.5, pitch: 0.0 });
+
+  // arriving lands exactly on the waypoint.
+  let p = path.step(&p).unwrap();
+  assert_eq!(p, waypoint(2.0, 1.0));
+  assert!(!path.is_done());
+
+  let p = path.step(&p).unwrap();
+  assert_eq!(p, waypoint(3.0, 1.0));
+  assert!(path.is_done());
+  assert!(path.step(&p).is_none());
+}
+
+#[test]
+fn empty_path_is_done() {
+  let mut path = PathFollower::new(Vec::new(), 1.0);
+  assert!(path.is_done());
+  let start = Waypoint { position: Pnt3::new(0.0, 0.0, 0.0), yaw: 0.0, pitch: 0.0 };
+  assert!(path.step(&start).is_none());
+}
+
+#[test]
+fn tween_eases_to_its_target() {
+  let at = |x: GLfloat, yaw: GLfloat| {
+    Waypoint { position: Pnt3::new(x, 0.0, 0.0), yaw: yaw, pitch: 0.0 }
+  };
+
+  let mut tween = Tween::new(at(0.0, 0.0), at(4.0, 1.0), 4);
+  let mut xs = Vec::new();
+  loop {
+    match tween.step() {
+      None => break,
+      Some(p) => xs.push(p.position.x),
+    }
+  }
+  assert_eq!(xs.len(), 4);
+  assert_eq!(xs[3], 4.0);
+  // slow at the ends, fast in the middle.
+  assert!(xs[0] < xs[2] - xs[1]);
+  assert!(xs[3] - xs[2] < xs[2] - xs[1]);
+  assert!(tween.is_done());
+}
+
+#[test]
+fn tween_turns_the_short_way() {
+  let at = |yaw: GLfloat| {
+    Waypoint { position: Pnt3::new(0.0, 0.0, 0.0), yaw: yaw, pitch: 0.0 }
+  };
+  let mut tween = Tween::new(at(0.1), at(2.0 * PI - 0.1), 1);
+  assert!((tween.step().unwrap().yaw - -0.1).abs() < 0.0001);
+}
+
+#[test]
+fn tour_visits_stops_in_order() {
+  let stops = [5u, 2, 9];
+  assert_eq!(next_stop(stops, None), Some(2));
+  assert_eq!(next_stop(stops, Some(2)), Some(5));
+  assert_eq!(next_stop(stops, Some(5)), Some(9));
+  assert_eq!(next_stop(stops, Some(9)), Some(2));
+  // picks up where it left off when the last stop is gone.
+  assert_eq!(next_stop(stops, Some(3)), Some(5));
+  assert_eq!(next_stop(&[], Some(3)), None::<uint>);
+}
@@ -1,19 +1,80 @@
-use nalgebra::Vec3;
+use bvh;
+use common::center;
+use nalgebra::{Norm, Pnt3, Vec3};
 use ncollide::bounding_volume::aabb::AABB;
+use ncollide::bounding_volume::BoundingVolume;
 use ncollide::math::Scalar;
 use octree;
-use std::collections::HashMap;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
 use std::hash::Hash;
+use std::num::Float;
+use std::rc::Rc;
+
+// how far apart two things can be and still count as touching.
+static NEIGHBOR_EPSILON: Scalar = 0.001;
+
+/// The default `skin_width`.
+pub static SKIN_WIDTH: Scalar = 0.01;
 
 pub struct Physics<T> {
   pub octree: octree::Octree<T>,
+  // the same things as `octree`, for picking and collisions.
+  pub bvh: bvh::Bvh<T>,
   pub bounds: HashMap<T, AABB>,
+  // everything has to stay inside these bounds.
+  pub world_bounds: AABB,
+  // things moving into something stop this far short of it, so they never
+  // end up exactly touching it and stuck when they try to slide along it.
+  pub skin_width: Scalar,
+  // things that are tracked, but that nothing collides with.
+  pub passable: HashSet<T>,
 }
 
 impl<T: Copy + Eq + PartialOrd + Hash> Physics<T> {
-  pub fn insert(&mut self, t: T, bounds: &AABB) {
+  /// An empty world with the given bounds.
+  pub fn new(octree_loader: Rc<RefCell<octree::OctreeLoader>>, world_bounds: AABB) -> Physics<T> {
+    Physics {
+      octree: octree::Octree::new(octree_loader, &world_bounds),
+      bvh: bvh::Bvh::new(),
+      bounds: HashMap::new(),
+      world_bounds: world_bounds,
+      skin_width: SKIN_WIDTH,
+      passable: HashSet::new(),
+    }
+  }
+
+  /// Whether `bounds` lies entirely inside the world.
+  pub fn in_world(&self, bounds: &AABB) -> bool {
+    self.world_bounds.contains(bounds)
+  }
+
+  /// Add `t`. Returns false (and does nothing) if `bounds` isn't inside the
+  /// world.
+  pub fn insert(&mut self, t: T, bounds: &AABB) -> bool {
+    if !self.in_world(bounds) {
+      return false;
+    }
     self.octree.insert(bounds.clone(), t);
+    self.bvh.insert(t, bounds);
     self.bounds.insert(t, bounds.clone());
+    true
+  }
+
+  /// Add `t`, like `insert`, but as something nothing collides with. It can
+  /// still be found by everything that isn't about collisions.
+  pub fn insert_passable(&mut self, t: T, bounds: &AABB) -> bool {
+    if !self.insert(t, bounds) {
+      return false;
+    }
+    self.passable.insert(t);
+    true
+  }
+
+  /// Whether anything other than `except` that things collide with overlaps
+  /// `bounds`.
+  pub fn collides(&self, bounds: &AABB, except: Option<T>) -> bool {
+    self.bvh.overlaps(bounds).iter().any(|t| Some(*t) != except && !self.passable.contains(t))
   }
 
   pub fn remove(&mut self, t: T) {
@@ -23,31 +84,388 @@ impl<T: Copy + Eq + PartialOrd + Hash> Physics<T> {
         self.octree.remove(t, bounds);
       },
     }
+    self.bvh.remove(t);
+    self.passable.remove(&t);
   }
 
   pub fn get_bounds(&self, t: T) -> Option<&AABB> {
     self.bounds.find(&t)
   }
 
-  pub fn translate(&mut self, t: T, amount: Vec3<Scalar>) -> Option<bool> {
+  /// Everything sharing a face with `t`, i.e. touching or overlapping it
+  /// over a nonzero area, in no particular order. Things that only meet `t`
+  /// at an edge or corner aren't included.
+  pub fn neighbors(&self, t: T) -> Vec<T> {
+    let bounds =
+      match self.bounds.find(&t) {
+        None => return Vec::new(),
+        Some(bounds) => bounds,
+      };
+    let candidates = self.octree.intersect_details(&bounds.loosened(NEIGHBOR_EPSILON), Some(t));
+    candidates.into_iter()
+      .filter(|other| share_face(bounds, self.bounds.find(other).unwrap()))
+      .collect()
+  }
+
+  /// Everything whose bounds are centered within `radius` of `p`, in no
+  /// particular order.
+  pub fn in_sphere(&self, p: &Pnt3<Scalar>, radius: Scalar) -> Vec<T> {
+    let r = Vec3::new(radius, radius, radius);
+    let bounds = AABB::new(*p - r, *p + r);
+    self.octree.intersect_details(&bounds, None).into_iter()
+      .filter(|t| (center(self.bounds.find(t).unwrap()) - *p).norm() <= radius)
+      .collect()
+  }
+
+  /// How far `group` could fall straight down (along -y) as one rigid piece
+  /// before landing on something outside it, or on the bottom of the world.
+  /// Things under only an edge or corner of the group don't hold it up, and
+  /// neither do passable things.
+  pub fn drop_distance(&self, group: &HashSet<T>) -> Scalar {
+    let floor = self.world_bounds.mins().y;
+    let mut distance = Float::infinity();
+    for &t in group.iter() {
+      let bounds =
+        match self.bounds.find(&t) {
+          None => continue,
+          Some(bounds) => bounds,
+        };
+      distance = distance.min(bounds.mins().y - floor);
+
+      // everything in the column under `t`, down to the floor.
+      let e = NEIGHBOR_EPSILON;
+      let column =
+        AABB::new(
+          Pnt3::new(bounds.mins().x + e, floor, bounds.mins().z + e),
+          Pnt3::new(bounds.maxs().x - e, bounds.mins().y, bounds.maxs().z - e),
+        );
+      for other in self.octree.intersect_details(&column, Some(t)).iter() {
+        if group.contains(other) || self.passable.contains(other) {
+          continue;
+        }
+        let top = self.bounds.find(other).unwrap().maxs().y;
+        distance = distance.min(bounds.mins().y - top);
+      }
+    }
+    distance.max(0.0)
+  }
+
+  /// The highest top (along y) of anything `include`d that the column through
+  /// (x, z) passes through, from the bottom of the world to the top, or None
+  /// if there's nothing there.
+  pub fn column_top(&self, x: Scalar, z: Scalar, include: |T| -> bool) -> Option<Scalar> {
+    let e = NEIGHBOR_EPSILON;
+    let column =
+      AABB::new(
+        Pnt3::new(x - e, self.world_bounds.mins().y, z - e),
+        Pnt3::new(x + e, self.world_bounds.maxs().y, z + e),
+      );
+    let mut top = None;
+    for &t in self.octree.intersect_details(&column, None).iter() {
+      if !include(t) {
+        continue;
+      }
+      let y = self.bounds.find(&t).unwrap().maxs().y;
+      top = Some(top.map_or(y, |top: Scalar| top.max(y)));
+    }
+    top
+  }
+
+  /// Move `t` to `new_bounds`, without checking for collisions. Returns false
+  /// (and does nothing) if `t` doesn't exist or `new_bounds` isn't inside the
+  /// world.
+  pub fn reinsert(&mut self, t: T, new_bounds: AABB) -> bool {
+    if !self.in_world(&new_bounds) {
+      return false;
+    }
     match self.bounds.find_mut(&t) {
-      None => None,
+      None => false,
       Some(bounds) => {
-        let new_bounds =
-          AABB::new(
-            bounds.mins() + amount,
-            bounds.maxs() + amount
-          );
+        self.octree.reinsert(t, bounds, new_bounds);
+        self.bvh.insert(t, &new_bounds);
+        *bounds = new_bounds;
+        true
+      },
+    }
+  }
 
-        let collision = self.octree.intersect(&new_bounds, Some(t));
+  /// Whether `t` could be moved to `bounds` without leaving the world or
+  /// running into anything.
+  fn fits(&self, t: T, bounds: &AABB) -> bool {
+    self.world_bounds.contains(bounds) && !self.collides(bounds, Some(t))
+  }
 
-        if !collision {
-          self.octree.reinsert(t, bounds, new_bounds);
-          *bounds = new_bounds;
-        }
+  // How far `t`, at `bounds`, can move along `amount` before coming within
+  // `skin_width` of something (or of the edge of the world), as a part of
+  // `amount`. Only works for movement along a single axis; otherwise it's
+  // None.
+  fn approach(&self, t: T, bounds: &AABB, amount: Vec3<Scalar>) -> Option<Vec3<Scalar>> {
+    let (axis, distance) =
+      match single_axis(&amount) {
+        None => return None,
+        Some(a) => a,
+      };
+    // how far `from` is ahead of `to` along the direction of motion.
+    let ahead = |from: &Pnt3<Scalar>, to: &Pnt3<Scalar>| {
+      let d = coord(from, axis) - coord(to, axis);
+      if distance > 0.0 { d } else { -d }
+    };
+    let (leading, world_edge) =
+      if distance > 0.0 {
+        (bounds.maxs(), self.world_bounds.maxs())
+      } else {
+        (bounds.mins(), self.world_bounds.mins())
+      };
 
-        Some(collision)
+    let mut gap = ahead(world_edge, leading);
+    let swept = bounds.merged(&AABB::new(*bounds.mins() + amount, *bounds.maxs() + amount));
+    for other in self.bvh.overlaps(&swept).iter() {
+      if *other == t || self.passable.contains(other) {
+        continue;
+      }
+      let other = self.bounds.find(other).unwrap();
+      let facing = if distance > 0.0 { other.mins() } else { other.maxs() };
+      let g = ahead(facing, leading);
+      // things alongside or behind aren't in the way.
+      if g >= 0.0 {
+        gap = gap.min(g);
+      }
+    }
+
+    let step = (gap - self.skin_width).min(distance.abs());
+    if step <= 0.0 {
+      None
+    } else {
+      Some(amount * (step / distance.abs()))
+    }
+  }
+
+  /// Move `t` by `amount`, unless that would run it into something. Moving
+  /// along a single axis into something moves it as close as `skin_width`
+  /// allows instead. Returns None if there's no `t`, or else whether it ran
+  /// into something, and how far it actually moved.
+  pub fn translate(&mut self, t: T, amount: Vec3<Scalar>) -> Option<(bool, Vec3<Scalar>)> {
+    let bounds =
+      match self.bounds.find(&t) {
+        None => return None,
+        Some(bounds) => bounds.clone(),
+      };
+    let moved = |v: Vec3<Scalar>| AABB::new(*bounds.mins() + v, *bounds.maxs() + v);
+
+    // the edge of the world is treated like a wall.
+    let new_bounds = moved(amount);
+    if self.fits(t, &new_bounds) {
+      self.reinsert(t, new_bounds);
+      return Some((false, amount));
+    }
+
+    match self.approach(t, &bounds, amount) {
+      Some(step) if self.fits(t, &moved(step)) => {
+        self.reinsert(t, moved(step));
+        Some((true, step))
       },
+      _ => Some((true, Vec3::new(0.0, 0.0, 0.0))),
+    }
+  }
+}
+
+// The one axis `v` points along, and how far, or None if it's zero or
+// points along more than one.
+fn single_axis(v: &Vec3<Scalar>) -> Option<(uint, Scalar)> {
+  match (v.x != 0.0, v.y != 0.0, v.z != 0.0) {
+    (true, false, false) => Some((0, v.x)),
+    (false, true, false) => Some((1, v.y)),
+    (false, false, true) => Some((2, v.z)),
+    _ => None,
+  }
+}
+
+fn coord(p: &Pnt3<Scalar>, axis: uint) -> Scalar {
+  match axis {
+    0 => p.x,
+    1 => p.y,
+    _ => p.z,
+  }
+}
+
+// Whether `a` and `b` touch or overlap over a nonzero area: they have to
+// meet along every axis, and overlap along at least two.
+fn share_face(a: &AABB, b: &AABB) -> bool {
+  let overlaps = [
+    a.maxs().x.min(b.maxs().x) - a.mins().x.max(b.mins().x),
+    a.maxs().y.min(b.maxs().y) - a.mins().y.max(b.mins().y),
+    a.maxs().z.min(b.maxs().z) - a.mins().z.max(b.mins().z),
+  ];
+  overlaps.iter().all(|&d| d >= -NEIGHBOR_EPSILON) &&
+  overlaps.iter().filter(|&&d| d > NEIGHBOR_EPSILON).count() >= 2
+}
+
+// An empty world from -8 to 8 on each axis.
+#[cfg(test)]
+fn test_physics() -> Physics<uint> {
+  let world = AABB::new(Pnt3::new(-8.0, -8.0, -8.0), Pnt3::new(8.0, 8.0, 8.0));
+  Physics::new(Rc::new(RefCell::new(::glw::queue::Queue::new(1 << 10))), world)
+}
+
+// A unit cube with its minimum corner at (x, y, z).
+#[cfg(test)]
+fn unit_box(x: Scalar, y: Scalar, z: Scalar) -> AABB {
+  AABB::new(Pnt3::new(x, y, z), Pnt3::new(x + 1.0, y + 1.0, z + 1.0))
+}
+
+#[test]
+fn neighbors_share_a_face() {
+  let mut physics = test_physics();
+
+  // a block at the origin with blocks on four of its faces, one touching only
+  // along an edge, one touching only at a corner, and one off on its own.
+  let blocks = [
+    (0u, unit_box(0.0, 0.0, 0.0)),
+    (1, unit_box(1.0, 0.0, 0.0)),
+    (2, unit_box(-1.0, 0.0, 0.0)),
+    (3, unit_box(0.0, 1.0, 0.0)),
+    (4, unit_box(0.0, 0.0, -1.0)),
+    (5, unit_box(1.0, 1.0, 0.0)),
+    (6, unit_box(1.0, 1.0, 1.0)),
+    (7, unit_box(4.0, 4.0, 4.0)),
+  ];
+  for &(id, ref bounds) in blocks.iter() {
+    assert!(physics.insert(id, bounds));
+  }
+
+  let sorted_neighbors = |id: uint| {
+    let mut neighbors = physics.neighbors(id);
+    neighbors.sort();
+    neighbors
+  };
+  assert_eq!(sorted_neighbors(0), vec!(1, 2, 3, 4));
+  assert_eq!(sorted_neighbors(5), vec!(1, 3, 6));
+  assert_eq!(sorted_neighbors(7), vec!());
+  assert_eq!(sorted_neighbors(8), vec!());
+}
+
+#[test]
+fn groups_drop_onto_whatever_is_under_them() {
+  let mut physics = test_physics();
+  let blocks = [
+    // a floor block, and a two-block tower floating over it.
+    (0u, unit_box(0.0, 0.0, 0.0)),
+    (1, unit_box(0.0, 3.0, 0.0)),
+    (2, unit_box(0.0, 4.0, 0.0)),
+    // a block floating over nothing, and one diagonally under its corner.
+    (3, unit_box(4.0, 2.0, 4.0)),
+    (4, unit_box(5.0, 0.0, 5.0)),
+  ];
+  for &(id, ref bounds) in blocks.iter() {
+    assert!(physics.insert(id, bounds));
+  }
+
+  let group = |ids: &[uint]| ids.iter().map(|&id| id).collect::<HashSet<uint>>();
+  assert_eq!(physics.drop_distance(&group([1, 2])), 2.0);
+  assert_eq!(physics.drop_distance(&group([2])), 0.0);
+  // down to the bottom of the world
+  assert_eq!(physics.drop_distance(&group([3])), 10.0);
+  assert_eq!(physics.drop_distance(&group([0])), 8.0);
+}
+
+#[test]
+fn column_tops_are_the_highest_thing_included() {
+  let mut physics = test_physics();
+  let blocks = [
+    // a floor block with a gap over it, then another block.
+    (0u, unit_box(0.0, 0.0, 0.0)),
+    (1, unit_box(0.0, 3.0, 0.0)),
+    // something higher in the same column that isn't included.
+    (2, unit_box(0.0, 6.0, 0.0)),
+    // a block next to the column.
+    (3, unit_box(1.5, 5.0, 0.0)),
+  ];
+  for &(id, ref bounds) in blocks.iter() {
+    assert!(physics.insert(id, bounds));
+  }
+
+  assert_eq!(physics.column_top(0.5, 0.5, |id| id != 2), Some(4.0));
+  assert_eq!(physics.column_top(0.5, 0.5, |_| true), Some(7.0));
+  assert_eq!(physics.column_top(0.5, 0.5, |id| id == 0), Some(1.0));
+  assert_eq!(physics.column_top(-4.0, -4.0, |_| true), None);
+}
+
+#[test]
+fn in_sphere_goes_by_centers() {
+  let mut physics = test_physics();
+
+  // a 3x3 square of blocks centered on the origin, plus one further out.
+  let mut id = 0u;
+  for &x in [-1.5, -0.5, 0.5].iter() {
+    for &z in [-1.5, -0.5, 0.5].iter() {
+      assert!(physics.insert(id, &unit_box(x, -0.5, z)));
+      id += 1;
     }
   }
+  assert!(physics.insert(id, &unit_box(3.5, -0.5, -0.5)));
+
+  let origin = Pnt3::new(0.0, 0.0, 0.0);
+  // just the middle block.
+  assert_eq!(physics.in_sphere(&origin, 0.5), vec!(4));
+  // the middle, and the four next to it on a side; the corners are sqrt(2)
+  // away.
+  let mut near = physics.in_sphere(&origin, 1.2);
+  near.sort();
+  assert_eq!(near, vec!(1, 3, 4, 5, 7));
+  assert_eq!(physics.in_sphere(&origin, 2.0).len(), 9);
+  assert_eq!(physics.in_sphere(&origin, 4.0).len(), 10);
+}
+
+#[test]
+fn skin_width_keeps_things_from_sticking() {
+  let mut physics = test_physics();
+  let skin = physics.skin_width;
+  // a unit box, and a tall wall a little way off along x.
+  assert!(physics.insert(0u, &unit_box(0.0, 0.0, 0.0)));
+  assert!(physics.insert(1u, &AABB::new(Pnt3::new(1.5, -4.0, -4.0), Pnt3::new(2.0, 4.0, 4.0))));
+
+  // moving into the wall stops just short of it.
+  let (collided, moved) = physics.translate(0, Vec3::new(1.0, 0.0, 0.0)).unwrap();
+  assert!(collided);
+  let gap = 1.5 - physics.get_bounds(0).unwrap().maxs().x;
+  assert!((gap - skin).abs() < 0.0001, "stopped {} from the wall", gap);
+  assert!((moved.x - (0.5 - skin)).abs() < 0.0001 && moved.y == 0.0 && moved.z == 0.0);
+
+  // pushing into it again gets no closer.
+  assert_eq!(physics.translate(0, Vec3::new(1.0, 0.0, 0.0)), Some((true, Vec3::new(0.0, 0.0, 0.0))));
+
+  // sliding along it isn't blocked, in any direction along it.
+  for &v in [Vec3::new(0.0, 0.5, 0.0), Vec3::new(0.0, -0.5, 0.0), Vec3::new(0.0, 0.0, 0.5), Vec3::new(0.0, 0.0, -0.5)].iter() {
+    assert_eq!(physics.translate(0, v), Some((false, v)));
+  }
+
+  // the edge of the world works the same way.
+  let (collided, _) = physics.translate(0, Vec3::new(0.0, -20.0, 0.0)).unwrap();
+  assert!(collided);
+  let gap = physics.get_bounds(0).unwrap().mins().y + 8.0;
+  assert!((gap - skin).abs() < 0.0001, "stopped {} from the floor", gap);
+
+  assert_eq!(physics.translate(2, Vec3::new(1.0, 0.0, 0.0)), None);
+}
+
+#[test]
+fn passable_things_are_not_collided_with() {
+  let mut physics = test_physics();
+  // a unit box, a passable wall in front of it, and a passable block under
+  // it.
+  assert!(physics.insert(0u, &unit_box(0.0, 0.0, 0.0)));
+  assert!(physics.insert_passable(1u, &AABB::new(Pnt3::new(1.5, -4.0, -4.0), Pnt3::new(2.0, 4.0, 4.0))));
+  assert!(physics.insert_passable(2u, &unit_box(0.0, -2.0, 0.0)));
+
+  assert!(!physics.collides(&unit_box(1.0, 0.0, 0.0), Some(0)));
+  assert_eq!(physics.translate(0, Vec3::new(2.0, 0.0, 0.0)), Some((false, Vec3::new(2.0, 0.0, 0.0))));
+  assert_eq!(physics.translate(0, Vec3::new(-2.0, 0.0, 0.0)), Some((false, Vec3::new(-2.0, 0.0, 0.0))));
+  // nothing holds it up but the bottom of the world.
+  let group = [0u].iter().map(|&id| id).collect::<HashSet<uint>>();
+  assert_eq!(physics.drop_distance(&group), 8.0);
+
+  // once it's solid, the wall's in the way again.
+  physics.remove(1);
+  assert!(physics.insert(1u, &AABB::new(Pnt3::new(1.5, -4.0, -4.0), Pnt3::new(2.0, 4.0, 4.0))));
+  assert!(physics.collides(&unit_box(1.0, 0.0, 0.0), Some(0)));
 }
@@ -1,94 +1,273 @@
 use gl::types::*;
 use glw::camera;
-use nalgebra::Vec3;
+use nalgebra::{Cross, Dot, Eye, Norm, Pnt3, Vec3};
 use nalgebra::RMul;
+use ncollide::bounding_volume::aabb::AABB;
 use ncollide::ray::Ray;
 use physics::Physics;
 use state::EntityId;
 use std::f32::consts::PI;
 
-static MAX_JUMP_FUEL: uint = 4;
+// upward acceleration added while jumping.
+static JUMP_ACCEL: GLfloat = 0.3;
+// fraction of upward speed kept when the jump is released early.
+static JUMP_RELEASE_SPEED: GLfloat = 0.5;
+
+/// Which world axis points "up" by default: the axis the world is built
+/// around, and the one gravity pulls along unless it's changed.
+#[deriving(Show, Copy, Clone, PartialEq, Eq)]
+pub enum UpAxis {
+  YUp,
+  ZUp,
+}
+
+impl UpAxis {
+  pub fn up(&self) -> Vec3<GLfloat> {
+    match *self {
+      YUp => Vec3::new(0.0, 1.0, 0.0),
+      ZUp => Vec3::new(0.0, 0.0, 1.0),
+    }
+  }
+
+  /// Convert a vector from the player's unrotated frame (x right, y up,
+  /// z backward) into world coordinates.
+  pub fn to_world(&self, v: Vec3<GLfloat>) -> Vec3<GLfloat> {
+    match *self {
+      YUp => v,
+      ZUp => Vec3::new(v.x, -v.z, v.y),
+    }
+  }
+
+  /// The inverse of `to_world`.
+  pub fn to_local(&self, v: Vec3<GLfloat>) -> Vec3<GLfloat> {
+    match *self {
+      YUp => v,
+      ZUp => Vec3::new(v.x, v.z, -v.y),
+    }
+  }
+}
 
 pub struct Player {
   pub camera: camera::Camera,
   // speed; units are world coordinates
   pub speed: Vec3<GLfloat>,
-  // acceleration; units are world coordinates
+  // acceleration, not counting gravity; units are world coordinates
   pub accel: Vec3<GLfloat>,
-  // acceleration; x/z units are relative to player facing
+  // units are world coordinates. "Up" for the player (jumping, standing,
+  // turning) is opposite this.
+  pub gravity: Vec3<GLfloat>,
+  // the sum of the directions currently being walked in (i.e. held keys);
+  // x/z units are relative to player facing
+  pub walk_direction: Vec3<GLfloat>,
+  // acceleration; x/z units are relative to player facing. This ramps
+  // towards `walk_direction * max_walk_accel` by up to `walk_ramp` per update.
   pub walk_accel: Vec3<GLfloat>,
+  pub max_walk_accel: GLfloat,
+  // `max_walk_accel` or more makes starting and stopping instant.
+  pub walk_ramp: GLfloat,
+  pub up_axis: UpAxis,
   // this is depleted as we jump and replenished as we stand.
   pub jump_fuel: uint,
+  // the number of updates a jump can be held for; higher jumps higher.
+  pub max_jump_fuel: uint,
   // are we currently trying to jump? (e.g. holding the key).
   pub is_jumping: bool,
   pub id: EntityId,
+  // the number of collision-checked steps each update's movement is split into.
+  pub substeps: uint,
+  // the player can't fall below this height (along the up axis).
+  pub floor: GLfloat,
+  // whether falling below `floor` sends the player back to `spawn`, rather
+  // than just stopping them.
+  pub respawn_below_floor: bool,
+  pub spawn: Pnt3<GLfloat>,
 
-  // rotation around the y-axis, in radians
+  // rotation around the up axis, in radians
   pub lateral_rotation: f32,
   // "pitch", in radians
   pub vertical_rotation: f32,
+  // rotation around the forward axis, in radians. This only affects the view;
+  // movement and `forward` ignore it.
+  pub roll: f32,
 }
 
 impl Player {
-  /// Translates the player/camera by a vector.
+  /// A player with the camera at the origin, standing still, with gravity
+  /// pulling it down `up_axis`. It isn't in any physics yet.
+  pub fn new(id: EntityId, up_axis: UpAxis) -> Player {
+    Player {
+      camera: camera::Camera::unit(),
+      speed: Vec3::new(0.0, 0.0, 0.0),
+      accel: Vec3::new(0.0, 0.0, 0.0),
+      gravity: up_axis.up() * (-0.1 as GLfloat),
+      walk_direction: Vec3::new(0.0, 0.0, 0.0),
+      walk_accel: Vec3::new(0.0, 0.0, 0.0),
+      max_walk_accel: 0.2,
+      walk_ramp: 0.05,
+      up_axis: up_axis,
+      jump_fuel: 0,
+      max_jump_fuel: 4,
+      is_jumping: false,
+      id: id,
+      substeps: 1,
+      floor: -16.0,
+      respawn_below_floor: true,
+      spawn: Pnt3::new(0.0, 0.0, 0.0),
+      lateral_rotation: 0.0,
+      vertical_rotation: 0.0,
+      roll: 0.0,
+    }
+  }
+
+  /// Translates the player/camera by a vector, or as far along it as they
+  /// can go.
   pub fn translate(&mut self, physics: &mut Physics<EntityId>, v: Vec3<GLfloat>) {
-    let collided = physics.translate(self.id, v).unwrap();
+    let (collided, moved) = physics.translate(self.id, v).unwrap();
+    self.camera.translate(moved);
     if collided {
-      self.speed = self.speed - v;
+      // stop moving along the axes we collided on.
+      if v.x != 0.0 { self.speed.x = 0.0; }
+      if v.y != 0.0 { self.speed.y = 0.0; }
+      if v.z != 0.0 { self.speed.z = 0.0; }
 
-      if v.y < 0.0 {
-        self.jump_fuel = MAX_JUMP_FUEL;
+      if v.dot(&self.up()) < 0.0 {
+        self.jump_fuel = self.max_jump_fuel;
       }
     } else {
-      self.camera.translate(v);
-
-      if v.y < 0.0 {
+      if v.dot(&self.up()) < 0.0 {
         self.jump_fuel = 0;
       }
     }
   }
 
+  /// Moves the player so the camera is at `position`, ignoring collisions.
+  /// Returns false (and doesn't move) if that's outside the world.
+  pub fn teleport(&mut self, physics: &mut Physics<EntityId>, position: Pnt3<GLfloat>) -> bool {
+    let v = position - self.camera.position;
+    let new_bounds = {
+      let bounds = physics.get_bounds(self.id).unwrap();
+      AABB::new(*bounds.mins() + v, *bounds.maxs() + v)
+    };
+    if !physics.reinsert(self.id, new_bounds) {
+      return false;
+    }
+    self.camera.translate(v);
+    true
+  }
+
   pub fn update(&mut self, physics: &mut Physics<EntityId>) {
     if self.is_jumping {
       if self.jump_fuel > 0 {
         self.jump_fuel -= 1;
       } else {
-        // this code is duplicated in a few places
-        self.is_jumping = false;
-        self.accel.y = self.accel.y - 0.3;
+        self.end_jump();
       }
     }
 
-    let delta_p = self.speed;
-    if delta_p.x != 0.0 {
-      self.translate(physics, Vec3::new(delta_p.x, 0.0, 0.0));
+    // Move in several smaller steps so fast movement doesn't skip through
+    // thin objects. A collision zeroes that axis' speed, so the remaining
+    // substeps stop moving along it.
+    let substeps = self.substeps as GLfloat;
+    for _ in range(0, self.substeps) {
+      let delta_p = self.speed / substeps;
+      if delta_p.x != 0.0 {
+        self.translate(physics, Vec3::new(delta_p.x, 0.0, 0.0));
+      }
+      if delta_p.y != 0.0 {
+        self.translate(physics, Vec3::new(0.0, delta_p.y, 0.0));
+      }
+      if delta_p.z != 0.0 {
+        self.translate(physics, Vec3::new(0.0, 0.0, delta_p.z));
+      }
     }
-    if delta_p.y != 0.0 {
-      self.translate(physics, Vec3::new(0.0, delta_p.y, 0.0));
+
+    // the floor is along the world's up axis, whichever way gravity points.
+    let up = self.up_axis.up();
+    let bottom = physics.get_bounds(self.id).unwrap().mins().to_vec().dot(&up);
+    if bottom < self.floor {
+      let falling = self.speed.dot(&up);
+      if falling < 0.0 {
+        self.speed = self.speed - up * falling;
+      }
+      let position =
+        if self.respawn_below_floor {
+          self.spawn
+        } else {
+          self.camera.position + up * (self.floor - bottom)
+        };
+      self.teleport(physics, position);
     }
-    if delta_p.z != 0.0 {
-      self.translate(physics, Vec3::new(0.0, 0.0, delta_p.z));
+
+    let target = self.walk_direction * self.max_walk_accel;
+    let change = target - self.walk_accel;
+    let change_len = change.norm();
+    self.walk_accel =
+      if change_len <= self.walk_ramp {
+        target
+      } else {
+        self.walk_accel + change * (self.walk_ramp / change_len)
+      };
+
+    let walk_v = self.to_world(self.walk_accel);
+    self.speed = self.speed + walk_v + self.accel + self.gravity;
+    // friction: strong horizontally, slight vertically.
+    let up = self.up();
+    let vertical = up * self.speed.dot(&up);
+    self.speed = (self.speed - vertical) * (0.7 as f32) + vertical * (0.99 as f32);
+  }
+
+  /// Start jumping, if we aren't already. The jump keeps accelerating us
+  /// upward until `stop_jump` is called or we run out of jump fuel.
+  pub fn start_jump(&mut self) {
+    if !self.is_jumping {
+      self.is_jumping = true;
+      self.accel = self.accel + self.up() * JUMP_ACCEL;
     }
+  }
 
-    let y_axis = Vec3::new(0.0, 1.0, 0.0);
-    let walk_v =
-        camera::from_axis_angle3(y_axis, self.lateral_rotation)
-        .rmul(&self.walk_accel);
-    self.speed = self.speed + walk_v + self.accel;
-    // friction
-    self.speed = self.speed * Vec3::new(0.7, 0.99, 0.7 as f32);
+  /// Stop jumping (e.g. the jump key was released). Releasing before the jump
+  /// fuel runs out also cuts our upward speed, for a shorter jump.
+  pub fn stop_jump(&mut self) {
+    if self.is_jumping {
+      let up = self.up();
+      let rising = self.speed.dot(&up);
+      if self.jump_fuel > 0 && rising > 0.0 {
+        self.speed = self.speed - up * (rising * (1.0 - JUMP_RELEASE_SPEED));
+      }
+      self.end_jump();
+    }
   }
 
-  /// Changes the camera's acceleration by the given `da`.
-  pub fn walk(&mut self, da: Vec3<GLfloat>) {
-    self.walk_accel = self.walk_accel + da * 0.2 as GLfloat;
+  fn end_jump(&mut self) {
+    self.is_jumping = false;
+    self.accel = self.accel - self.up() * JUMP_ACCEL;
   }
 
-  /// Rotate the camera around the y axis, by `r` radians. Positive is
+  /// Move the camera by `v`, in the same player-relative axes as `walk`,
+  /// ignoring collisions.
+  pub fn nudge(&mut self, physics: &mut Physics<EntityId>, v: Vec3<GLfloat>) {
+    let position = self.camera.position + self.to_world(v);
+    self.teleport(physics, position);
+  }
+
+  /// Start walking in direction `d` (e.g. a key was pressed), or stop, if `d`
+  /// cancels out an earlier call (e.g. the key was released). The walking
+  /// acceleration ramps up and down over a few updates.
+  pub fn walk(&mut self, d: Vec3<GLfloat>) {
+    self.walk_direction = self.walk_direction + d;
+  }
+
+  /// Stop walking immediately.
+  pub fn stop_walking(&mut self) {
+    self.walk_direction = Vec3::new(0.0, 0.0, 0.0);
+    self.walk_accel = Vec3::new(0.0, 0.0, 0.0);
+  }
+
+  /// Rotate the camera around the up axis, by `r` radians. Positive is
   /// counterclockwise.
   pub fn rotate_lateral(&mut self, r: GLfloat) {
     self.lateral_rotation = self.lateral_rotation + r;
-    self.camera.rotate(Vec3::new(0.0, 1.0, 0.0), r);
+    self.rebuild_rotation();
   }
 
   /// Changes the camera pitch by `r` radians. Positive is up.
@@ -103,30 +282,226 @@ impl Player {
     }
 
     self.vertical_rotation = new_rotation;
-    let axis = self.right();
-    self.camera.rotate(axis, r);
+    self.rebuild_rotation();
+  }
+
+  /// Roll the camera around the forward axis, by `r` radians. Positive leans
+  /// left.
+  pub fn rotate_roll(&mut self, r: GLfloat) {
+    self.roll = self.roll + r;
+    self.rebuild_rotation();
+  }
+
+  /// Face a given direction: `lateral` radians around the up axis, pitched by
+  /// `vertical` radians.
+  pub fn set_rotation(&mut self, lateral: GLfloat, vertical: GLfloat) {
+    self.lateral_rotation = lateral;
+    self.vertical_rotation = vertical;
+    self.rebuild_rotation();
+  }
+
+  /// Rebuild the camera's rotation from scratch out of the yaw, pitch and
+  /// roll angles, rather than accumulating small rotations (and their error).
+  fn rebuild_rotation(&mut self) {
+    self.camera.rotation = Eye::new_identity(4);
+    self.camera.rotate(Vec3::new(0.0, 0.0, 1.0), self.roll);
+    self.camera.rotate(Vec3::new(1.0, 0.0, 0.0), self.vertical_rotation);
+    if self.up_axis == ZUp {
+      // turn the world so +z is up on screen, and +y is forward.
+      self.camera.rotate(Vec3::new(1.0, 0.0, 0.0), PI / 2.0);
+    }
+    self.camera.rotate(self.up_axis.up(), self.lateral_rotation);
+    let (axis, angle) = self.alignment();
+    if angle != 0.0 {
+      self.camera.rotate(axis, angle);
+    }
+  }
+
+  /// Change the direction and strength of gravity. The player's up direction
+  /// becomes the opposite of `gravity`, so e.g. pointing it sideways lets
+  /// them walk on walls.
+  pub fn set_gravity(&mut self, gravity: Vec3<GLfloat>) {
+    // the jump acceleration is along the old up direction.
+    if self.is_jumping {
+      self.end_jump();
+    }
+    self.gravity = gravity;
+    self.rebuild_rotation();
   }
 
   // axes
 
+  /// The direction the player considers up: opposite gravity, or the up axis
+  /// if there's no gravity.
+  pub fn up(&self) -> Vec3<GLfloat> {
+    if self.gravity.sqnorm() == 0.0 {
+      self.up_axis.up()
+    } else {
+      -self.gravity.normalize()
+    }
+  }
+
+  /// The rotation (as an axis and an angle) that takes the up axis to `up()`.
+  fn alignment(&self) -> (Vec3<GLfloat>, GLfloat) {
+    let from = self.up_axis.up();
+    let to = self.up();
+    let axis = from.cross(&to);
+    let s = axis.norm();
+    let c = from.dot(&to);
+    if s > 0.0 {
+      (axis / s, s.atan2(c))
+    } else if c < 0.0 {
+      // upside down; any perpendicular axis will do.
+      (self.up_axis.to_world(Vec3::new(1.0, 0.0, 0.0)), PI)
+    } else {
+      (from, 0.0)
+    }
+  }
+
+  fn align(&self, v: Vec3<GLfloat>) -> Vec3<GLfloat> {
+    let (axis, angle) = self.alignment();
+    camera::from_axis_angle3(axis, angle).rmul(&v)
+  }
+
+  /// Convert a vector relative to the player's facing (ignoring pitch) into
+  /// world coordinates.
+  fn to_world(&self, v: Vec3<GLfloat>) -> Vec3<GLfloat> {
+    let v =
+      camera::from_axis_angle3(self.up_axis.up(), self.lateral_rotation)
+        .rmul(&self.up_axis.to_world(v));
+    self.align(v)
+  }
+
+  /// The lateral and vertical rotations that would make the player look along
+  /// `direction`.
+  pub fn look_angles(&self, direction: Vec3<GLfloat>) -> (GLfloat, GLfloat) {
+    let (axis, angle) = self.alignment();
+    let d = camera::from_axis_angle3(axis, -angle).rmul(&direction.normalize());
+    let d = self.up_axis.to_local(d);
+    ((-d.x).atan2(-d.z), d.y.max(-1.0).min(1.0).asin())
+  }
+
   /// Return the "right" axis (i.e. the x-axis rotated to match you).
   pub fn right(&self) -> Vec3<GLfloat> {
-    return
-      camera::from_axis_angle3(Vec3::new(0.0, 1.0, 0.0), self.lateral_rotation)
-        .rmul(&Vec3::new(1.0, 0.0, 0.0))
+    self.to_world(Vec3::new(1.0, 0.0, 0.0))
   }
 
   /// Return the "Ray axis (i.e. the z-axis rotated to match you).
   pub fn forward(&self) -> Vec3<GLfloat> {
-    let y_axis = Vec3::new(0.0, 1.0, 0.0);
-    let transform =
-      camera::from_axis_angle3(self.right(), self.vertical_rotation) *
-      camera::from_axis_angle3(y_axis, self.lateral_rotation);
-    let forward_orig = Vec3::new(0.0, 0.0, -1.0);
-    return transform.rmul(&forward_orig);
+    let lateral = camera::from_axis_angle3(self.up_axis.up(), self.lateral_rotation);
+    let right = lateral.rmul(&self.up_axis.to_world(Vec3::new(1.0, 0.0, 0.0)));
+    let transform = camera::from_axis_angle3(right, self.vertical_rotation) * lateral;
+    let forward_orig = self.up_axis.to_world(Vec3::new(0.0, 0.0, -1.0));
+    self.align(transform.rmul(&forward_orig))
   }
 
   pub fn forward_ray(&self) -> Ray {
     Ray { orig: self.camera.position, dir: self.forward() }
   }
 }
+
+#[test]
+fn collisions_stop_the_player_at_a_face() {
+  let world = AABB::new(Pnt3::new(-8.0, -8.0, -8.0), Pnt3::new(8.0, 8.0, 8.0));
+  let id: EntityId = ::std::default::Default::default();
+  let block_id = id + 1;
+  let speed = 0.4;
+
+  let directions = [
+    Vec3::new(1.0, 0.0, 0.0), Vec3::new(-1.0, 0.0, 0.0),
+    Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, -1.0, 0.0),
+    Vec3::new(0.0, 0.0, 1.0), Vec3::new(0.0, 0.0, -1.0),
+  ];
+  for &d in directions.iter() {
+    let mut physics =
+      Physics::new(::std::rc::Rc::new(::std::cell::RefCell::new(::glw::queue::Queue::new(1 << 10))), world);
+    // a unit box player at the origin, without gravity, and a unit block one
+    // unit away from it in direction `d`.
+    let mut player = Player::new(id, YUp);
+    player.gravity = Vec3::new(0.0, 0.0, 0.0);
+    player.respawn_below_floor = false;
+    let player_bounds = AABB::new(Pnt3::new(0.0, 0.0, 0.0), Pnt3::new(1.0, 1.0, 1.0));
+    assert!(physics.insert(id, &player_bounds));
+    let block_min = Pnt3::new(0.0, 0.0, 0.0) + d * 2.0;
+    let block = AABB::new(block_min, block_min + Vec3::new(1.0, 1.0, 1.0));
+    assert!(physics.insert(block_id, &block));
+
+    for _ in range(0u, 20) {
+      player.speed = d * speed;
+      player.update(&mut physics);
+    }
+
+    let bounds = physics.get_bounds(id).unwrap().clone();
+    assert!(
+      !::ncollide::bounding_volume::BoundingVolume::intersects(&bounds, &block),
+      "moving along {} went into the block", d
+    );
+    // the gap between the player's leading face and the block's facing face.
+    let gap =
+      if d.x + d.y + d.z > 0.0 {
+        (*block.mins() - *bounds.maxs()).dot(&d)
+      } else {
+        (*bounds.mins() - *block.maxs()).dot(&-d)
+      };
+    assert!(0.0 < gap && gap < speed, "moving along {} stopped {} from the block", d, gap);
+    // it hasn't drifted along any other axis.
+    let moved = *bounds.mins() - *player_bounds.mins();
+    assert!((moved - d * moved.dot(&d)).norm() < 0.0001);
+    // and the camera moved with it.
+    assert!((player.camera.position.to_vec() - moved).norm() < 0.0001);
+  }
+}
+
+#[test]
+fn gravity_stops_at_the_ground() {
+  let world = AABB::new(Pnt3::new(-8.0, -8.0, -8.0), Pnt3::new(8.0, 8.0, 8.0));
+  let mut physics =
+    Physics::new(::std::rc::Rc::new(::std::cell::RefCell::new(::glw::queue::Queue::new(1 << 10))), world);
+  let id: EntityId = ::std::default::Default::default();
+  let floor_id = id + 1;
+
+  let mut player = Player::new(id, YUp);
+  player.respawn_below_floor = false;
+  // a 1x2x1 player dropped from 3 units above a wide floor.
+  assert!(physics.insert(id, &AABB::new(Pnt3::new(0.0, 3.0, 0.0), Pnt3::new(1.0, 5.0, 1.0))));
+  let floor = AABB::new(Pnt3::new(-4.0, -1.0, -4.0), Pnt3::new(4.0, 0.0, 4.0));
+  assert!(physics.insert(floor_id, &floor));
+
+  for _ in range(0u, 60) {
+    player.update(&mut physics);
+  }
+
+  let bottom = physics.get_bounds(id).unwrap().mins().y;
+  assert!(0.0 < bottom && bottom < 0.1, "the player stopped at {}", bottom);
+  // standing on the ground refills jump fuel.
+  assert_eq!(player.jump_fuel, player.max_jump_fuel);
+}
+
+#[test]
+fn falling_settles_at_any_update_rate() {
+  let world = AABB::new(Pnt3::new(-8.0, -8.0, -8.0), Pnt3::new(8.0, 8.0, 8.0));
+  let id: EntityId = ::std::default::Default::default();
+  let floor_id = id + 1;
+  // movement is per update, so this is the same motion at each rate, run for
+  // two seconds' worth of updates.
+  for &updates_per_second in [30u, 60, 120].iter() {
+    let mut physics =
+      Physics::new(::std::rc::Rc::new(::std::cell::RefCell::new(::glw::queue::Queue::new(1 << 10))), world);
+    let mut player = Player::new(id, YUp);
+    player.respawn_below_floor = false;
+    // a 1x2x1 player dropped from 3 units above a thin floor.
+    assert!(physics.insert(id, &AABB::new(Pnt3::new(0.0, 3.0, 0.0), Pnt3::new(1.0, 5.0, 1.0))));
+    let floor = AABB::new(Pnt3::new(-4.0, -0.25, -4.0), Pnt3::new(4.0, 0.0, 4.0));
+    assert!(physics.insert(floor_id, &floor));
+
+    for _ in range(0, 2 * updates_per_second) {
+      player.update(&mut physics);
+      let bottom = physics.get_bounds(id).unwrap().mins().y;
+      assert!(bottom > 0.0, "at {} updates per second, the player went through the floor", updates_per_second);
+    }
+
+    let bottom = physics.get_bounds(id).unwrap().mins().y;
+    assert!(0.0 < bottom && bottom < 0.1, "at {} updates per second, the player stopped at {}", updates_per_second, bottom);
+    assert_eq!(player.jump_fuel, player.max_jump_fuel);
+  }
+}
@@ -25,20 +25,44 @@ extern crate shader_version;
 // so time! macro is defined in main
 mod stopwatch;
 
+mod buffer_index;
+mod bvh;
+mod chunk;
 mod common;
+mod console;
+mod crosshair;
 mod event_handler;
 mod fontloader;
+mod frustum;
+mod help;
+mod hooks;
 mod id_allocator;
 mod player;
 mod loader;
 mod main;
+mod measure;
+mod menu;
 mod mob;
+mod motion_blur;
 mod octree;
+mod path;
 mod physics;
+mod profiler;
 mod render;
+mod replay;
+mod rng;
+mod screenshot;
+mod select;
+mod settings;
+mod shake;
 mod shader;
+mod shadow;
+mod skybox;
 mod state;
+mod sun;
 mod terrain;
+mod terrain_types;
+mod text_overlay;
 mod ttf;
 mod update;
 
@@ -0,0 +1,99 @@
+//! A HUD readout of the timers that took the most time recently.
+
+use fontloader;
+use state::App;
+use stopwatch::Stopwatch;
+use text_overlay::TextLineId;
+
+pub struct Profiler {
+  pub is_visible: bool,
+  // one line per timer shown
+  lines: Vec<TextLineId>,
+  // updates since the last sample
+  updates: uint,
+}
+
+impl Profiler {
+  /// A profiler that shows the top `lines.len()` timers.
+  pub fn new(lines: Vec<TextLineId>) -> Profiler {
+    Profiler {
+      is_visible: false,
+      lines: lines,
+      updates: 0,
+    }
+  }
+}
+
+/// The `n` timers with the most self time, most first.
+pub fn top_timers(mut timers: Vec<(String, Stopwatch)>, n: uint) -> Vec<(String, Stopwatch)> {
+  timers.sort_by(|&(_, ref a), &(_, ref b)| b.self_time.cmp(&a.self_time));
+  timers.truncate(n);
+  timers
+}
+
+fn describe(name: &str, sw: &Stopwatch, updates: uint) -> String {
+  let ms_per_update = |ns: u64| ns as f64 / updates as f64 / 1000000.0;
+  format!(
+    "{}: {:.2}ms self, {:.2}ms total per update",
+    name,
+    ms_per_update(sw.self_time),
+    ms_per_update(sw.total_time),
+  )
+}
+
+/// Call once per update. While the profiler is visible, once a second (going
+/// by the `updates_per_second` setting) this shows the timers with the most
+/// self time since the last sample, then resets all the timers.
+pub fn sample<'a>(app: &mut App<'a>) {
+  if !app.profiler.is_visible {
+    return;
+  }
+
+  app.profiler.updates += 1;
+  if app.profiler.updates < app.settings.updates_per_second as uint {
+    return;
+  }
+
+  let updates = app.profiler.updates;
+  app.profiler.updates = 0;
+
+  let top = top_timers(app.timers.snapshot(), app.profiler.lines.len());
+  app.timers.reset();
+
+  for (i, &line) in app.profiler.lines.iter().enumerate() {
+    let text =
+      match top.as_slice().get(i) {
+        None => String::new(),
+        Some(&(ref name, ref sw)) => describe(name.as_slice(), sw, updates),
+      };
+    app.text_overlay.set_text(app.font.get(fontloader::Mono), line, text.as_slice());
+  }
+}
+
+/// Show or hide the profiler. Timers are reset when it's shown, so the first
+/// sample only covers time since then.
+pub fn toggle<'a>(app: &mut App<'a>) {
+  app.profiler.is_visible = !app.profiler.is_visible;
+  app.profiler.updates = 0;
+  if app.profiler.is_visible {
+    app.timers.reset();
+  } else {
+    for &line in app.profiler.lines.iter() {
+      app.text_overlay.set_text(app.font.get(fontloader::Mono), line, "");
+    }
+  }
+}
+
+#[test]
+fn top_timers_are_by_self_time() {
+  let sw = |self_time: u64| {
+    Stopwatch { total_time: 100, self_time: self_time, number_of_windows: 1 }
+  };
+  let timers = vec!(
+    (String::from_str("a"), sw(10)),
+    (String::from_str("b"), sw(30)),
+    (String::from_str("c"), sw(20)),
+  );
+  let top: Vec<String> = top_timers(timers, 2).into_iter().map(|(name, _)| name).collect();
+  assert_eq!(top, vec!(String::from_str("b"), String::from_str("c")));
+}
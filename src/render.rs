@@ -1,4 +1,10 @@
+extern crate time;
+
+use common::*;
 use gl;
+use motion_blur;
+use shadow;
+use shadow::SHADOW_RADIUS;
 use state::App;
 use stopwatch;
 use stopwatch::*;
@@ -6,61 +12,197 @@ use stopwatch::*;
 // TODO: make this parameter non-mut
 pub fn render<'a>(app: &mut App<'a>) {
   time!(app.timers.deref(), "render", || {
-    app.gl.clear_buffer();
-
-    app.color_shader.borrow_mut().set_camera(&mut app.gl, &app.player.camera);
+    app.start_frame(time::precise_time_ns());
+    if app.console.is_open {
+      // for the caret's blinking
+      app.refresh_console();
+    }
+    app.update_physics_outlines();
 
-    app.gl.use_shader(app.color_shader.borrow().deref(), |_| {
-      // debug stuff
-      app.line_of_sight.draw(&app.gl);
+    if USE_LIGHTING && app.settings.shadows {
+      render_shadow_map(app);
+    }
 
-      if app.render_octree {
-        app.octree_buffers.draw(&app.gl);
-      }
-    });
+    app.gl.set_viewport(0, 0, WINDOW_WIDTH, WINDOW_HEIGHT);
+    app.gl.clear_buffer();
 
-    app.texture_shader.borrow_mut().set_camera(&mut app.gl, &app.player.camera);
+    if USE_LIGHTING {
+      let ambient = app.ambient;
+      app.texture_shader.borrow_mut().set_ambient_light(&mut app.gl, ambient);
+    }
 
-    // draw the world
-    if app.render_outlines {
-      gl::PolygonMode(gl::FRONT_AND_BACK, gl::LINE);
-      gl::Disable(gl::CULL_FACE);
-      app.gl.use_shader(app.texture_shader.borrow().deref(), |gl| {
-        app.terrain_buffers.draw(gl);
-      });
-      app.gl.use_shader(app.color_shader.borrow().deref(), |gl| {
-        app.mob_buffers.draw(gl);
-      });
-      gl::PolygonMode(gl::FRONT_AND_BACK, gl::FILL);
-      gl::Enable(gl::CULL_FACE);
+    if app.player2.is_none() {
+      render_scene(app, false);
     } else {
-      app.gl.use_shader(app.texture_shader.borrow().deref(), |gl| {
-        app.terrain_buffers.draw(gl);
-      });
-      app.gl.use_shader(app.color_shader.borrow().deref(), |gl| {
-        app.mob_buffers.draw(gl);
+      // player on the left, player2 on the right.
+      app.gl.set_viewport(0, 0, WINDOW_WIDTH / 2, WINDOW_HEIGHT);
+      render_scene(app, false);
+      app.gl.set_viewport(WINDOW_WIDTH / 2, 0, WINDOW_WIDTH / 2, WINDOW_HEIGHT);
+      render_scene(app, true);
+      app.gl.set_viewport(0, 0, WINDOW_WIDTH, WINDOW_HEIGHT);
+    }
+
+    // Draw the HUD over the world, one layer at a time: text (including the
+    // help screen), then the crosshair, then the menu, then anything drawn by
+    // render hooks. Depth testing is off,
+    // so each layer covers the ones before it regardless of depth.
+    gl::Disable(gl::DEPTH_TEST);
+
+    // motion blur only smears the world, so it's done before any HUD is drawn.
+    if app.render_motion_blur {
+      let weight = motion_blur::frame_weight(app.settings.motion_blur, app.frame_dt());
+      app.gl.use_shader(app.hud_texture_shader.borrow().deref(), |gl| {
+        gl::ActiveTexture(app.misc_texture_unit.gl_id());
+        app.motion_blur.blend(gl, weight);
       });
+      app.motion_blur.capture(&app.gl);
+    }
+
+    // each overlay's panels go under its own text, but over the overlays
+    // before it.
+    {
+      let mut overlays = vec!(&app.text_overlay);
+      if app.show_help {
+        overlays.push(&app.help);
+      }
+      for overlay in overlays.into_iter() {
+        app.gl.use_shader(app.hud_color_shader.borrow().deref(), |gl| {
+          overlay.draw_backgrounds(gl);
+        });
+        app.gl.use_shader(app.hud_texture_shader.borrow().deref(), |gl| {
+          gl::ActiveTexture(app.misc_texture_unit.gl_id());
+          overlay.draw(gl);
+        });
+      }
     }
 
-    // draw the hud
     app.gl.use_shader(app.hud_color_shader.borrow().deref(), |gl| {
       app.hud_triangles.draw(gl);
+      app.dig_bar.draw(gl);
     });
 
+    if app.menu.is_open() {
+      app.gl.use_shader(app.hud_color_shader.borrow().deref(), |gl| {
+        app.menu.draw_quads(gl);
+      });
+      app.gl.use_shader(app.hud_texture_shader.borrow().deref(), |gl| {
+        gl::ActiveTexture(app.misc_texture_unit.gl_id());
+        app.menu.draw_labels(gl);
+      });
+    }
+
+    app.run_render_hooks();
+
+    app.capture_frame();
+
+    gl::Enable(gl::DEPTH_TEST);
+
     match gl::GetError() {
       gl::NO_ERROR => {},
       err => fail!("OpenGL error 0x{:x}", err),
     }
 
-    // draw hud textures
-    app.gl.use_shader(app.hud_texture_shader.borrow().deref(), |gl| {
-      gl::ActiveTexture(app.misc_texture_unit.gl_id());
-      for (i, tex) in app.text_textures.iter().enumerate() {
-        tex.bind_2d(gl);
-        app.text_triangles.draw_slice(gl, i * 2, 2);
-      }
-    });
-
     gl::Finish();
   })
 }
+
+/// Draw the terrain around the player into the shadow map, from the sun, and
+/// tell the world shader where the sun's looking from.
+fn render_shadow_map<'a>(app: &mut App<'a>) {
+  let m = shadow::light_matrix(&app.player.camera.position, &app.sun.direction, SHADOW_RADIUS);
+  app.shadow_shader.borrow_mut().set_projection_matrix(&mut app.gl, &m);
+  app.texture_shader.borrow_mut().set_matrix(&mut app.gl, "light_matrix", &m);
+  app.shadow_map.render(&app.gl, |gl| {
+    gl.use_shader(app.shadow_shader.borrow().deref(), |gl| {
+      app.terrain_buffers.draw(gl);
+    });
+  });
+}
+
+/// Draw the world (but not the HUD) from `player`'s view, or `player2`'s if
+/// `second_player` is set.
+fn render_scene<'a>(app: &mut App<'a>, second_player: bool) {
+  let camera =
+    if second_player {
+      &app.player2.as_ref().unwrap().camera
+    } else {
+      &app.player.camera
+    };
+
+  app.color_shader.borrow_mut().set_camera(&mut app.gl, camera);
+
+  match app.skybox {
+    None => {},
+    Some(ref skybox) => skybox.draw(&mut app.gl, camera),
+  }
+
+  // the sun goes behind everything, so draw it first, without writing depth.
+  // It's just inside the far clip plane.
+  app.sun.move_to(&camera.position, 0.9 * app.settings.render_distance);
+  gl::DepthMask(gl::FALSE);
+  app.gl.use_shader(app.color_shader.borrow().deref(), |gl| {
+    app.sun.draw(gl);
+  });
+  gl::DepthMask(gl::TRUE);
+
+  app.gl.use_shader(app.color_shader.borrow().deref(), |_| {
+    // debug stuff
+    app.line_of_sight.draw(&app.gl);
+    app.selection_outlines.draw(&app.gl);
+    if app.render_chunks {
+      app.chunk_outlines.draw(&app.gl);
+    }
+    app.reach_ring.draw(&app.gl);
+    if app.render_physics {
+      app.physics_outlines.draw(&app.gl);
+    }
+  });
+
+  match app.octree_buffers {
+    Some(ref octree_buffers) if app.render_octree => {
+      let fade_distance = app.outline_fade_distance;
+      app.color_shader.borrow_mut().set_float(&mut app.gl, "fade_distance", fade_distance);
+      app.gl.use_shader(app.color_shader.borrow().deref(), |gl| {
+        octree_buffers.draw(gl);
+      });
+      app.color_shader.borrow_mut().set_float(&mut app.gl, "fade_distance", 0.0);
+    },
+    _ => {},
+  }
+
+  app.texture_shader.borrow_mut().set_camera(&mut app.gl, camera);
+
+  // draw the world
+  if app.render_outlines {
+    gl::PolygonMode(gl::FRONT_AND_BACK, gl::LINE);
+    gl::Disable(gl::CULL_FACE);
+    app.gl.use_shader(app.texture_shader.borrow().deref(), |gl| {
+      app.terrain_buffers.draw(gl);
+    });
+    app.gl.use_shader(app.color_shader.borrow().deref(), |gl| {
+      app.mob_buffers.draw(gl);
+    });
+    gl::PolygonMode(gl::FRONT_AND_BACK, gl::FILL);
+    gl::Enable(gl::CULL_FACE);
+  } else {
+    app.gl.use_shader(app.texture_shader.borrow().deref(), |gl| {
+      app.terrain_buffers.draw(gl);
+    });
+    app.gl.use_shader(app.color_shader.borrow().deref(), |gl| {
+      app.mob_buffers.draw(gl);
+    });
+  }
+
+  // The highlight is exactly as far away as what it covers, so it has to pass
+  // the depth test at equal depth. It doesn't write depth, so it doesn't hide
+  // anything drawn after it.
+  if app.render_target_highlight {
+    gl::DepthFunc(gl::LEQUAL);
+    gl::DepthMask(gl::FALSE);
+    app.gl.use_shader(app.color_shader.borrow().deref(), |gl| {
+      app.target_highlight.draw(gl);
+    });
+    gl::DepthMask(gl::TRUE);
+    gl::DepthFunc(gl::LESS);
+  }
+}
@@ -0,0 +1,228 @@
+//! Recording and playback of input events, for reproducing sessions.
+//!
+//! A replay file has one event per line: the number of updates that had
+//! happened when it arrived, then the event, e.g.
+//!
+//! ```text
+//! 12 key_press 119
+//! 40 mouse_move 400 300
+//! 41 mouse_press left
+//! ```
+//!
+//! Keys are written as their key codes. Playing a replay back against a fresh
+//! `App` feeds each event in just before the same update, so with
+//! deterministic physics the session plays out the same way.
+
+use input;
+use input::{InputEvent, Press, Release, Move, Keyboard, Mouse, MouseCursor};
+use std::io::fs::File;
+use std::num::FromPrimitive;
+use std::path::Path;
+
+fn mouse_button_name(button: input::mouse::Button) -> Option<&'static str> {
+  match button {
+    input::mouse::Left => Some("left"),
+    input::mouse::Right => Some("right"),
+    input::mouse::Middle => Some("middle"),
+    _ => None,
+  }
+}
+
+fn mouse_button_of_name(name: &str) -> Option<input::mouse::Button> {
+  match name {
+    "left" => Some(input::mouse::Left),
+    "right" => Some(input::mouse::Right),
+    "middle" => Some(input::mouse::Middle),
+    _ => None,
+  }
+}
+
+/// Format an event that arrived after `tick` updates as a replay file line,
+/// or None if it's a kind of event that isn't recorded.
+pub fn format_event(tick: u64, event: &InputEvent) -> Option<String> {
+  let event =
+    match *event {
+      Press(Keyboard(key)) => format!("key_press {}", key as u64),
+      Release(Keyboard(key)) => format!("key_release {}", key as u64),
+      Press(Mouse(button)) =>
+        match mouse_button_name(button) {
+          None => return None,
+          Some(name) => format!("mouse_press {}", name),
+        },
+      Release(Mouse(button)) =>
+        match mouse_button_name(button) {
+          None => return None,
+          Some(name) => format!("mouse_release {}", name),
+        },
+      Move(MouseCursor(x, y)) => format!("mouse_move {} {}", x, y),
+      _ => return None,
+    };
+  Some(format!("{} {}", tick, event))
+}
+
+/// Parse a line written by `format_event`.
+pub fn parse_event(line: &str) -> Result<(u64, InputEvent), String> {
+  let words: Vec<&str> = line.words().collect();
+  if words.len() < 3 {
+    return Err(format!("too few fields in \"{}\"", line));
+  }
+
+  let tick =
+    match from_str::<u64>(words[0]) {
+      None => return Err(format!("bad tick \"{}\"", words[0])),
+      Some(tick) => tick,
+    };
+
+  let key = || {
+    match from_str::<u64>(words[2]).and_then(|code| FromPrimitive::from_u64(code)) {
+      None => Err(format!("bad key \"{}\"", words[2])),
+      Some(key) => Ok(key),
+    }
+  };
+  let button = || {
+    match mouse_button_of_name(words[2]) {
+      None => Err(format!("bad mouse button \"{}\"", words[2])),
+      Some(button) => Ok(button),
+    }
+  };
+
+  let event =
+    match words[1] {
+      "key_press" => Press(Keyboard(try!(key()))),
+      "key_release" => Release(Keyboard(try!(key()))),
+      "mouse_press" => Press(Mouse(try!(button()))),
+      "mouse_release" => Release(Mouse(try!(button()))),
+      "mouse_move" => {
+        let x = if words.len() == 4 { from_str::<f64>(words[2]) } else { None };
+        let y = if words.len() == 4 { from_str::<f64>(words[3]) } else { None };
+        match (x, y) {
+          (Some(x), Some(y)) => Move(MouseCursor(x, y)),
+          _ => return Err(format!("bad mouse position in \"{}\"", line)),
+        }
+      },
+      kind => return Err(format!("unknown event \"{}\"", kind)),
+    };
+  Ok((tick, event))
+}
+
+/// Collects input events to be saved as a replay.
+pub struct Recorder {
+  lines: Vec<String>,
+}
+
+impl Recorder {
+  pub fn new() -> Recorder {
+    Recorder {
+      lines: Vec::new(),
+    }
+  }
+
+  /// Record an event that arrived after `tick` updates.
+  pub fn record(&mut self, tick: u64, event: &InputEvent) {
+    format_event(tick, event).map(|line| self.lines.push(line));
+  }
+
+  /// Write the recorded events to a file, warning if that fails.
+  pub fn save(&self, path: &Path) {
+    let mut text = String::new();
+    for line in self.lines.iter() {
+      text.push_str(line.as_slice());
+      text.push('\n');
+    }
+    match File::create(path).and_then(|mut f| f.write_str(text.as_slice())) {
+      Ok(()) => {},
+      Err(e) => warn!("Couldn't write replay file \"{}\": {}", path.display(), e),
+    }
+  }
+}
+
+/// Feeds recorded events back in at the updates they originally arrived at.
+pub struct Playback {
+  events: Vec<(u64, InputEvent)>,
+  next: uint,
+}
+
+impl Playback {
+  pub fn new(events: Vec<(u64, InputEvent)>) -> Playback {
+    Playback {
+      events: events,
+      next: 0,
+    }
+  }
+
+  /// Load a replay file. Unparseable lines are warned about and skipped.
+  pub fn load(path: &Path) -> Result<Playback, String> {
+    let text =
+      match File::open(path).and_then(|mut f| f.read_to_string()) {
+        Ok(text) => text,
+        Err(e) => return Err(format!("Couldn't read replay file \"{}\": {}", path.display(), e)),
+      };
+
+    let mut events = Vec::new();
+    for line in text.as_slice().lines() {
+      if line.trim().is_empty() {
+        continue;
+      }
+      match parse_event(line) {
+        Ok(event) => events.push(event),
+        Err(e) => warn!("Ignoring replay line: {}", e),
+      }
+    }
+    Ok(Playback::new(events))
+  }
+
+  pub fn is_done(&self) -> bool {
+    self.next >= self.events.len()
+  }
+
+  /// The events to feed in before the update that follows `tick` updates.
+  pub fn due(&mut self, tick: u64) -> Vec<InputEvent> {
+    let mut due = Vec::new();
+    while self.next < self.events.len() {
+      let (event_tick, ref event) = self.events[self.next];
+      if event_tick > tick {
+        break;
+      }
+      due.push(event.clone());
+      self.next += 1;
+    }
+    due
+  }
+}
+
+#[test]
+fn round_trip() {
+  let events = [
+    Press(Keyboard(input::keyboard::W)),
+    Release(Keyboard(input::keyboard::W)),
+    Press(Mouse(input::mouse::Left)),
+    Release(Mouse(input::mouse::Right)),
+    Move(MouseCursor(400.0, 299.5)),
+  ];
+  for (i, event) in events.iter().enumerate() {
+    let line = format_event(i as u64, event).unwrap();
+    assert_eq!(parse_event(line.as_slice()), Ok((i as u64, event.clone())));
+  }
+}
+
+#[test]
+fn bad_lines_are_errors() {
+  assert!(parse_event("").is_err());
+  assert!(parse_event("3 key_press").is_err());
+  assert!(parse_event("x key_press 119").is_err());
+  assert!(parse_event("3 mouse_press thumb").is_err());
+  assert!(parse_event("3 mouse_move 1").is_err());
+  assert!(parse_event("3 dance 1").is_err());
+}
+
+#[test]
+fn playback_feeds_events_at_their_tick() {
+  let w = Press(Keyboard(input::keyboard::W));
+  let click = Press(Mouse(input::mouse::Left));
+  let mut playback = Playback::new(vec!((0, w.clone()), (2, click.clone()), (2, w.clone())));
+  assert_eq!(playback.due(0), vec!(w.clone()));
+  assert_eq!(playback.due(1), vec!());
+  assert!(!playback.is_done());
+  assert_eq!(playback.due(2), vec!(click, w));
+  assert!(playback.is_done());
+}
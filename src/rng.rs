@@ -0,0 +1,67 @@
+//! A small, seedable, deterministic random number generator.
+//!
+//! This is Marsaglia's 32-bit xorshift with shifts (13, 17, 5). From a seed
+//! of 1, the first outputs are 270369, 67634689, 2647435461, 307599695.
+//! Anything random in the world should come from one of these (or from the
+//! world seed directly), so that a given seed always builds the same world.
+
+/// xorshift gets stuck on a state of 0, so a seed of 0 uses this instead.
+static ZERO_SEED: u32 = 2463534242;
+
+pub struct Rng {
+  state: u32,
+}
+
+impl Rng {
+  pub fn new(seed: u32) -> Rng {
+    Rng {
+      state: if seed == 0 { ZERO_SEED } else { seed },
+    }
+  }
+
+  /// The next value in the sequence; never 0.
+  pub fn next_u32(&mut self) -> u32 {
+    let mut x = self.state;
+    x = x ^ (x << 13);
+    x = x ^ (x >> 17);
+    x = x ^ (x << 5);
+    self.state = x;
+    x
+  }
+
+  /// A value in [0, 1).
+  pub fn next_f32(&mut self) -> f32 {
+    // use the top 24 bits, which an f32 can represent exactly.
+    (self.next_u32() >> 8) as f32 / (1u32 << 24) as f32
+  }
+
+  /// A value in [`min`, `max`).
+  pub fn range_f32(&mut self, min: f32, max: f32) -> f32 {
+    min + (max - min) * self.next_f32()
+  }
+}
+
+#[test]
+fn fixed_sequence() {
+  let mut rng = Rng::new(1);
+  let expected = [270369u32, 67634689, 2647435461, 307599695, 2398689233];
+  for &x in expected.iter() {
+    assert_eq!(rng.next_u32(), x);
+  }
+}
+
+#[test]
+fn zero_seed_is_not_stuck() {
+  let mut rng = Rng::new(0);
+  assert_eq!(rng.next_u32(), 723471715);
+  assert_eq!(rng.next_u32(), 2497366906);
+}
+
+#[test]
+fn floats_are_in_range() {
+  let mut rng = Rng::new(7);
+  for _ in range(0u, 1000) {
+    let x = rng.range_f32(-2.0, 3.0);
+    assert!(-2.0 <= x && x < 3.0);
+  }
+}
@@ -0,0 +1,123 @@
+//! Saving what's on screen as PNGs: one at a time, or every so many frames as
+//! a numbered sequence for timelapses.
+
+use common::*;
+use glw::gl_context::GLContext;
+use png;
+use std::io;
+use std::io::fs;
+use std::path::Path;
+
+/// Flip an image made of rows of `row_bytes` bytes upside-down.
+pub fn flip_rows(pixels: &[u8], row_bytes: uint) -> Vec<u8> {
+  assert!(pixels.len() % row_bytes == 0);
+  let mut flipped = Vec::with_capacity(pixels.len());
+  for row in pixels.chunks(row_bytes).rev() {
+    flipped.push_all(row);
+  }
+  flipped
+}
+
+/// Save the whole window to `path` as a PNG, warning if that fails.
+pub fn save(gl: &GLContext, path: &Path) {
+  // OpenGL reads the bottom row first; PNGs start at the top.
+  let pixels = gl.read_pixels_rect(0, 0, WINDOW_WIDTH, WINDOW_HEIGHT);
+  let pixels = flip_rows(pixels.as_slice(), 3 * WINDOW_WIDTH);
+  let mut image =
+    png::Image {
+      width: WINDOW_WIDTH as u32,
+      height: WINDOW_HEIGHT as u32,
+      pixels: png::RGB8(pixels),
+    };
+  match png::store_png(&mut image, path) {
+    Ok(()) => info!("Saved screenshot \"{}\"", path.display()),
+    Err(e) => warn!("Couldn't save screenshot \"{}\": {}", path.display(), e),
+  }
+}
+
+/// Which frames of a timelapse to save, and where.
+pub struct Timelapse {
+  dir: Path,
+  // every `interval`th frame is saved
+  interval: uint,
+  // frames seen so far
+  frames: uint,
+  // frames saved so far
+  saved: uint,
+}
+
+impl Timelapse {
+  /// A timelapse saving every `interval`th frame (starting with the first) to
+  /// `dir`, as 00000.png, 00001.png and so on.
+  pub fn new(dir: Path, interval: uint) -> Timelapse {
+    Timelapse {
+      dir: dir,
+      interval: if interval == 0 { 1 } else { interval },
+      frames: 0,
+      saved: 0,
+    }
+  }
+
+  /// Start a timelapse in `dir`, creating it if need be. Returns None (with a
+  /// warning) if it can't be created.
+  pub fn start(dir: Path, interval: uint) -> Option<Timelapse> {
+    match fs::mkdir_recursive(&dir, io::USER_RWX) {
+      Ok(()) => Some(Timelapse::new(dir, interval)),
+      Err(e) => {
+        warn!("Couldn't create timelapse directory \"{}\": {}", dir.display(), e);
+        None
+      },
+    }
+  }
+
+  /// Count a frame, returning where to save it if it's one of the frames that
+  /// gets saved.
+  pub fn frame(&mut self) -> Option<Path> {
+    let capture = self.frames % self.interval == 0;
+    self.frames += 1;
+    if !capture {
+      return None;
+    }
+    let path = self.dir.join(format!("{:05}.png", self.saved));
+    self.saved += 1;
+    Some(path)
+  }
+
+  pub fn dir(&self) -> &Path {
+    &self.dir
+  }
+
+  pub fn saved(&self) -> uint {
+    self.saved
+  }
+}
+
+#[test]
+fn flipping_reverses_rows() {
+  let pixels = [1u8, 2, 3, 4, 5, 6];
+  assert_eq!(flip_rows(pixels, 2), vec!(5, 6, 3, 4, 1, 2));
+  assert_eq!(flip_rows(pixels, 6), vec!(1, 2, 3, 4, 5, 6));
+}
+
+#[test]
+fn timelapse_saves_every_nth_frame_in_order() {
+  let mut timelapse = Timelapse::new(Path::new("timelapse"), 3);
+  let saved: Vec<(uint, Path)> =
+    range(0u, 8)
+      .filter_map(|i| timelapse.frame().map(|path| (i, path)))
+      .collect();
+  assert_eq!(
+    saved,
+    vec!(
+      (0, Path::new("timelapse/00000.png")),
+      (3, Path::new("timelapse/00001.png")),
+      (6, Path::new("timelapse/00002.png")),
+    ),
+  );
+  assert_eq!(timelapse.saved(), 3);
+
+  // an interval of 0 saves everything rather than failing.
+  let mut every = Timelapse::new(Path::new("t"), 0);
+  assert!(every.frame().is_some());
+  assert!(every.frame().is_some());
+}
@@ -0,0 +1,70 @@
+//! Selecting groups of connected things, e.g. for removing them all at once.
+
+use std::collections::HashSet;
+use std::hash::Hash;
+
+/// Everything reachable from `start` by repeatedly following `neighbors`,
+/// including `start` itself, visited breadth-first. At most `limit` things
+/// are selected; the ones nearest `start` are kept.
+pub fn flood_fill<T: Copy + Eq + Hash>(
+  start: T,
+  limit: uint,
+  neighbors: |T| -> Vec<T>,
+) -> HashSet<T> {
+  let mut selected = HashSet::new();
+  if limit == 0 {
+    return selected;
+  }
+
+  // things are visited in the order they were selected.
+  let mut queue = vec!(start);
+  let mut next = 0;
+  selected.insert(start);
+  while next < queue.len() {
+    let t = queue[next];
+    next += 1;
+    for n in neighbors(t).into_iter() {
+      if selected.len() >= limit {
+        return selected;
+      }
+      if selected.insert(n) {
+        queue.push(n);
+      }
+    }
+  }
+  selected
+}
+
+#[test]
+fn flood_fill_follows_neighbors() {
+  // two separate rings, 0-1-2-3 and 4-5.
+  let neighbors = |i: uint| {
+    match i {
+      0 => vec!(1, 3),
+      1 => vec!(0, 2),
+      2 => vec!(1, 3),
+      3 => vec!(2, 0),
+      4 => vec!(5),
+      5 => vec!(4),
+      _ => vec!(),
+    }
+  };
+  let mut selected: Vec<uint> = flood_fill(1, 100, |i| neighbors(i)).into_iter().collect();
+  selected.sort();
+  assert_eq!(selected, vec!(0, 1, 2, 3));
+
+  let selected: Vec<uint> = flood_fill(4, 100, |i| neighbors(i)).into_iter().collect();
+  assert_eq!(selected.len(), 2);
+  assert_eq!(flood_fill(9, 100, |i| neighbors(i)).len(), 1);
+}
+
+#[test]
+fn flood_fill_stops_at_the_limit() {
+  // an endless line of things, each next to the numbers either side.
+  let mut selected: Vec<int> =
+    flood_fill(0i, 5, |i| vec!(i - 1, i + 1)).into_iter().collect();
+  selected.sort();
+  // breadth-first, so the nearest ones are kept.
+  assert_eq!(selected, vec!(-2, -1, 0, 1, 2));
+  assert!(flood_fill(0i, 0, |i| vec!(i + 1)).is_empty());
+}
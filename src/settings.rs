@@ -0,0 +1,352 @@
+//! User settings, loaded from and saved to a simple `key = value` file.
+
+use crosshair::CrosshairStyle;
+use gl::types::*;
+use physics;
+use player;
+use player::UpAxis;
+use std::default::Default;
+use std::io::fs::File;
+use std::path::Path;
+
+/// Where settings are loaded from at startup and saved to at exit.
+pub static SETTINGS_PATH: &'static str = "settings.cfg";
+
+#[deriving(Clone, PartialEq, Show)]
+pub struct Settings {
+  /// radians of rotation per pixel of horizontal mouse movement
+  pub sensitivity_x: GLfloat,
+  /// radians of rotation per pixel of vertical mouse movement
+  pub sensitivity_y: GLfloat,
+  /// vertical field of view, in radians
+  pub fov: GLfloat,
+  /// distance to the near clipping plane
+  pub near: GLfloat,
+  /// distance to the far clipping plane
+  pub render_distance: GLfloat,
+  /// which world axis is up; `y` or `z` in the file
+  pub up_axis: UpAxis,
+  /// `dot`, `cross` or `circle` in the file
+  pub crosshair: CrosshairStyle,
+  /// whether octree outlines are built at all; `true` or `false` in the file.
+  /// Without them, O has nothing to show, but there's no outline geometry to
+  /// keep up to date as the octree changes.
+  pub octree_outlines: bool,
+  /// whether terrain colors blend smoothly across faces of different types,
+  /// rather than each face being one flat color; `true` or `false` in the file.
+  pub smooth_colors: bool,
+  /// whether the world is dithered, to hide color banding; `true` or `false`
+  /// in the file.
+  pub dither: bool,
+  /// simulation updates per second. Collisions resolve the same way at any
+  /// rate, but movement isn't scaled by the time step yet: speeds are per
+  /// update, so anything other than 30 changes how fast the game runs.
+  pub updates_per_second: u64,
+  /// how much of the last frame is blended into each new one while motion
+  /// blur is on, at 60 frames per second, from 0 to `motion_blur::MAX_BLUR`
+  pub motion_blur: GLfloat,
+  /// how far away terrain can be dug, in world units
+  pub reach: GLfloat,
+  /// whether a ring showing `reach` is drawn on the ground around the player;
+  /// `true` or `false` in the file.
+  pub reach_ring: bool,
+  /// while a timelapse is being recorded, every this many frames is saved
+  pub timelapse_interval: uint,
+  /// how far the view shakes when terrain is dug out, in world units; 0
+  /// turns shaking off
+  pub camera_shake: GLfloat,
+  /// how far short of whatever they run into things stop, so they don't
+  /// stick to walls they're sliding along
+  pub skin_width: GLfloat,
+  /// whether terrain casts shadows away from the sun; `true` or `false` in
+  /// the file.
+  pub shadows: bool,
+  /// a directory of cubemap faces (see `skybox::FACE_FILES`) to draw as the
+  /// sky, instead of a flat color; empty in the file for none.
+  pub skybox: Option<String>,
+}
+
+// the allowed range for `updates_per_second`.
+static MIN_UPDATES_PER_SECOND: u64 = 1;
+static MAX_UPDATES_PER_SECOND: u64 = 240;
+
+/// Whether `near` and `far` make a usable depth range.
+pub fn valid_clip_planes(near: GLfloat, far: GLfloat) -> bool {
+  near > 0.0 && far > near
+}
+
+impl Default for Settings {
+  fn default() -> Settings {
+    Settings {
+      sensitivity_x: 3.14 / 2048.0,
+      sensitivity_y: 3.14 / 1600.0,
+      fov: 3.14 / 3.0,
+      near: 0.1,
+      render_distance: 100.0,
+      up_axis: player::YUp,
+      crosshair: Default::default(),
+      octree_outlines: true,
+      smooth_colors: false,
+      dither: false,
+      updates_per_second: 30,
+      motion_blur: 0.5,
+      reach: 8.0,
+      reach_ring: false,
+      timelapse_interval: 30,
+      camera_shake: 0.05,
+      skin_width: physics::SKIN_WIDTH,
+      shadows: false,
+      skybox: None,
+    }
+  }
+}
+
+impl Settings {
+  /// Parse settings from `key = value` lines. Blank lines and lines starting
+  /// with `#` are ignored. Missing keys keep their defaults; unknown keys and
+  /// unparseable values are warned about and skipped.
+  pub fn parse(text: &str) -> Settings {
+    let mut settings: Settings = Default::default();
+    for line in text.lines() {
+      let line = line.trim();
+      if line.is_empty() || line.starts_with("#") {
+        continue;
+      }
+
+      let (key, value) =
+        match line.find('=') {
+          None => {
+            warn!("Ignoring setting line without '=': \"{}\"", line);
+            continue;
+          },
+          Some(i) => (line.slice_to(i).trim(), line.slice_from(i + 1).trim()),
+        };
+
+      if key == "up_axis" {
+        match value {
+          "y" => settings.up_axis = player::YUp,
+          "z" => settings.up_axis = player::ZUp,
+          _ => warn!("Ignoring bad value \"{}\" for setting \"{}\"", value, key),
+        }
+        continue;
+      }
+
+      if key == "crosshair" {
+        match CrosshairStyle::from_name(value) {
+          None => warn!("Ignoring bad value \"{}\" for setting \"{}\"", value, key),
+          Some(style) => settings.crosshair = style,
+        }
+        continue;
+      }
+
+      if key == "updates_per_second" {
+        match from_str::<u64>(value) {
+          Some(n) if MIN_UPDATES_PER_SECOND <= n && n <= MAX_UPDATES_PER_SECOND =>
+            settings.updates_per_second = n,
+          _ => warn!("Ignoring bad value \"{}\" for setting \"{}\"", value, key),
+        }
+        continue;
+      }
+
+      if key == "timelapse_interval" {
+        match from_str::<uint>(value) {
+          Some(n) if n > 0 => settings.timelapse_interval = n,
+          _ => warn!("Ignoring bad value \"{}\" for setting \"{}\"", value, key),
+        }
+        continue;
+      }
+
+      if key == "skybox" {
+        settings.skybox =
+          if value.is_empty() {
+            None
+          } else {
+            Some(String::from_str(value))
+          };
+        continue;
+      }
+
+      let flag =
+        match key {
+          "octree_outlines" => Some(&mut settings.octree_outlines),
+          "smooth_colors" => Some(&mut settings.smooth_colors),
+          "dither" => Some(&mut settings.dither),
+          "reach_ring" => Some(&mut settings.reach_ring),
+          "shadows" => Some(&mut settings.shadows),
+          _ => None,
+        };
+      match flag {
+        None => {},
+        Some(flag) => {
+          match from_str::<bool>(value) {
+            None => warn!("Ignoring bad value \"{}\" for setting \"{}\"", value, key),
+            Some(b) => *flag = b,
+          }
+          continue;
+        },
+      }
+
+      let field =
+        match key {
+          "sensitivity_x" => &mut settings.sensitivity_x,
+          "sensitivity_y" => &mut settings.sensitivity_y,
+          "fov" => &mut settings.fov,
+          "near" => &mut settings.near,
+          "render_distance" => &mut settings.render_distance,
+          "motion_blur" => &mut settings.motion_blur,
+          "reach" => &mut settings.reach,
+          "camera_shake" => &mut settings.camera_shake,
+          "skin_width" => &mut settings.skin_width,
+          _ => {
+            warn!("Ignoring unknown setting \"{}\"", key);
+            continue;
+          },
+        };
+
+      match from_str::<GLfloat>(value) {
+        None => warn!("Ignoring bad value \"{}\" for setting \"{}\"", value, key),
+        Some(value) => *field = value,
+      }
+    }
+    if !valid_clip_planes(settings.near, settings.render_distance) {
+      warn!(
+        "Ignoring bad clip planes (near {}, far {})",
+        settings.near,
+        settings.render_distance,
+      );
+      let defaults: Settings = Default::default();
+      settings.near = defaults.near;
+      settings.render_distance = defaults.render_distance;
+    }
+    settings
+  }
+
+  /// The settings in the format `parse` reads.
+  pub fn to_string(&self) -> String {
+    format!(
+      "sensitivity_x = {}\nsensitivity_y = {}\nfov = {}\nnear = {}\nrender_distance = {}\nup_axis = {}\ncrosshair = {}\noctree_outlines = {}\nsmooth_colors = {}\ndither = {}\nupdates_per_second = {}\nmotion_blur = {}\nreach = {}\nreach_ring = {}\ntimelapse_interval = {}\ncamera_shake = {}\nskin_width = {}\nshadows = {}\nskybox = {}\n",
+      self.sensitivity_x,
+      self.sensitivity_y,
+      self.fov,
+      self.near,
+      self.render_distance,
+      match self.up_axis {
+        player::YUp => "y",
+        player::ZUp => "z",
+      },
+      self.crosshair.name(),
+      self.octree_outlines,
+      self.smooth_colors,
+      self.dither,
+      self.updates_per_second,
+      self.motion_blur,
+      self.reach,
+      self.reach_ring,
+      self.timelapse_interval,
+      self.camera_shake,
+      self.skin_width,
+      self.shadows,
+      match self.skybox {
+        None => "",
+        Some(ref dir) => dir.as_slice(),
+      },
+    )
+  }
+
+  /// Load settings from a file. If the file can't be read, the defaults are
+  /// used.
+  pub fn load(path: &Path) -> Settings {
+    if !path.exists() {
+      return Default::default();
+    }
+
+    match File::open(path).and_then(|mut f| f.read_to_string()) {
+      Ok(text) => Settings::parse(text.as_slice()),
+      Err(e) => {
+        warn!("Couldn't read settings file \"{}\": {}", path.display(), e);
+        Default::default()
+      },
+    }
+  }
+
+  /// Save settings to a file, warning if that fails.
+  pub fn save(&self, path: &Path) {
+    match File::create(path).and_then(|mut f| f.write_str(self.to_string().as_slice())) {
+      Ok(()) => {},
+      Err(e) => warn!("Couldn't write settings file \"{}\": {}", path.display(), e),
+    }
+  }
+}
+
+#[test]
+fn missing_keys_are_defaulted() {
+  let settings = Settings::parse("# comment\n\nfov = 1.5\n");
+  let defaults: Settings = Default::default();
+  assert_eq!(settings.fov, 1.5);
+  assert_eq!(settings.sensitivity_x, defaults.sensitivity_x);
+  assert_eq!(settings.sensitivity_y, defaults.sensitivity_y);
+  assert_eq!(settings.render_distance, defaults.render_distance);
+}
+
+#[test]
+fn bad_lines_are_skipped() {
+  let settings = Settings::parse("fov = wide\nnonsense\ncolor = 3\nrender_distance=50\n");
+  let defaults: Settings = Default::default();
+  assert_eq!(settings.fov, defaults.fov);
+  assert_eq!(settings.render_distance, 50.0);
+}
+
+#[test]
+fn round_trip() {
+  let settings = Settings {
+    sensitivity_x: 0.25,
+    sensitivity_y: 0.5,
+    fov: 1.0,
+    near: 0.5,
+    render_distance: 64.0,
+    up_axis: player::ZUp,
+    crosshair: ::crosshair::Cross,
+    octree_outlines: false,
+    smooth_colors: true,
+    dither: true,
+    updates_per_second: 60,
+    motion_blur: 0.75,
+    reach: 4.5,
+    reach_ring: true,
+    timelapse_interval: 5,
+    camera_shake: 0.125,
+    skin_width: 0.02,
+    shadows: true,
+    skybox: Some(String::from_str("skies/day")),
+  };
+  assert_eq!(Settings::parse(settings.to_string().as_slice()), settings);
+
+  let defaults: Settings = Default::default();
+  assert_eq!(Settings::parse(defaults.to_string().as_slice()).skybox, None);
+}
+
+#[test]
+fn bad_clip_planes_are_defaulted() {
+  let settings = Settings::parse("near = 10\nrender_distance = 5\n");
+  let defaults: Settings = Default::default();
+  assert_eq!(settings.near, defaults.near);
+  assert_eq!(settings.render_distance, defaults.render_distance);
+}
+
+#[test]
+fn updates_per_second_is_bounded() {
+  assert_eq!(Settings::parse("updates_per_second = 120\n").updates_per_second, 120);
+  let defaults: Settings = Default::default();
+  for line in ["updates_per_second = 0\n", "updates_per_second = 1000\n", "updates_per_second = 2.5\n"].iter() {
+    assert_eq!(Settings::parse(*line).updates_per_second, defaults.updates_per_second);
+  }
+}
+
+#[test]
+fn timelapse_interval_is_positive() {
+  assert_eq!(Settings::parse("timelapse_interval = 10\n").timelapse_interval, 10);
+  let defaults: Settings = Default::default();
+  for line in ["timelapse_interval = 0\n", "timelapse_interval = -3\n"].iter() {
+    assert_eq!(Settings::parse(*line).timelapse_interval, defaults.timelapse_interval);
+  }
+}
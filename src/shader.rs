@@ -172,12 +172,35 @@ pub fn from_files<T: Iterator<(String, GLenum)>>(
   }))
 }
 
+// GLSL shared between shaders. Each is spliced into any shader loaded with
+// `from_file_prefix` that refers to it by name, e.g. `$dither$`.
+static SNIPPETS: [(&'static str, &'static str), ..1] = [
+  ("dither", "shaders/dither.glsl"),
+];
+
+// `vars`, plus the contents of each of the `SNIPPETS`.
+fn with_snippets(vars: &HashMap<String, String>) -> HashMap<String, String> {
+  let mut vars = vars.clone();
+  for &(name, path) in SNIPPETS.iter() {
+    match File::open(&Path::new(path)).and_then(|mut f| f.read_to_string()) {
+      Ok(s) => {
+        vars.insert(String::from_str(name), s);
+      },
+      Err(e) => {
+        fail!("Couldn't read shader snippet \"{}\": {}", path, e);
+      },
+    }
+  }
+  vars
+}
+
 pub fn from_file_prefix<T: Iterator<GLenum>>(
   gl: &mut GLContext,
   prefix: String,
   components: T,
   vars: &HashMap<String, String>,
 ) -> Shader {
+  let vars = with_snippets(vars);
   from_files(
     gl,
     components.map(|component| {
@@ -189,6 +212,14 @@ pub fn from_file_prefix<T: Iterator<GLenum>>(
       };
       ((prefix + "." + suffix), component)
     }),
-    vars,
+    &vars,
   )
 }
+
+#[test]
+fn snippets_are_spliced_in() {
+  let vars = with_snippets(&HashMap::new());
+  let actual = preprocess(String::from_str("$dither$\nvoid main() {}\n"), &vars).unwrap();
+  assert!(actual.as_slice().contains("vec3 dithered(vec3 color)"), "{}", actual);
+  assert!(actual.as_slice().ends_with("void main() {}\n"));
+}
@@ -0,0 +1,168 @@
+//! Shadows cast away from the sun, by shadow mapping.
+//!
+//! Each frame, the terrain around the player is drawn into a depth texture
+//! from the sun's point of view, through an orthographic projection (the sun
+//! is far enough away that its rays are parallel). The world shader then
+//! projects each fragment the same way, and if something in the depth
+//! texture is nearer the sun than it, the fragment's in shadow.
+//!
+//! Shadow maps compare depths that come from different rasterizations of the
+//! same surfaces, so surfaces tend to shadow themselves in speckled stripes
+//! ("shadow acne"). That's fought twice over: the depth pass is drawn with a
+//! polygon offset, which pushes steep faces back more than flat ones, and the
+//! world shader allows `SHADOW_BIAS` of slack when comparing.
+
+use gl;
+use gl::types::*;
+use glw::camera;
+use glw::gl_context::GLContext;
+use glw::texture::{Texture, TextureUnit};
+use nalgebra::{Cross, Dot, Mat4, Norm, Pnt3, Vec3};
+use std::ptr;
+
+/// The width and height of the shadow map, in texels.
+pub static SHADOW_MAP_SIZE: uint = 2048;
+/// How far from the player, sideways from the sun, shadows are drawn.
+pub static SHADOW_RADIUS: GLfloat = 48.0;
+/// How much nearer the sun than a fragment something has to be to shadow it,
+/// in the shadow map's [0, 1] depth range.
+pub static SHADOW_BIAS: GLfloat = 0.002;
+
+// `glPolygonOffset` factor and units for the depth pass.
+static OFFSET_FACTOR: GLfloat = 2.0;
+static OFFSET_UNITS: GLfloat = 4.0;
+
+/// The sun's view of the cube of half-width `radius` around `center`, as a
+/// matrix taking world coordinates to [-1, 1] on each axis. `direction`
+/// points toward the sun; depth increases away from it.
+pub fn light_matrix(center: &Pnt3<GLfloat>, direction: &Vec3<GLfloat>, radius: GLfloat) -> Mat4<GLfloat> {
+  let back = direction.normalize();
+  // any up will do, as long as it isn't along the light.
+  let up =
+    if back.y.abs() < 0.99 {
+      Vec3::new(0.0, 1.0, 0.0)
+    } else {
+      Vec3::new(1.0, 0.0, 0.0)
+    };
+  let right = up.cross(&back).normalize();
+  let up = back.cross(&right);
+  // back far enough that nothing within `radius` is behind the eye.
+  let eye = center.to_vec() + back * (2.0 * radius);
+
+  let view = Mat4 {
+    m11: right.x, m12: right.y, m13: right.z, m14: -right.dot(&eye),
+    m21: up.x,    m22: up.y,    m23: up.z,    m24: -up.dot(&eye),
+    m31: back.x,  m32: back.y,  m33: back.z,  m34: -back.dot(&eye),
+    m41: 0.0,     m42: 0.0,     m43: 0.0,     m44: 1.0,
+  };
+  camera::ortho(-radius, radius, -radius, radius, 0.0, 4.0 * radius) * view
+}
+
+/// A depth texture, and the framebuffer that draws into it.
+pub struct ShadowMap {
+  framebuffer: GLuint,
+  // stays bound to the unit it was made on, and is deleted with the map.
+  _depth: Texture,
+}
+
+impl ShadowMap {
+  /// Make a shadow map, and leave it bound to `unit`.
+  pub fn new(_gl: &GLContext, unit: &TextureUnit) -> ShadowMap {
+    let mut depth = 0;
+    let mut framebuffer = 0;
+    let size = SHADOW_MAP_SIZE as GLsizei;
+    gl::ActiveTexture(unit.gl_id());
+    unsafe {
+      gl::GenTextures(1, &mut depth);
+      gl::BindTexture(gl::TEXTURE_2D, depth);
+      gl::TexImage2D(
+        gl::TEXTURE_2D, 0, gl::DEPTH_COMPONENT24 as GLint, size, size, 0,
+        gl::DEPTH_COMPONENT, gl::FLOAT, ptr::null(),
+      );
+      gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as GLint);
+      gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as GLint);
+      gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as GLint);
+      gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as GLint);
+
+      gl::GenFramebuffers(1, &mut framebuffer);
+    }
+    gl::BindFramebuffer(gl::FRAMEBUFFER, framebuffer);
+    gl::FramebufferTexture2D(gl::FRAMEBUFFER, gl::DEPTH_ATTACHMENT, gl::TEXTURE_2D, depth, 0);
+    // there's no color to draw.
+    gl::DrawBuffer(gl::NONE);
+    gl::ReadBuffer(gl::NONE);
+    let status = gl::CheckFramebufferStatus(gl::FRAMEBUFFER);
+    assert!(status == gl::FRAMEBUFFER_COMPLETE, "shadow map framebuffer incomplete: 0x{:x}", status);
+    gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+
+    match gl::GetError() {
+      gl::NO_ERROR => {},
+      err => fail!("OpenGL error 0x{:x} making the shadow map", err),
+    }
+
+    ShadowMap {
+      framebuffer: framebuffer,
+      _depth: Texture { gl_id: depth },
+    }
+  }
+
+  /// Clear the shadow map and run `f` to draw depth into it. Use a shader
+  /// with the sun's `light_matrix` as its projection. The viewport is left
+  /// covering the shadow map, so set it back afterwards.
+  pub fn render(&self, gl: &GLContext, f: |&GLContext|) {
+    gl::BindFramebuffer(gl::FRAMEBUFFER, self.framebuffer);
+    gl.set_viewport(0, 0, SHADOW_MAP_SIZE, SHADOW_MAP_SIZE);
+    gl::Clear(gl::DEPTH_BUFFER_BIT);
+    gl::Enable(gl::POLYGON_OFFSET_FILL);
+    gl::PolygonOffset(OFFSET_FACTOR, OFFSET_UNITS);
+
+    f(gl);
+
+    gl::Disable(gl::POLYGON_OFFSET_FILL);
+    gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+  }
+}
+
+impl Drop for ShadowMap {
+  fn drop(&mut self) {
+    unsafe { gl::DeleteFramebuffers(1, &self.framebuffer); }
+  }
+}
+
+#[test]
+fn the_light_looks_at_the_center() {
+  // where `m` takes `p`; it's orthographic, so there's no w to divide by.
+  let project = |m: &Mat4<GLfloat>, p: Pnt3<GLfloat>| {
+    Pnt3::new(
+      m.m11 * p.x + m.m12 * p.y + m.m13 * p.z + m.m14,
+      m.m21 * p.x + m.m22 * p.y + m.m23 * p.z + m.m24,
+      m.m31 * p.x + m.m32 * p.y + m.m33 * p.z + m.m34,
+    )
+  };
+
+  let center = Pnt3::new(10.0, 2.0, -5.0);
+  let direction = Vec3::new(0.3, 1.0, 0.2);
+  let radius = 8.0;
+  let m = light_matrix(&center, &direction, radius);
+  assert_eq!((m.m41, m.m42, m.m43, m.m44), (0.0, 0.0, 0.0, 1.0));
+
+  let c = project(&m, center);
+  assert!(c.x.abs() < 0.0001 && c.y.abs() < 0.0001 && c.z.abs() < 0.0001);
+
+  // moving toward the sun gets nearer, without moving sideways.
+  let toward = project(&m, center + direction.normalize() * 4.0);
+  assert!(toward.z < c.z);
+  assert!(toward.x.abs() < 0.0001 && toward.y.abs() < 0.0001);
+
+  // everything within `radius` is in view.
+  for &d in [Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, 0.0, 1.0)].iter() {
+    for &sign in [-1.0, 1.0].iter() {
+      let p = project(&m, center + d * (sign * radius * 0.99));
+      assert!(p.x.abs() <= 1.0 && p.y.abs() <= 1.0 && p.z.abs() <= 1.0, "{} is out of view", p);
+    }
+  }
+
+  // straight up works too.
+  let m = light_matrix(&center, &Vec3::new(0.0, 1.0, 0.0), radius);
+  assert!(project(&m, center + Vec3::new(0.0, 1.0, 0.0)).z < project(&m, center).z);
+}
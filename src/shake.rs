@@ -0,0 +1,43 @@
+//! Camera shake: a brief, decaying wobble of the view, e.g. when terrain is
+//! dug out. Only what's drawn moves; the player, their camera position and
+//! their collisions stay where they are.
+
+use gl::types::*;
+use nalgebra::Vec3;
+use rng::Rng;
+use std::cmp;
+
+/// How many updates a shake lasts.
+pub static SHAKE_TICKS: uint = 8;
+
+/// How far the view is offset, sideways and up, during update `tick` of a
+/// shake of `magnitude` with `ticks_left` updates to go, in a world with the
+/// given `seed`. Its direction jumps around from update to update, and its
+/// size shrinks steadily to nothing.
+pub fn offset(magnitude: GLfloat, ticks_left: uint, seed: u32, tick: u64) -> Vec3<GLfloat> {
+  if ticks_left == 0 {
+    return Vec3::new(0.0, 0.0, 0.0);
+  }
+  // consecutive seeds give similar first outputs, so spread them out.
+  let mut rng = Rng::new(seed ^ (tick as u32) * 2654435761);
+  let scale = magnitude * cmp::min(ticks_left, SHAKE_TICKS) as GLfloat / SHAKE_TICKS as GLfloat;
+  Vec3::new(rng.range_f32(-1.0, 1.0), rng.range_f32(-1.0, 1.0), 0.0) * scale
+}
+
+#[test]
+fn shakes_die_down() {
+  let magnitude = 0.1;
+  assert_eq!(offset(magnitude, 0, 1, 7), Vec3::new(0.0, 0.0, 0.0));
+  for ticks_left in range(1, SHAKE_TICKS + 1) {
+    let limit = magnitude * ticks_left as GLfloat / SHAKE_TICKS as GLfloat;
+    for tick in range(0, 20u64) {
+      let v = offset(magnitude, ticks_left, 1, tick);
+      assert!(v.x.abs() <= limit && v.y.abs() <= limit && v.z == 0.0);
+    }
+  }
+  // it's deterministic, but doesn't sit still, and differs between worlds.
+  assert_eq!(offset(magnitude, 3, 1, 5), offset(magnitude, 3, 1, 5));
+  assert!(offset(magnitude, 3, 1, 5) != offset(magnitude, 3, 1, 6));
+  assert!(offset(magnitude, 3, 1, 5) != offset(magnitude, 3, 2, 5));
+  assert_eq!(offset(0.0, 3, 1, 5), Vec3::new(0.0, 0.0, 0.0));
+}
@@ -0,0 +1,168 @@
+//! An optional cubemap drawn behind everything else, in place of the flat sky
+//! color.
+
+use gl;
+use gl::types::*;
+use glw::camera::Camera;
+use glw::gl_buffer::{GLArray, GLBuffer, StaticDraw, Triangles};
+use glw::gl_context::GLContext;
+use glw::shader::Shader;
+use glw::texture::{Texture, TextureUnit};
+use glw::vertex;
+use nalgebra::Pnt3;
+use png;
+use shader;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// The files a skybox's faces are loaded from, in the directory it's given,
+/// in the order of `glw::texture::CUBE_FACES`.
+pub static FACE_FILES: [&'static str, ..6] = [
+  "posx.png", "negx.png",
+  "posy.png", "negy.png",
+  "posz.png", "negz.png",
+];
+
+// two triangles for each face of the cube.
+const VERTICES: uint = 6 * 6;
+
+pub struct Skybox {
+  shader: Rc<RefCell<Shader>>,
+  texture: Texture,
+  // the unit `texture` is bound to for drawing
+  unit: TextureUnit,
+  triangles: GLArray<Pnt3<GLfloat>>,
+}
+
+impl Skybox {
+  /// Load a skybox from the faces in `dir`, to be drawn from `unit`. Fails if
+  /// any face is missing, or they aren't all square RGB or RGBA PNGs of the
+  /// same size.
+  pub fn load(gl: &mut GLContext, unit: &TextureUnit, dir: &Path) -> Result<Skybox, String> {
+    let mut size = None;
+    let mut format = None;
+    let mut faces = Vec::with_capacity(FACE_FILES.len());
+    for name in FACE_FILES.iter() {
+      let path = dir.join(*name);
+      let image = try!(png::load_png(&path));
+      if image.width != image.height {
+        return Err(format!("\"{}\" isn't square", path.display()));
+      }
+      if size.map_or(false, |size| size != image.width) {
+        return Err(format!("\"{}\" isn't the same size as the other faces", path.display()));
+      }
+      size = Some(image.width);
+
+      let (face_format, pixels) =
+        match image.pixels {
+          png::RGB8(pixels) => (gl::RGB, pixels),
+          png::RGBA8(pixels) => (gl::RGBA, pixels),
+          _ => return Err(format!("\"{}\" isn't RGB or RGBA", path.display())),
+        };
+      if format.map_or(false, |format| format != face_format) {
+        return Err(format!("\"{}\" isn't the same format as the other faces", path.display()));
+      }
+      format = Some(face_format);
+      faces.push(pixels);
+    }
+
+    let shader =
+      Rc::new(RefCell::new(shader::from_file_prefix(
+        gl,
+        String::from_str("shaders/skybox"),
+        [ gl::VERTEX_SHADER, gl::FRAGMENT_SHADER, ].to_vec().into_iter(),
+        &HashMap::new(),
+      )));
+
+    gl::ActiveTexture(unit.gl_id());
+    let texture = Texture::new_cube(gl, size.unwrap() as uint, format.unwrap(), faces.as_slice());
+    shader.borrow_mut().with_uniform_location(gl, "skybox", |loc| {
+      gl::Uniform1i(loc, unit.glsl_id as GLint);
+    });
+
+    let mut triangles =
+      GLArray::new(
+        gl,
+        shader.clone(),
+        [ vertex::AttribData { name: "position", size: 3, unit: vertex::Float } ],
+        Triangles,
+        GLBuffer::new(VERTICES, StaticDraw),
+      );
+    triangles.push(cube().as_slice());
+
+    Ok(Skybox {
+      shader: shader,
+      texture: texture,
+      unit: unit.clone(),
+      triangles: triangles,
+    })
+  }
+
+  /// Draw the skybox as seen by `camera`. Everything drawn afterwards covers
+  /// it.
+  pub fn draw(&self, gl: &mut GLContext, camera: &Camera) {
+    // only the camera's rotation, since the sky is infinitely far away.
+    let m = camera.fov * camera.rotation;
+    self.shader.borrow_mut().set_projection_matrix(gl, &m);
+
+    gl::Disable(gl::DEPTH_TEST);
+    gl.use_shader(self.shader.borrow().deref(), |gl| {
+      gl::ActiveTexture(self.unit.gl_id());
+      self.texture.bind_cube(gl);
+      self.triangles.draw(gl);
+    });
+    gl::Enable(gl::DEPTH_TEST);
+  }
+}
+
+/// Triangles for the faces of a cube around the origin, with corners at +/-1,
+/// wound to face the inside.
+pub fn cube() -> Vec<Pnt3<GLfloat>> {
+  let mut vs = Vec::with_capacity(VERTICES);
+  // for each axis, the two other axes in an order where u x v = axis, so
+  // (u, v) runs counterclockwise seen from +axis.
+  for &(axis, u, v) in [(0u, 1u, 2u), (1, 2, 0), (2, 0, 1)].iter() {
+    for &side in [-1.0, 1.0].iter() {
+      let corner = |&(a, b): &(GLfloat, GLfloat)| {
+        let mut p = [0.0, 0.0, 0.0];
+        p[axis] = side;
+        p[u] = a;
+        p[v] = b;
+        Pnt3::new(p[0], p[1], p[2])
+      };
+      // counterclockwise seen from +axis, which is the inside of the -side
+      // face; reversed, it faces the inside of the +side face.
+      let mut quad = [
+        (-1.0, -1.0), (1.0, -1.0), (1.0, 1.0),
+        (-1.0, -1.0), (1.0, 1.0), (-1.0, 1.0),
+      ];
+      if side > 0.0 {
+        quad.reverse();
+      }
+      for c in quad.iter() {
+        vs.push(corner(c));
+      }
+    }
+  }
+  vs
+}
+
+#[test]
+fn the_cube_faces_inward() {
+  use nalgebra::{Cross, Dot};
+
+  let vs = cube();
+  assert_eq!(vs.len(), VERTICES);
+  for t in vs.as_slice().chunks(3) {
+    for p in t.iter() {
+      assert!(p.x.abs() == 1.0 || p.y.abs() == 1.0 || p.z.abs() == 1.0);
+    }
+    // counterclockwise seen from the center, i.e. the normal points back
+    // toward it.
+    let normal = (t[1] - t[0]).cross(&(t[2] - t[0]));
+    // (three times the triangle's middle; only the sign matters.)
+    let middle = t[0].to_vec() + t[1].to_vec() + t[2].to_vec();
+    assert!(normal.dot(&middle) < 0.0);
+  }
+}
@@ -1,5 +1,13 @@
+use chunk;
+use chunk::ChunkCoords;
 use common::*;
+use console;
+use console::Console;
+use crosshair;
+use crosshair::CrosshairStyle;
 use fontloader;
+use frustum::Frustum;
+use help;
 use gl;
 use gl::types::*;
 use glw::camera;
@@ -10,35 +18,115 @@ use glw::gl_context::GLContext;
 use glw::light::Light;
 use glw::queue::Queue;
 use glw::shader::Shader;
-use glw::texture::{Texture, TextureUnit};
+use glw::texture::TextureUnit;
 use glw::vertex;
-use glw::vertex::{ColoredVertex, TextureVertex};
+use glw::vertex::ColoredVertex;
+use hooks::{RenderHook, WorldHook};
 use id_allocator::IdAllocator;
 use input;
-use loader::{Loader, Load};
+use loader::{Loader, Load, Unload};
+use measure;
+use measure::MeasureState;
+use menu::Menu;
 use mob;
-use nalgebra::{Pnt2, Vec2, Vec3, Pnt3, Norm};
+use motion_blur::MotionBlur;
+use nalgebra::{Mat4, Pnt2, Vec3, Pnt3, Norm};
 use nalgebra::Cross;
 use noise::source::Perlin;
 use noise::model::Plane;
 use ncollide::math::Scalar;
 use ncollide::bounding_volume::aabb::AABB;
+use ncollide::bounding_volume::BoundingVolume;
 use octree;
+use path;
+use path::{PathFollower, Tween, Waypoint};
 use physics::Physics;
 use player::Player;
+use profiler::Profiler;
+use rng::Rng;
+use screenshot;
+use screenshot::Timelapse;
 use sdl2::mouse;
+use select;
+use settings;
+use settings::{Settings, SETTINGS_PATH};
 use shader;
+use shadow::{ShadowMap, SHADOW_BIAS};
+use skybox::Skybox;
+use shake;
+use shake::SHAKE_TICKS;
 use stopwatch;
 use stopwatch::*;
 use std::cell::RefCell;
-use std::collections::HashMap;
+use std::cmp;
+use std::collections::{HashMap, HashSet};
 use std::default::Default;
 use std::f32::consts::PI;
 use std::iter::range_inclusive;
+use std::mem;
+use std::path::Path;
 use std::rc::Rc;
+use sun::Sun;
 use terrain;
+use terrain_types::{TerrainTypes, TERRAIN_TYPES_PATH};
+use text_overlay::{TextOverlay, TextLineId, TEXT_PANEL_COLOR};
+use update;
 
 static SKY_COLOR: Color4<GLfloat>  = Color4 {r: 0.2, g: 0.5, b: 0.7, a: 1.0 };
+// every random part of the world is derived from this.
+static WORLD_SEED: u32 = 0;
+// `fly_to` stops this far in front of the terrain it flies to.
+static FLY_TO_DISTANCE: GLfloat = 3.0;
+// how many updates `fly_to` takes (half a second at 30 updates per second).
+static FLY_TO_STEPS: uint = 15;
+
+// direction toward the sun (it doesn't need to be normalized).
+static SUN_DIRECTION: Vec3<GLfloat> = Vec3 { x: 0.3, y: 0.8, z: -0.5 };
+
+// starting ambient light level, for each of r, g and b.
+static AMBIENT_LIGHT: GLfloat = 0.4;
+// how much brightening/darkening the ambient light changes it by.
+pub static AMBIENT_LIGHT_STEP: GLfloat = 0.05;
+
+// the number of timers the profiler shows.
+static PROFILER_LINES: uint = 5;
+
+// connected terrain at least this big is treated as part of the landscape,
+// and never falls when settling.
+static MAX_FALLING_STRUCTURE: uint = 256;
+// the most removed pieces remembered for the next `settle`.
+pub static MAX_UNSETTLED: uint = 1024;
+
+// the most terrain `select_connected` will select at once.
+static MAX_SELECTION: uint = 1024;
+static SELECTION_COLOR: Color4<GLfloat> = Color4 { r: 1.0, g: 1.0, b: 0.0, a: 1.0 };
+
+// how many lines the reach ring is drawn with
+pub static REACH_RING_SEGMENTS: uint = 48;
+
+// how many chunks out from the player's chunk boundaries are drawn
+static CHUNK_OUTLINE_RADIUS: i32 = 1;
+// chunks with loaded terrain in them are outlined more brightly than empty ones.
+static LOADED_CHUNK_COLOR: Color4<GLfloat> = Color4 { r: 0.0, g: 1.0, b: 1.0, a: 0.8 };
+static EMPTY_CHUNK_COLOR: Color4<GLfloat> = Color4 { r: 0.0, g: 1.0, b: 1.0, a: 0.2 };
+
+// physics boxes are only outlined this far from the player (on each axis),
+// and only this many of them, or the whole world would need a vast buffer.
+static PHYSICS_OUTLINE_RADIUS: GLfloat = 32.0;
+static MAX_PHYSICS_OUTLINES: uint = 8192;
+// terrain is outlined in one color, and everything that moves in another.
+static TERRAIN_PHYSICS_COLOR: Color4<GLfloat> = Color4 { r: 0.0, g: 1.0, b: 0.0, a: 0.5 };
+static MOVING_PHYSICS_COLOR: Color4<GLfloat> = Color4 { r: 1.0, g: 0.0, b: 1.0, a: 1.0 };
+
+// the player is knocked back by explosions within this many times their
+// radius, hardest at the center.
+static KNOCKBACK_RANGE: GLfloat = 2.0;
+// speed given to a player at the center of an explosion, per unit of its
+// radius.
+static KNOCKBACK_SPEED: GLfloat = 0.5;
+
+// the default `App::color_jitter`, which toggling it back on restores.
+static COLOR_JITTER: GLfloat = 0.08;
 
 #[deriving(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Show)]
 pub struct EntityId(u32);
@@ -56,10 +144,6 @@ impl Add<u32, EntityId> for EntityId {
   }
 }
 
-fn center(bounds: &AABB) -> Pnt3<GLfloat> {
-  (bounds.mins() + bounds.maxs().to_vec()) / (2.0 as GLfloat)
-}
-
 /// Defines volumes that can be tightened.
 trait TightBoundingVolume {
   /// Reduce each of a volume's bounds by some amount.
@@ -89,78 +173,29 @@ impl TightBoundingVolume for AABB {
   }
 }
 
-fn make_text(
-  gl: &GLContext,
-  shader: Rc<RefCell<Shader>>,
-) -> (Vec<Texture>, GLArray<TextureVertex>) {
-  let fontloader = fontloader::FontLoader::new();
-  let mut textures = Vec::new();
-  let mut triangles = {
-    GLArray::new(
-      gl,
-      shader,
-      [ vertex::AttribData { name: "position", size: 3, unit: vertex::Float },
-        vertex::AttribData { name: "texture_position", size: 2, unit: vertex::Float },
-      ],
-      Triangles,
-      GLBuffer::new(8 * VERTICES_PER_TRIANGLE),
-    )
-  };
-
-  let instructions = [
-          "Use WASD to move, and spacebar to jump.",
-          "Use the mouse to look around, and click to remove terrain."
-      ].to_vec();
-
-  let mut y = 0.99;
-
-  for line in instructions.iter() {
-    textures.push(fontloader.sans.red(*line));
-
-    triangles.push(
-      TextureVertex::square(
-        Vec2 { x: -0.97, y: y - 0.2 },
-        Vec2 { x: 0.0,   y: y       }
-      )
-    );
-    y -= 0.2;
-  }
-
-  (textures, triangles)
-}
-
 fn make_hud(
   gl: &GLContext,
   shader: Rc<RefCell<Shader>>,
+  crosshair_style: CrosshairStyle,
 ) -> GLArray<ColoredVertex> {
-  let mut hud_triangles = {
-    GLArray::new(
-      gl,
-      shader,
-      [ vertex::AttribData { name: "position", size: 3, unit: vertex::Float },
-        vertex::AttribData { name: "in_color", size: 4, unit: vertex::Float },
-      ],
-      Triangles,
-      GLBuffer::new(16 * VERTICES_PER_TRIANGLE),
-    )
-  };
-
   let cursor_color = Color4::of_rgba(0.0, 0.0, 0.0, 0.75);
 
-  hud_triangles.push(
-    ColoredVertex::square(
-      Pnt2 { x: -0.02, y: -0.02 },
-      Pnt2 { x:  0.02, y:  0.02 },
-      cursor_color
-    )
-  );
-
-  hud_triangles
+  GLArray::new(
+    gl,
+    shader,
+    [ vertex::AttribData { name: "position", size: 3, unit: vertex::Float },
+      vertex::AttribData { name: "in_color", size: 4, unit: vertex::Float },
+    ],
+    Triangles,
+    GLBuffer::from_slice(crosshair::vertices(crosshair_style, cursor_color).as_slice()),
+  )
 }
 
 fn make_terrain(
   physics: &mut Physics<EntityId>,
+  terrain_types: &TerrainTypes,
   id_allocator: &mut IdAllocator<EntityId>,
+  seed: u32,
 ) -> (HashMap<EntityId, terrain::TerrainPiece>, Loader<EntityId, EntityId>) {
   let mut terrains = HashMap::new();
   let mut terrain_loader = Queue::new(1 << 20);
@@ -170,6 +205,7 @@ fn make_terrain(
     let place_terrain = |bounds, vertices, normal, typ| {
       place_terrain(
         physics,
+        terrain_types,
         &mut terrains,
         &mut terrain_loader,
         id_allocator,
@@ -178,6 +214,7 @@ fn make_terrain(
         normal,
         typ,
         false,
+        0,
       );
     };
 
@@ -197,7 +234,7 @@ fn make_terrain(
     let amplitude = 64.0;
     let perlin =
       Perlin::new()
-      .seed(0)
+      .seed(seed as int)
       .frequency(1.0 / 32.0)
       .persistence(1.0 / 8.0)
       .lacunarity(8.0)
@@ -228,7 +265,7 @@ fn make_terrain(
           let side1: Vec3<GLfloat> = *center.as_vec() - *v1.as_vec();
           let side2: Vec3<GLfloat> =     *v2.as_vec() - *v1.as_vec();
           let normal: Vec3<GLfloat> = Norm::normalize_cpy(&Cross::cross(&side1, &side2));
-          let bounds = AABB::new(Pnt3::new(minx, v1.y, minz), Pnt3::new(maxx, maxy, maxz));
+          let bounds = aabb_of_corners(&Pnt3::new(minx, v1.y, minz), &Pnt3::new(maxx, maxy, maxz));
           place_terrain(bounds, [v1.clone(), v2.clone(), center.clone()], normal, typ);
         };
 
@@ -244,9 +281,9 @@ fn make_terrain(
         }
         let terrain =
           if center_lower_than >= 3 {
-            terrain::Dirt
+            terrain::DIRT
           } else {
-            terrain::Grass
+            terrain::GRASS
           }
         ;
 
@@ -309,28 +346,28 @@ fn make_terrain(
     for i in range_inclusive(-ground_range, ground_range) {
       for j in range_inclusive(0i, wall_height) {
         let (i, j) = (i as GLfloat * w, j as GLfloat * w);
-        place_square(i, j, -ground_steps as f32, w, w, terrain::Stone, Back);
+        place_square(i, j, -ground_steps as f32, w, w, terrain::STONE, Back);
       }
     }
     // back wall
     for i in range_inclusive(-ground_range, ground_range) {
       for j in range_inclusive(0i, wall_height) {
         let (i, j) = (i as GLfloat * w, j as GLfloat * w);
-        place_square(i, j, ground_steps as f32 - w, w, w, terrain::Stone, Front);
+        place_square(i, j, ground_steps as f32 - w, w, w, terrain::STONE, Front);
       }
     }
     // left wall
     for i in range_inclusive(-ground_range, ground_range) {
       for j in range_inclusive(0i, wall_height) {
         let (i, j) = (i as GLfloat * w, j as GLfloat * w);
-        place_square(-ground_steps as f32, j, i, w, w, terrain::Stone, Right);
+        place_square(-ground_steps as f32, j, i, w, w, terrain::STONE, Right);
       }
     }
     // right wall
     for i in range_inclusive(-ground_range, ground_range) {
       for j in range_inclusive(0i, wall_height) {
         let (i, j) = (i as GLfloat * w, j as GLfloat * w);
-        place_square(ground_steps as f32 - w, j, i, w, w, terrain::Stone, Left);
+        place_square(ground_steps as f32 - w, j, i, w, w, terrain::STONE, Left);
       }
     }
   }
@@ -343,7 +380,28 @@ pub struct App<'a> {
   pub physics: Physics<EntityId>,
   pub terrains: HashMap<EntityId, terrain::TerrainPiece>,
   pub player: Player,
+  // while this is set, the player is moved along it instead of by physics.
+  pub path: Option<PathFollower>,
+  // a smooth move to a single spot, e.g. from `fly_to`
+  pub tween: Option<Tween>,
+  // the last piece of terrain `tour_next` flew to
+  pub tour_stop: Option<EntityId>,
+  // a second local player, sharing the window in split-screen.
+  pub player2: Option<Player>,
   pub mobs: HashMap<EntityId, mob::Mob>,
+  // terrain picked out for bulk operations, e.g. by `select_connected`
+  pub selection: HashSet<EntityId>,
+  // terrain placed in the last `FLASH_TICKS` updates, which is still flashing
+  pub flashing: Vec<EntityId>,
+  // the terrain being dug at with the left mouse button, and for how many
+  // updates it's been dug at
+  pub dig: Option<(EntityId, uint)>,
+  // the ids and bounds of terrain removed since the last `settle`
+  pub unsettled: Vec<(EntityId, AABB)>,
+  // every random part of the world is derived from this.
+  pub seed: u32,
+
+  pub id_allocator: IdAllocator<EntityId>,
 
   pub terrain_loader: Loader<EntityId, EntityId>,
   pub octree_loader: Rc<RefCell<Loader<(octree::OctreeId, AABB), octree::OctreeId>>>,
@@ -351,25 +409,117 @@ pub struct App<'a> {
   // OpenGL buffers
   pub mob_buffers: mob::MobBuffers,
   pub terrain_buffers: terrain::TerrainBuffers,
-  pub octree_buffers: octree::OctreeBuffers<EntityId>,
+  // None if the `octree_outlines` setting is off.
+  pub octree_buffers: Option<octree::OctreeBuffers<EntityId>>,
   pub line_of_sight: GLArray<ColoredVertex>,
+  // outlines around each piece of terrain in `selection`
+  pub selection_outlines: GLArray<ColoredVertex>,
+  // a ring on the ground around the player, `settings.reach` in radius
+  pub reach_ring: GLArray<ColoredVertex>,
+  // a translucent box over whatever's under the crosshair
+  pub target_highlight: GLArray<ColoredVertex>,
+  // the boundaries of the chunks around `chunk_outlines_around`
+  pub chunk_outlines: GLArray<ColoredVertex>,
+  // the chunk `chunk_outlines` is centered on, if they're being drawn
+  pub chunk_outlines_around: Option<ChunkCoords>,
+  // outlines of the boxes in `physics` near the player, rebuilt every frame
+  pub physics_outlines: GLArray<ColoredVertex>,
+  pub sun: Sun,
+  pub skybox: Option<Skybox>,
   pub hud_triangles: GLArray<ColoredVertex>,
-  pub text_triangles: GLArray<TextureVertex>,
+  // how far along `dig` is, under the crosshair
+  pub dig_bar: GLArray<ColoredVertex>,
+  pub motion_blur: MotionBlur,
 
   pub misc_texture_unit: TextureUnit,
-  pub text_textures: Vec<Texture>,
+  pub font: fontloader::FontLoader,
+  pub menu: Menu,
+  pub text_overlay: TextOverlay,
+  // the key bindings, shown while `show_help` is set
+  pub help: TextOverlay,
+  pub show_help: bool,
+  // describes whatever is under the crosshair
+  pub target_info: TextLineId,
+  // shows the player's speed when `show_debug` is set
+  pub speed_info: TextLineId,
+  pub profiler: Profiler,
+  pub console: Console,
+  pub measure: MeasureState,
+  // prompts for and shows the result of the measure tool
+  pub measure_info: TextLineId,
 
   // OpenGL shader "program" ids
   pub color_shader: Rc<RefCell<Shader>>,
   pub texture_shader: Rc<RefCell<Shader>>,
   pub hud_texture_shader: Rc<RefCell<Shader>>,
   pub hud_color_shader: Rc<RefCell<Shader>>,
+  // draws terrain depth from the sun's view, into `shadow_map`
+  pub shadow_shader: Rc<RefCell<Shader>>,
+  pub shadow_map: ShadowMap,
 
   // which mouse buttons are currently pressed
   pub mouse_buttons_pressed: Vec<input::mouse::Button>,
+  // whether left control is held
+  pub ctrl_held: bool,
+  // whether the window has focus. Without it, the cursor is shown and isn't
+  // warped back to the center.
+  pub has_focus: bool,
+  // set on refocusing or closing the menu, so the jump from wherever the
+  // cursor was back to the center isn't taken as mouse-look.
+  pub ignore_next_mouse_move: bool,
+  // last known cursor position, in HUD coordinates
+  pub cursor_position: Pnt2<GLfloat>,
+  pub settings: Settings,
+  // the registry of terrain types, indexed by `TerrainPiece::typ`
+  pub terrain_types: TerrainTypes,
+
+  // run at the end of every update
+  hooks: Vec<Box<WorldHook + 'static>>,
+  // run at the end of every frame, to draw over the HUD
+  render_hooks: Vec<Box<RenderHook + 'static>>,
 
   pub render_octree: bool,
   pub render_outlines: bool,
+  // how much the shade of terrain varies within a type; see
+  // `set_color_jitter`
+  pub color_jitter: GLfloat,
+  pub render_chunks: bool,
+  pub render_motion_blur: bool,
+  pub render_target_highlight: bool,
+  pub render_physics: bool,
+  // ambient light intensity, uploaded every frame
+  pub ambient: Vec3<GLfloat>,
+  // octree outlines fade out completely at this distance; 0 disables fading.
+  pub outline_fade_distance: GLfloat,
+  // show debugging readouts on the HUD
+  pub show_debug: bool,
+  // draw back faces (normally culled) in magenta
+  pub show_backfaces: bool,
+  // while set, the player is frozen in place and the arrow keys and
+  // page up/down move the camera in small steps.
+  pub nudge_mode: bool,
+
+  // the number of updates so far
+  pub ticks: u64,
+  // updates left in the current camera shake; 0 when there isn't one
+  pub shake_ticks: uint,
+  // when the current frame started, from `time::precise_time_ns`
+  pub frame_start_ns: Option<u64>,
+  // seconds between the starts of the last frame and the current one
+  frame_dt: f64,
+  // seconds of frames rendered so far, i.e. the sum of every `frame_dt`
+  pub frame_time: f64,
+  // the number of frames rendered so far
+  pub frames: u64,
+  // set to save a screenshot at the end of the next frame
+  pub screenshot_requested: bool,
+  // while set, frames are being saved for a timelapse
+  pub timelapse: Option<Timelapse>,
+
+  // when paused, the world isn't updated.
+  pub paused: bool,
+  // set to make the main loop exit.
+  pub quit: bool,
 
   pub timers: Rc<stopwatch::TimerSet>,
   pub gl: GLContext,
@@ -380,13 +530,13 @@ impl<'a> App<'a> {
   pub fn new() -> App<'a> {
     let timers = Rc::new(stopwatch::TimerSet::new());
     time!(timers.deref(), "load", || {
+      let settings = Settings::load(&Path::new(SETTINGS_PATH));
+      let terrain_types = TerrainTypes::load(&Path::new(TERRAIN_TYPES_PATH));
       let mut gl = GLContext::new();
 
       gl.print_stats();
 
-      gl::FrontFace(gl::CCW);
-      gl::CullFace(gl::BACK);
-      gl::Enable(gl::CULL_FACE);
+      gl.enable_culling(Default::default(), Default::default());
       gl.enable_alpha_blending();
       gl.enable_smooth_lines();
       gl.enable_depth_buffer(100.0);
@@ -397,6 +547,7 @@ impl<'a> App<'a> {
         Pnt3 { x: -512.0, y: -32.0, z: -512.0 },
         Pnt3 { x: 512.0, y: 512.0, z: 512.0 },
       );
+      let seed = WORLD_SEED;
 
       let texture_shader = {
         let texture_shader =
@@ -405,7 +556,11 @@ impl<'a> App<'a> {
             String::from_str("shaders/world_texture"),
             [ gl::VERTEX_SHADER, gl::FRAGMENT_SHADER, ].to_vec().into_iter(),
             &FromIterator::from_iter(
-              [(String::from_str("lighting"), (USE_LIGHTING as uint).to_string())].to_vec().into_iter(),
+              [
+                (String::from_str("lighting"), (USE_LIGHTING as uint).to_string()),
+                (String::from_str("max_terrain_types"), terrain::MAX_TERRAIN_TYPES.to_string()),
+                (String::from_str("terrain_type_count"), terrain_types.len().to_string()),
+              ].to_vec().into_iter(),
             ),
           )));
         if USE_LIGHTING {
@@ -416,17 +571,30 @@ impl<'a> App<'a> {
               intensity: Vec3::new(0.6, 0.6, 0.6),
             }
           );
-          texture_shader.borrow_mut().set_ambient_light(
-            &mut gl,
-            Vec3::new(0.4, 0.4, 0.4),
-          );
-          texture_shader.borrow_mut().set_ambient_light(
-            &mut gl,
-            Vec3::new(0.4, 0.4, 0.4),
-          );
+        }
+        for &(typ, info) in terrain_types.iter().iter() {
+          texture_shader.borrow_mut().set_color(&mut gl, typ.color_uniform(), info.color);
+          let emissive = if info.emissive { 1.0 } else { 0.0 };
+          texture_shader.borrow_mut().set_float(&mut gl, typ.emissive_uniform(), emissive);
+        }
+        texture_shader.borrow_mut().set_float(&mut gl, "color_jitter", COLOR_JITTER);
+        let smooth_colors = if settings.smooth_colors { 1.0 } else { 0.0 };
+        texture_shader.borrow_mut().set_float(&mut gl, "smooth_colors", smooth_colors);
+        texture_shader.borrow_mut().set_dither(&mut gl, settings.dither);
+        if USE_LIGHTING {
+          let shadows = if settings.shadows { 1.0 } else { 0.0 };
+          texture_shader.borrow_mut().set_float(&mut gl, "shadows", shadows);
+          texture_shader.borrow_mut().set_float(&mut gl, "shadow_bias", SHADOW_BIAS);
         }
         texture_shader
       };
+      let shadow_shader =
+        Rc::new(RefCell::new(shader::from_file_prefix(
+          &mut gl,
+          String::from_str("shaders/shadow_depth"),
+          [ gl::VERTEX_SHADER, gl::FRAGMENT_SHADER, ].to_vec().into_iter(),
+          &HashMap::new(),
+        )));
       let color_shader =
         Rc::new(RefCell::new(shader::from_file_prefix(
           &mut gl,
@@ -434,6 +602,8 @@ impl<'a> App<'a> {
           [ gl::VERTEX_SHADER, gl::FRAGMENT_SHADER, ].to_vec().into_iter(),
           &HashMap::new(),
         )));
+      // only the world is dithered; the HUD's flat colors don't band.
+      color_shader.borrow_mut().set_dither(&mut gl, settings.dither);
       let hud_color_shader =
         Rc::new(RefCell::new(shader::from_file_prefix(
           &mut gl,
@@ -466,6 +636,8 @@ impl<'a> App<'a> {
         err => fail!("OpenGL error 0x{:x} setting up shaders", err),
       }
 
+      let sun = Sun::new(&gl, color_shader.clone(), SUN_DIRECTION);
+
       let line_of_sight = {
         let mut line_of_sight = {
           GLArray::new(
@@ -475,7 +647,7 @@ impl<'a> App<'a> {
               vertex::AttribData { name: "in_color", size: 4, unit: vertex::Float },
             ],
             Lines,
-            GLBuffer::new(2 * 2),
+            GLBuffer::new(2 * 2, DynamicDraw),
           )
         };
 
@@ -495,37 +667,158 @@ impl<'a> App<'a> {
         line_of_sight
       };
 
-      let hud_triangles = make_hud(&gl, hud_color_shader.clone());
+      let selection_outlines =
+        GLArray::new(
+          &gl,
+          color_shader.clone(),
+          [ vertex::AttribData { name: "position", size: 3, unit: vertex::Float },
+            vertex::AttribData { name: "in_color", size: 4, unit: vertex::Float },
+          ],
+          Lines,
+          GLBuffer::new(MAX_SELECTION * LINE_VERTICES_PER_BOX, DynamicDraw),
+        );
+
+      let reach_ring =
+        GLArray::new(
+          &gl,
+          color_shader.clone(),
+          [ vertex::AttribData { name: "position", size: 3, unit: vertex::Float },
+            vertex::AttribData { name: "in_color", size: 4, unit: vertex::Float },
+          ],
+          Lines,
+          GLBuffer::new(REACH_RING_SEGMENTS * VERTICES_PER_LINE, DynamicDraw),
+        );
+
+      let target_highlight =
+        GLArray::new(
+          &gl,
+          color_shader.clone(),
+          [ vertex::AttribData { name: "position", size: 3, unit: vertex::Float },
+            vertex::AttribData { name: "in_color", size: 4, unit: vertex::Float },
+          ],
+          Triangles,
+          GLBuffer::new(TRIANGLE_VERTICES_PER_BOX, DynamicDraw),
+        );
+
+      let chunk_outlines =
+        GLArray::new(
+          &gl,
+          color_shader.clone(),
+          [ vertex::AttribData { name: "position", size: 3, unit: vertex::Float },
+            vertex::AttribData { name: "in_color", size: 4, unit: vertex::Float },
+          ],
+          Lines,
+          GLBuffer::new(
+            chunk::chunks_around((0, 0, 0), CHUNK_OUTLINE_RADIUS).len() * LINE_VERTICES_PER_BOX,
+            DynamicDraw,
+          ),
+        );
+
+      let physics_outlines =
+        GLArray::new(
+          &gl,
+          color_shader.clone(),
+          [ vertex::AttribData { name: "position", size: 3, unit: vertex::Float },
+            vertex::AttribData { name: "in_color", size: 4, unit: vertex::Float },
+          ],
+          Lines,
+          GLBuffer::new(MAX_PHYSICS_OUTLINES * LINE_VERTICES_PER_BOX, DynamicDraw),
+        );
+
+      let hud_triangles = make_hud(&gl, hud_color_shader.clone(), settings.crosshair);
+      let dig_bar =
+        GLArray::new(
+          &gl,
+          hud_color_shader.clone(),
+          [ vertex::AttribData { name: "position", size: 3, unit: vertex::Float },
+            vertex::AttribData { name: "in_color", size: 4, unit: vertex::Float },
+          ],
+          Triangles,
+          GLBuffer::new(2 * 6, DynamicDraw),
+        );
 
       let octree_loader = Rc::new(RefCell::new(Queue::new(4 * MAX_WORLD_SIZE)));
 
-      let octree_buffers = unsafe {
-        octree::OctreeBuffers::new(&gl, &color_shader)
-      };
+      let octree_buffers =
+        if settings.octree_outlines {
+          Some(unsafe { octree::OctreeBuffers::new(&gl, &color_shader) })
+        } else {
+          None
+        };
 
       let mut texture_unit_alloc: IdAllocator<TextureUnit> = IdAllocator::new();
 
       let terrain_buffers = {
-        let terrain_buffers = terrain::TerrainBuffers::new(&gl);
+        let terrain_buffers = terrain::TerrainBuffers::new(&gl, seed, terrain_types.len());
         terrain_buffers.bind(&mut gl, &mut texture_unit_alloc, texture_shader.clone());
+        terrain_buffers.bind_positions(&mut gl, &mut texture_unit_alloc, shadow_shader.clone());
         terrain_buffers
       };
 
-      let (text_textures, text_triangles) = make_text(&gl, hud_texture_shader.clone());
+      let shadow_map = {
+        let unit = texture_unit_alloc.allocate();
+        let shadow_map = ShadowMap::new(&gl, &unit);
+        if USE_LIGHTING {
+          texture_shader.borrow_mut().with_uniform_location(&mut gl, "shadow_map", |loc| {
+            gl::Uniform1i(loc, unit.glsl_id as GLint);
+          });
+        }
+        shadow_map
+      };
 
-      let mut physics =
-        Physics {
-          octree: octree::Octree::new(octree_loader.clone(), &world_bounds),
-          bounds: HashMap::new(),
+      let skybox =
+        match settings.skybox {
+          None => None,
+          Some(ref dir) => {
+            let unit = texture_unit_alloc.allocate();
+            match Skybox::load(&mut gl, &unit, &Path::new(dir.as_slice())) {
+              Ok(skybox) => Some(skybox),
+              Err(e) => {
+                warn!("Couldn't load skybox \"{}\": {}", dir, e);
+                None
+              },
+            }
+          },
         };
 
+      let font = fontloader::FontLoader::new();
+      let motion_blur = MotionBlur::new(&gl, hud_texture_shader.clone());
+      let help = help::make_overlay(&gl, hud_texture_shader.clone(), hud_color_shader.clone(), &font);
+      let menu = Menu::new(&gl, hud_color_shader.clone(), hud_texture_shader.clone(), &font);
+      let mut text_overlay =
+        TextOverlay::new(&gl, hud_texture_shader.clone(), hud_color_shader.clone(), 16);
+      let (target_info, measure_info, speed_info, console, profiler) = {
+        let aspect = WINDOW_WIDTH as GLfloat / WINDOW_HEIGHT as GLfloat;
+        let white = Color4::of_rgba(0xFF, 0xFF, 0xFF, 0xFF);
+        let panel = Some(TEXT_PANEL_COLOR);
+        (
+          text_overlay.add_line(Pnt2::new(0.03 - aspect, -0.9), white, panel),
+          text_overlay.add_line(Pnt2::new(0.03 - aspect, -0.8), white, panel),
+          text_overlay.add_line(Pnt2::new(0.03 - aspect, -0.7), white, panel),
+          Console::new(
+            text_overlay.add_line(Pnt2::new(0.03 - aspect, 0.97), white, panel),
+            text_overlay.add_line(Pnt2::new(0.03 - aspect, 0.9), white, panel),
+          ),
+          Profiler::new(
+            range(0, PROFILER_LINES)
+              .map(|i| text_overlay.add_line(Pnt2::new(0.03 - aspect, 0.8 - 0.1 * i as GLfloat), white, panel))
+              .collect(),
+          ),
+        )
+      };
+
+      let mut physics = Physics::new(octree_loader.clone(), world_bounds);
+      physics.skin_width = settings.skin_width;
+
       let mut id_allocator = IdAllocator::new();
 
       let (terrains, terrain_loader) =
         time!(timers.deref(), "make_terrain", || {
           make_terrain(
             &mut physics,
+            &terrain_types,
             &mut id_allocator,
+            seed,
           )
         });
 
@@ -539,31 +832,14 @@ impl<'a> App<'a> {
           )
         });
 
-      let player = {
-        let mut player = Player {
-          camera: Camera::unit(),
-          speed: Vec3::new(0.0, 0.0, 0.0),
-          accel: Vec3::new(0.0, -0.1, 0.0),
-          walk_accel: Vec3::new(0.0, 0.0, 0.0),
-          jump_fuel: 0,
-          is_jumping: false,
-          id: id_allocator.allocate(),
-          lateral_rotation: 0.0,
-          vertical_rotation: 0.0,
-        };
-
-        let min = Pnt3::new(0.0, 64.0, 4.0);
-        let max = min + Vec3::new(1.0, 2.0, 1.0);
-        let bounds = AABB::new(min, max);
-        physics.insert(player.id, &bounds);
-
-        // initialize the projection matrix
-        player.camera.translate(center(&bounds).to_vec());
-        player.camera.fov = camera::perspective(3.14/3.0, 4.0/3.0, 0.1, 100.0);
-        player.rotate_lateral(PI / 2.0);
-
-        player
-      };
+      let player =
+        make_player(
+          &mut physics,
+          &mut id_allocator,
+          Pnt3::new(0.0, 64.0, 4.0),
+          &settings,
+          WINDOW_WIDTH as GLfloat / WINDOW_HEIGHT as GLfloat,
+        );
 
       let misc_texture_unit = texture_unit_alloc.allocate();
       gl::ActiveTexture(misc_texture_unit.gl_id());
@@ -584,6 +860,14 @@ impl<'a> App<'a> {
 
       App {
         line_of_sight: line_of_sight,
+        selection_outlines: selection_outlines,
+        reach_ring: reach_ring,
+        target_highlight: target_highlight,
+        chunk_outlines: chunk_outlines,
+        chunk_outlines_around: None,
+        physics_outlines: physics_outlines,
+        sun: sun,
+        skybox: skybox,
         physics: physics,
         terrain_loader: terrain_loader,
         octree_loader: octree_loader,
@@ -591,30 +875,940 @@ impl<'a> App<'a> {
         octree_buffers: octree_buffers,
         terrain_buffers: terrain_buffers,
         terrains: terrains,
+        seed: seed,
         player: player,
+        path: None,
+        tween: None,
+        tour_stop: None,
+        player2: None,
+        id_allocator: id_allocator,
         mobs: mobs,
+        selection: HashSet::new(),
+        dig: None,
+        unsettled: Vec::new(),
+        flashing: Vec::new(),
         hud_triangles: hud_triangles,
-        text_textures: text_textures,
-        text_triangles: text_triangles,
+        dig_bar: dig_bar,
+        motion_blur: motion_blur,
+        font: font,
+        menu: menu,
+        text_overlay: text_overlay,
+        help: help,
+        show_help: false,
+        target_info: target_info,
+        speed_info: speed_info,
+        profiler: profiler,
+        console: console,
+        measure: measure::Inactive,
+        measure_info: measure_info,
         misc_texture_unit: misc_texture_unit,
         color_shader: color_shader,
         texture_shader: texture_shader,
+        shadow_shader: shadow_shader,
+        shadow_map: shadow_map,
         hud_color_shader: hud_color_shader,
         hud_texture_shader: hud_texture_shader,
         mouse_buttons_pressed: Vec::new(),
+        ctrl_held: false,
+        has_focus: true,
+        ignore_next_mouse_move: false,
+        cursor_position: Pnt2::new(0.0, 0.0),
+        settings: settings,
+        terrain_types: terrain_types,
+        hooks: Vec::new(),
+        render_hooks: Vec::new(),
         render_octree: false,
         render_outlines: false,
+        color_jitter: COLOR_JITTER,
+        render_chunks: false,
+        render_motion_blur: false,
+        render_target_highlight: false,
+        render_physics: false,
+        ambient: Vec3::new(AMBIENT_LIGHT, AMBIENT_LIGHT, AMBIENT_LIGHT),
+        outline_fade_distance: 32.0,
+        show_debug: false,
+        show_backfaces: false,
+        nudge_mode: false,
+        ticks: 0,
+        shake_ticks: 0,
+        frame_start_ns: None,
+        frame_dt: 0.0,
+        frame_time: 0.0,
+        frames: 0,
+        screenshot_requested: false,
+        timelapse: None,
+        paused: false,
+        quit: false,
         timers: timers.clone(),
         gl: gl,
       }
     })
   }
 
+  /// Run exactly one step of the simulation, without loading anything into
+  /// OpenGL or rendering.
+  pub fn tick(&mut self) {
+    update::tick(self);
+  }
+
   #[inline]
   pub fn is_mouse_pressed(&self, b: input::mouse::Button) -> bool {
     self.mouse_buttons_pressed.iter().any(|x| *x == b)
   }
 
+  /// Change the color that all terrain of type `typ` is drawn with. Channels
+  /// outside [0, 1] are clamped.
+  /// Only the terrain shader is affected; outlines and mobs keep their colors.
+  pub fn recolor_type(&mut self, typ: terrain::TerrainType, color: Color4<GLfloat>) {
+    self.texture_shader.borrow_mut().set_color(&mut self.gl, typ.color_uniform(), color.clamp01());
+  }
+
+  /// Remove every piece of terrain of type `typ`, returning how many there were.
+  /// The ids are collected before anything is queued, and the pieces are only
+  /// unloaded (and the buffers reshuffled) when the loader queue is processed.
+  pub fn remove_all_of_type(&mut self, typ: terrain::TerrainType) -> uint {
+    let ids: Vec<EntityId> =
+      self.terrains.iter()
+        .filter(|&(_, terrain)| terrain::TerrainType(terrain.typ) == typ)
+        .map(|(&id, _)| id)
+        .collect();
+    for &id in ids.iter() {
+      self.terrain_loader.push(Unload(id));
+    }
+    ids.len()
+  }
+
+  /// Set how much the shade of each piece of terrain varies from its type's
+  /// color, as a fraction of that color. 0 disables the variation.
+  pub fn set_color_jitter(&mut self, amount: GLfloat) {
+    self.color_jitter = amount;
+    self.texture_shader.borrow_mut().set_float(&mut self.gl, "color_jitter", amount);
+  }
+
+  /// Turn the variation in shade within each terrain type off, or back on.
+  pub fn toggle_color_jitter(&mut self) {
+    let amount = if self.color_jitter > 0.0 { 0.0 } else { COLOR_JITTER };
+    self.set_color_jitter(amount);
+  }
+
+  /// Turn back face highlighting on or off. While it's on, back faces aren't
+  /// culled, and are drawn magenta, so inside-out geometry stands out.
+  pub fn toggle_backfaces(&mut self) {
+    self.show_backfaces = !self.show_backfaces;
+    let flag = if self.show_backfaces { 1.0 } else { 0.0 };
+    self.color_shader.borrow_mut().set_float(&mut self.gl, "show_backfaces", flag);
+    self.texture_shader.borrow_mut().set_float(&mut self.gl, "show_backfaces", flag);
+    if self.show_backfaces {
+      self.gl.disable_culling();
+    } else {
+      self.gl.enable_culling(Default::default(), Default::default());
+    }
+  }
+
+  /// Brighten (or, for negative `delta`, darken) the ambient light, keeping
+  /// each component in [0, 1].
+  pub fn adjust_ambient(&mut self, delta: GLfloat) {
+    let clamp = |x: GLfloat| (x + delta).max(0.0).min(1.0);
+    self.ambient = Vec3::new(clamp(self.ambient.x), clamp(self.ambient.y), clamp(self.ambient.z));
+  }
+
+  /// Open or close the console. Movement stops while it's open, since keys go
+  /// to the console instead.
+  pub fn toggle_console(&mut self) {
+    self.console.is_open = !self.console.is_open;
+    self.console.input.clear();
+    self.stop_moving();
+    self.refresh_console();
+  }
+
+  /// Re-render the console's input line.
+  pub fn refresh_console(&mut self) {
+    let text = self.console.input_text(self.frame_time);
+    self.text_overlay.set_text(self.font.get(fontloader::Mono), self.console.input_line, text.as_slice());
+  }
+
+  /// Run whatever's been typed into the console, and show the result.
+  pub fn submit_console(&mut self) {
+    let line = self.console.input.clone();
+    self.console.input.clear();
+    let output =
+      match console::parse(line.as_slice(), &self.terrain_types) {
+        Err(e) => e,
+        Ok(command) => self.run_command(command),
+      };
+    self.text_overlay.set_text(self.font.get(fontloader::Mono), self.console.output_line, output.as_slice());
+    self.refresh_console();
+  }
+
+  /// Run a console command, returning a description of the result.
+  pub fn run_command(&mut self, command: console::Command) -> String {
+    match command {
+      console::Teleport(p) => {
+        if self.player.teleport(&mut self.physics, p) {
+          format!("teleported to {}", p)
+        } else {
+          String::from_str("can't teleport outside the world")
+        }
+      },
+      console::TeleportToTop(x, z) => {
+        match self.teleport_to_top(x, z) {
+          Ok(p) => format!("teleported to {}", p),
+          Err(e) => e,
+        }
+      },
+      console::Clear(typ) => {
+        let count = self.remove_all_of_type(typ);
+        format!("removing {} {}", count, self.terrain_types.name(typ))
+      },
+      console::Recolor(typ, color) => {
+        self.recolor_type(typ, color);
+        format!("recolored {}", self.terrain_types.name(typ))
+      },
+      console::Clip(near, far) => {
+        if self.set_clip_planes(near, far) {
+          format!("clip planes set to {} and {}", near, far)
+        } else {
+          String::from_str("need 0 < near < far")
+        }
+      },
+      console::Stress(count) => self.stress(count),
+      console::Explode(center, radius) => {
+        let count = self.explode(center, radius);
+        format!("blew up {} pieces", count)
+      },
+      console::Settle => {
+        let count = self.settle();
+        format!("{} pieces fell", count)
+      },
+      console::Gravity(g) => {
+        self.player.set_gravity(g);
+        format!("gravity set to {}", g)
+      },
+      console::Size(w, h) => {
+        if self.set_player_size(w, h) {
+          format!("resized to {} x {}", w, h)
+        } else {
+          String::from_str("no room to resize")
+        }
+      },
+      console::FollowPath(waypoints, speed) => {
+        let count = waypoints.len();
+        self.follow_path(waypoints, speed);
+        format!("following {} waypoints", count)
+      },
+      console::Step(count) => {
+        for _ in range(0, count) {
+          self.tick();
+        }
+        format!("ran {} steps", count)
+      },
+    }
+  }
+
+  /// Teleport the player to stand on the highest solid terrain in the column
+  /// through (x, z), just clear of it, and return where they ended up. Nothing
+  /// solid is above the top of the column, so they can't land inside
+  /// anything. Players, mobs and non-solid terrain in the column are ignored.
+  pub fn teleport_to_top(&mut self, x: GLfloat, z: GLfloat) -> Result<Pnt3<GLfloat>, String> {
+    let top = {
+      let terrains = &self.terrains;
+      let terrain_types = &self.terrain_types;
+      let is_solid_terrain = |id: EntityId| {
+        terrains.find(&id)
+          .and_then(|t| terrain_types.get(terrain::TerrainType(t.typ)))
+          .map_or(false, |info| info.solid)
+      };
+      match self.physics.column_top(x, z, is_solid_terrain) {
+        None => return Err(format!("no solid terrain at {} {}", x, z)),
+        Some(top) => top,
+      }
+    };
+    // how far above their feet the player's eye is.
+    let eye_height = {
+      let bounds = self.physics.get_bounds(self.player.id).unwrap();
+      self.player.camera.position.y - bounds.mins().y
+    };
+    let p = Pnt3::new(x, top + self.physics.skin_width + eye_height, z);
+    if !self.player.teleport(&mut self.physics, p) {
+      return Err(String::from_str("can't teleport outside the world"));
+    }
+    self.player.speed = Vec3::new(0.0, 0.0, 0.0);
+    Ok(p)
+  }
+
+  /// Move the player through `waypoints` (position, yaw, pitch), `speed`
+  /// units per update, ignoring physics until the last one is reached.
+  pub fn follow_path(&mut self, waypoints: Vec<(Pnt3<GLfloat>, GLfloat, GLfloat)>, speed: GLfloat) {
+    let waypoints =
+      waypoints.into_iter()
+        .map(|(position, yaw, pitch)| Waypoint { position: position, yaw: yaw, pitch: pitch })
+        .collect();
+    self.player.speed = Vec3::new(0.0, 0.0, 0.0);
+    self.path = Some(PathFollower::new(waypoints, speed));
+  }
+
+  /// Place up to `count` small random terrain pieces around the world, for
+  /// stress testing. Returns a report of how many were placed, how long that
+  /// took, and how full the terrain buffers now are. Pieces that would collide
+  /// with something are skipped, and placement stops when the buffers or the
+  /// load queue are full.
+  pub fn stress(&mut self, count: uint) -> String {
+    let room =
+      cmp::min(
+        MAX_WORLD_SIZE - self.terrains.len(),
+        self.terrain_loader.contents.capacity() - self.terrain_loader.len(),
+      );
+    let mut rng = Rng::new(self.seed ^ self.terrains.len() as u32);
+    let world = self.physics.world_bounds.clone();
+    let mut placed = 0u;
+    let mut attempts = 0u;
+
+    let before = self.timers.get("stress").map(|sw| sw.total_time).unwrap_or(0);
+    time!(self.timers.deref(), "stress", || {
+      while attempts < count && placed < room {
+        attempts += 1;
+        let corner =
+          Pnt3::new(
+            rng.range_f32(world.mins().x, world.maxs().x - 1.0),
+            rng.range_f32(world.mins().y, world.maxs().y),
+            rng.range_f32(world.mins().z, world.maxs().z - 1.0),
+          );
+        // a flat triangle, facing up.
+        let vertices = [
+          corner,
+          corner + Vec3::new(0.0, 0.0, 1.0),
+          corner + Vec3::new(1.0, 0.0, 0.0),
+        ];
+        let bounds = aabb_of_points(vertices).unwrap();
+        let id =
+          place_terrain(
+            &mut self.physics,
+            &self.terrain_types,
+            &mut self.terrains,
+            &mut self.terrain_loader,
+            &mut self.id_allocator,
+            bounds,
+            vertices,
+            Vec3::new(0.0, 1.0, 0.0),
+            terrain::STONE,
+            true,
+            self.ticks,
+          );
+        if id.is_some() {
+          placed += 1;
+        }
+      }
+    });
+    let after = self.timers.get("stress").map(|sw| sw.total_time).unwrap_or(0);
+
+    let report =
+      format!(
+        "placed {} of {} in {}ms; terrain buffers {:.1}% full",
+        placed,
+        count,
+        (after - before) / 1000000,
+        100.0 * self.terrains.len() as f32 / MAX_WORLD_SIZE as f32,
+      );
+    info!("stress: {}", report);
+    report
+  }
+
+  /// The ids of everything sharing a face with `id`.
+  pub fn neighbors(&self, id: EntityId) -> Vec<EntityId> {
+    self.physics.neighbors(id)
+  }
+
+  /// Blow up everything around `center`: every piece of terrain whose bounds
+  /// are centered within `radius` of it is removed, and a nearby player is
+  /// knocked away from it. Returns how many pieces were removed.
+  pub fn explode(&mut self, center: Pnt3<GLfloat>, radius: GLfloat) -> uint {
+    let mut removed = 0;
+    for id in self.physics.in_sphere(&center, radius).into_iter() {
+      if self.terrains.contains_key(&id) {
+        self.terrain_loader.push(Unload(id));
+        removed += 1;
+      }
+    }
+
+    let range = KNOCKBACK_RANGE * radius;
+    let away = ::common::center(self.get_bounds(self.player.id)) - center;
+    let distance = away.norm();
+    if distance < range {
+      let direction =
+        if distance > 0.0 {
+          away / distance
+        } else {
+          self.player.up()
+        };
+      let push = direction * KNOCKBACK_SPEED * radius * (1.0 - distance / range);
+      let v = self.player_velocity() + push;
+      self.set_player_velocity(v);
+    }
+    removed
+  }
+
+  /// Select the terrain piece `id` and all the terrain of the same type
+  /// connected to it by shared faces, up to `MAX_SELECTION` pieces, replacing
+  /// any previous selection. Returns the selected ids.
+  pub fn select_connected(&mut self, id: EntityId) -> HashSet<EntityId> {
+    let selection =
+      match self.terrains.find(&id) {
+        None => HashSet::new(),
+        Some(terrain) => {
+          let typ = terrain.typ;
+          let terrains = &self.terrains;
+          let physics = &self.physics;
+          select::flood_fill(id, MAX_SELECTION, |id| {
+            physics.neighbors(id).into_iter()
+              .filter(|n| terrains.find(n).map_or(false, |t| t.typ == typ))
+              .collect()
+          })
+        },
+      };
+    self.set_selection(selection.clone());
+    selection
+  }
+
+  /// Replace the selection, and outline the new one.
+  pub fn set_selection(&mut self, selection: HashSet<EntityId>) {
+    let length = self.selection_outlines.buffer.length;
+    if length > 0 {
+      self.selection_outlines.swap_remove(0, length);
+    }
+    for id in selection.iter() {
+      let bounds = self.get_bounds(*id).clone();
+      self.selection_outlines.push(to_outlines(&bounds, &SELECTION_COLOR));
+    }
+    self.selection = selection;
+  }
+
+  /// Queue every selected piece of terrain for removal, and clear the
+  /// selection. Returns how many pieces were removed.
+  pub fn remove_selection(&mut self) -> uint {
+    let mut count = 0;
+    for id in self.selection.iter() {
+      if self.terrains.contains_key(id) {
+        self.terrain_loader.push(Unload(*id));
+        count += 1;
+      }
+    }
+    self.set_selection(HashSet::new());
+    count
+  }
+
+  /// Drop any small structures that were resting on terrain removed since the
+  /// last call, like sand: each connected group of terrain falls straight
+  /// down as one piece until it lands on something. Groups of at least
+  /// `MAX_FALLING_STRUCTURE` pieces are part of the landscape and stay put.
+  /// Returns how many pieces fell.
+  pub fn settle(&mut self) -> uint {
+    let removed = mem::replace(&mut self.unsettled, Vec::new());
+
+    // whatever was touching the removed terrain might have been resting on it.
+    let mut candidates = HashSet::new();
+    for &(id, ref bounds) in removed.iter() {
+      for other in self.physics.octree.intersect_details(&bounds.loosened(0.001), Some(id)).into_iter() {
+        if self.terrains.contains_key(&other) {
+          candidates.insert(other);
+        }
+      }
+    }
+
+    // settle the lowest things first, so the ones above them land on them
+    // where they end up.
+    let mut candidates: Vec<EntityId> = candidates.into_iter().collect();
+    candidates.sort_by(|a, b| {
+      let (a, b) = (self.get_bounds(*a).mins().y, self.get_bounds(*b).mins().y);
+      a.partial_cmp(&b).unwrap_or(cmp::Equal)
+    });
+
+    let mut seen = HashSet::new();
+    let mut fallen = 0;
+    for &id in candidates.iter() {
+      if seen.contains(&id) {
+        continue;
+      }
+      let structure = {
+        let terrains = &self.terrains;
+        let physics = &self.physics;
+        select::flood_fill(id, MAX_FALLING_STRUCTURE, |id| {
+          physics.neighbors(id).into_iter()
+            .filter(|n| terrains.contains_key(n))
+            .collect()
+        })
+      };
+      seen.extend(structure.iter().map(|&id| id));
+      if structure.len() >= MAX_FALLING_STRUCTURE {
+        continue;
+      }
+
+      let distance = self.physics.drop_distance(&structure);
+      if distance > 0.0 {
+        for &id in structure.iter() {
+          self.move_terrain(id, Vec3::new(0.0, -distance, 0.0));
+        }
+        fallen += structure.len();
+      }
+    }
+    fallen
+  }
+
+  /// Move the terrain piece `id` by `delta`, without checking for collisions.
+  fn move_terrain(&mut self, id: EntityId, delta: Vec3<GLfloat>) {
+    let bounds = self.get_bounds(id).clone();
+    self.physics.reinsert(id, AABB::new(*bounds.mins() + delta, *bounds.maxs() + delta));
+
+    for v in self.terrains.find_mut(&id).unwrap().vertices.iter_mut() {
+      *v = *v + delta;
+    }
+    // if it hasn't been loaded yet, it'll be loaded where it is now.
+    if self.terrain_buffers.contains(id) {
+      let terrain = self.terrains.find(&id).unwrap();
+      self.terrain_buffers.swap_remove(id);
+      self.terrain_buffers.push(
+        id,
+        terrain,
+        terrain.ambient_occlusion(&self.physics),
+        terrain.type_weights(&self.physics, &self.terrains, self.terrain_types.len()).as_slice(),
+      );
+    }
+  }
+
+  /// Smoothly move the camera to look at the terrain piece `id` from a little
+  /// way in front of it. Returns false if there's no such piece.
+  pub fn fly_to(&mut self, id: EntityId) -> bool {
+    let normal =
+      match self.terrains.find(&id) {
+        None => return false,
+        Some(terrain) => terrain.normal,
+      };
+    let target = center(self.get_bounds(id));
+    let position = target + normal * FLY_TO_DISTANCE;
+    let (yaw, pitch) = self.player.look_angles(-normal);
+
+    let from = Waypoint {
+      position: self.player.camera.position,
+      yaw: self.player.lateral_rotation,
+      pitch: self.player.vertical_rotation,
+    };
+    let to = Waypoint { position: position, yaw: yaw, pitch: pitch };
+    self.player.speed = Vec3::new(0.0, 0.0, 0.0);
+    self.tween = Some(Tween::new(from, to, FLY_TO_STEPS));
+    true
+  }
+
+  /// The chunk containing `p`. See the `chunk` module.
+  pub fn chunk_of(&self, p: Pnt3<GLfloat>) -> ChunkCoords {
+    chunk::chunk_of(&p)
+  }
+
+  /// Every chunk that has the center of some loaded piece of terrain in it,
+  /// in sorted order.
+  pub fn loaded_chunks(&self) -> Vec<ChunkCoords> {
+    let mut chunks: Vec<ChunkCoords> =
+      self.terrains.keys()
+        .filter(|&&id| self.terrain_buffers.contains(id))
+        .map(|&id| chunk::chunk_of(&center(self.get_bounds(id))))
+        .collect();
+    chunks.sort();
+    chunks.dedup();
+    chunks
+  }
+
+  /// Note that a frame is starting at `now_ns` (from `time::precise_time_ns`),
+  /// to update `frame_dt` and `frame_time`.
+  pub fn start_frame(&mut self, now_ns: u64) {
+    self.frame_dt =
+      match self.frame_start_ns {
+        None => 0.0,
+        Some(then) => (now_ns - then) as f64 / 1.0e9,
+      };
+    self.frame_start_ns = Some(now_ns);
+    self.frame_time += self.frame_dt;
+    self.frames += 1;
+  }
+
+  /// Save whatever's on screen at the end of the next frame, as
+  /// screenshot-<frame>.png.
+  pub fn request_screenshot(&mut self) {
+    self.screenshot_requested = true;
+  }
+
+  /// Start or stop saving every `timelapse_interval`th frame. Each timelapse
+  /// goes in its own directory, named for the frame it started on.
+  pub fn toggle_timelapse(&mut self) {
+    match self.timelapse.take() {
+      Some(timelapse) => {
+        info!("Saved {} timelapse frames in \"{}\"", timelapse.saved(), timelapse.dir().display());
+      },
+      None => {
+        let dir = Path::new(format!("timelapse-{}", self.frames));
+        self.timelapse = Timelapse::start(dir, self.settings.timelapse_interval);
+      },
+    }
+  }
+
+  /// Save this frame if a screenshot was asked for or a timelapse wants it.
+  /// Call once everything's been drawn.
+  pub fn capture_frame(&mut self) {
+    if self.screenshot_requested {
+      self.screenshot_requested = false;
+      screenshot::save(&self.gl, &Path::new(format!("screenshot-{}.png", self.frames)));
+    }
+    let timelapse_frame =
+      match self.timelapse {
+        None => None,
+        Some(ref mut timelapse) => timelapse.frame(),
+      };
+    match timelapse_frame {
+      None => {},
+      Some(path) => screenshot::save(&self.gl, &path),
+    }
+  }
+
+  /// Seconds between the starts of the last frame and the current one, or 0
+  /// during the first frame. Unlike updates, frames don't come at a fixed
+  /// rate, so anything animated per frame should scale by this.
+  pub fn frame_dt(&self) -> f64 {
+    self.frame_dt
+  }
+
+  /// Turn outlining every box in the physics world near the player on or off.
+  pub fn toggle_physics_outlines(&mut self) {
+    self.render_physics = !self.render_physics;
+  }
+
+  /// Outline the physics boxes near the player, as they are right now, if
+  /// they're being drawn. Terrain and things that move get different colors.
+  pub fn update_physics_outlines(&mut self) {
+    let length = self.physics_outlines.buffer.length;
+    if length > 0 {
+      self.physics_outlines.swap_remove(0, length);
+    }
+    if !self.render_physics {
+      return;
+    }
+
+    let r = Vec3::new(PHYSICS_OUTLINE_RADIUS, PHYSICS_OUTLINE_RADIUS, PHYSICS_OUTLINE_RADIUS);
+    let position = self.player.camera.position;
+    let near = AABB::new(position - r, position + r);
+    let mut count = 0;
+    for (id, bounds) in self.physics.bounds.iter() {
+      if count == MAX_PHYSICS_OUTLINES {
+        break;
+      }
+      if !bounds.intersects(&near) {
+        continue;
+      }
+      let color =
+        if self.terrains.contains_key(id) {
+          &TERRAIN_PHYSICS_COLOR
+        } else {
+          &MOVING_PHYSICS_COLOR
+        };
+      self.physics_outlines.push(to_outlines(bounds, color));
+      count += 1;
+    }
+  }
+
+  /// Turn filling whatever's under the crosshair with a translucent color on
+  /// or off.
+  pub fn toggle_target_highlight(&mut self) {
+    self.render_target_highlight = !self.render_target_highlight;
+  }
+
+  /// Turn motion blur on or off. The strength comes from the `motion_blur`
+  /// setting.
+  pub fn toggle_motion_blur(&mut self) {
+    self.render_motion_blur = !self.render_motion_blur;
+    // don't blend in whatever was on screen when it was last turned off.
+    self.motion_blur.reset();
+  }
+
+  /// Turn drawing the chunk boundaries around the player on or off.
+  pub fn toggle_chunk_outlines(&mut self) {
+    self.render_chunks = !self.render_chunks;
+    self.chunk_outlines_around = None;
+    self.update_chunk_outlines();
+  }
+
+  /// Outline the chunks around the player, if chunk boundaries are being
+  /// drawn and the player has moved into a different chunk since they were
+  /// last outlined.
+  pub fn update_chunk_outlines(&mut self) {
+    if !self.render_chunks {
+      return;
+    }
+    let around = self.chunk_of(self.player.camera.position);
+    if self.chunk_outlines_around == Some(around) {
+      return;
+    }
+
+    let loaded = self.loaded_chunks();
+    let length = self.chunk_outlines.buffer.length;
+    if length > 0 {
+      self.chunk_outlines.swap_remove(0, length);
+    }
+    for &c in chunk::chunks_around(around, CHUNK_OUTLINE_RADIUS).iter() {
+      let color =
+        if loaded.contains(&c) {
+          &LOADED_CHUNK_COLOR
+        } else {
+          &EMPTY_CHUNK_COLOR
+        };
+      self.chunk_outlines.push(to_outlines(&chunk::chunk_bounds(c), color));
+    }
+    self.chunk_outlines_around = Some(around);
+  }
+
+  /// How far through digging out the terrain under the crosshair the player
+  /// is, from 0 to 1, or None if they aren't digging.
+  pub fn dig_progress(&self) -> Option<GLfloat> {
+    let (id, ticks) =
+      match self.dig {
+        None => return None,
+        Some(dig) => dig,
+      };
+    self.terrains.find(&id)
+      .and_then(|terrain| self.terrain_types.get(terrain::TerrainType(terrain.typ)))
+      .map(|info| info.dig_progress(ticks))
+  }
+
+  /// What's under the crosshair: its id, which face of its bounds the
+  /// player's looking at (a `bvh::Face`, as a number), and exactly where on
+  /// that face.
+  pub fn pick_point(&self) -> Option<(u32, uint, Pnt3<GLfloat>)> {
+    let ray = self.player.forward_ray();
+    self.physics.bvh.raycast(&ray.orig, &ray.dir, Some(self.player.id)).map(|(id, face, t)| {
+      let EntityId(id) = id;
+      (id, face as uint, ray.orig + ray.dir * t)
+    })
+  }
+
+  /// The ids of the terrain inside the player's view, in order. Anything
+  /// even partly inside counts, whether or not something else hides it.
+  pub fn visible_blocks(&self) -> Vec<u32> {
+    let frustum = Frustum::from_matrix(&self.player.camera.projection_matrix());
+    let mut ids: Vec<u32> =
+      self.terrains.keys()
+        .filter(|&&id| self.physics.get_bounds(id).map_or(false, |bounds| frustum.intersects(bounds)))
+        .map(|&EntityId(id)| id)
+        .collect();
+    ids.sort();
+    ids
+  }
+
+  /// Fly to the next piece of terrain on a tour of all of them, in order of
+  /// id. Returns the piece, or None if there's no terrain.
+  pub fn tour_next(&mut self) -> Option<EntityId> {
+    let ids: Vec<EntityId> = self.terrains.keys().map(|&id| id).collect();
+    let next = path::next_stop(ids.as_slice(), self.tour_stop);
+    match next {
+      None => {},
+      Some(id) => {
+        self.fly_to(id);
+      },
+    }
+    self.tour_stop = next;
+    next
+  }
+
+  /// Register a hook to be run at the end of every update.
+  #[allow(dead_code)]
+  pub fn add_hook(&mut self, hook: Box<WorldHook + 'static>) {
+    self.hooks.push(hook);
+  }
+
+  /// Run every registered hook. Hooks may register more hooks; those first
+  /// run on the next update.
+  pub fn run_hooks(&mut self) {
+    let mut hooks = mem::replace(&mut self.hooks, Vec::new());
+    for hook in hooks.iter_mut() {
+      hook.on_update(self);
+    }
+    let added = mem::replace(&mut self.hooks, hooks);
+    self.hooks.extend(added.into_iter());
+  }
+
+  /// Register a hook to draw over the HUD at the end of every frame. See
+  /// `RenderHook` for the state it's drawn in.
+  #[allow(dead_code)]
+  pub fn add_render_hook(&mut self, hook: Box<RenderHook + 'static>) {
+    self.render_hooks.push(hook);
+  }
+
+  /// Run every registered render hook, in the order they were added.
+  pub fn run_render_hooks(&mut self) {
+    for hook in self.render_hooks.iter_mut() {
+      hook.on_render(&mut self.gl, &self.hud_color_shader, &self.hud_texture_shader);
+    }
+  }
+
+  /// The player's velocity, in world units per update.
+  pub fn player_velocity(&self) -> Vec3<GLfloat> {
+    self.player.speed
+  }
+
+  /// Set the player's velocity, in world units per update, e.g. to knock them
+  /// back. The next update moves the player by `v` (stopping along any axis
+  /// where they hit something, the same as ordinary movement), and then
+  /// applies walking, gravity and friction to it as usual. While the player is
+  /// following a path, tweening or nudging, the velocity is kept until that
+  /// ends.
+  pub fn set_player_velocity(&mut self, v: Vec3<GLfloat>) {
+    self.player.speed = v;
+  }
+
+  /// Turn nudge mode on or off.
+  pub fn toggle_nudge_mode(&mut self) {
+    self.nudge_mode = !self.nudge_mode;
+    self.player.speed = Vec3::new(0.0, 0.0, 0.0);
+  }
+
+  /// Turn the measure tool on or off.
+  pub fn toggle_measure(&mut self) {
+    self.measure = self.measure.toggle();
+    self.text_overlay.set_text(self.font.get(fontloader::Mono), self.measure_info, self.measure.prompt());
+  }
+
+  /// Feed a click on `id` to the measure tool, showing the distance once two
+  /// things have been clicked.
+  pub fn measure_pick(&mut self, id: EntityId) {
+    let (state, measured) = self.measure.pick(id);
+    self.measure = state;
+    let text =
+      match measured {
+        None => String::from_str(state.prompt()),
+        Some((a, b)) => measure::describe(&self.physics, a, b),
+      };
+    self.text_overlay.set_text(self.font.get(fontloader::Mono), self.measure_info, text.as_slice());
+  }
+
+  /// Change the near and far clip planes. Returns false (and changes nothing)
+  /// unless 0 < `near` < `far`.
+  pub fn set_clip_planes(&mut self, near: GLfloat, far: GLfloat) -> bool {
+    if !settings::valid_clip_planes(near, far) {
+      return false;
+    }
+    self.settings.near = near;
+    self.settings.render_distance = far;
+    self.update_projection();
+    true
+  }
+
+  /// Rebuild the players' projection matrices from the current settings,
+  /// offset by the current camera shake, if there is one.
+  pub fn update_projection(&mut self) {
+    let mut aspect = WINDOW_WIDTH as GLfloat / WINDOW_HEIGHT as GLfloat;
+    match self.player2 {
+      None => {},
+      Some(ref mut player2) => {
+        aspect = aspect / 2.0;
+        player2.camera.fov = player_fov(&self.settings, aspect);
+      },
+    }
+    let shake = shake::offset(self.settings.camera_shake, self.shake_ticks, self.seed, self.ticks);
+    self.player.camera.fov = player_fov(&self.settings, aspect) * camera::translation(-shake);
+  }
+
+  /// Start shaking the view, if the `camera_shake` setting allows it. A shake
+  /// already underway starts over.
+  pub fn start_shake(&mut self) {
+    if self.settings.camera_shake > 0.0 {
+      self.shake_ticks = SHAKE_TICKS;
+    }
+  }
+
+  /// Add or remove a second local player. While there is one, the window is
+  /// split into a left half for `player` and a right half for `player2`.
+  pub fn toggle_split_screen(&mut self) {
+    let full_aspect = WINDOW_WIDTH as GLfloat / WINDOW_HEIGHT as GLfloat;
+    match self.player2.take() {
+      Some(player2) => {
+        self.physics.remove(player2.id);
+      },
+      None => {
+        let player2 =
+          make_player(
+            &mut self.physics,
+            &mut self.id_allocator,
+            Pnt3::new(2.0, 64.0, 4.0),
+            &self.settings,
+            full_aspect / 2.0,
+          );
+        self.player2 = Some(player2);
+      },
+    }
+    self.update_projection();
+  }
+
+  /// Release the mouse when the window loses focus (e.g. alt-tab), and
+  /// recapture it when focus comes back.
+  pub fn set_focus(&mut self, has_focus: bool) {
+    if has_focus == self.has_focus {
+      return;
+    }
+    self.has_focus = has_focus;
+    if has_focus {
+      self.ignore_next_mouse_move = true;
+      // the menu keeps the cursor visible.
+      if !self.menu.is_open() {
+        mouse::show_cursor(false);
+      }
+    } else {
+      // key releases won't be seen while we're out of focus.
+      self.stop_moving();
+      mouse::show_cursor(true);
+    }
+  }
+
+  /// Open the in-game menu, pausing the game and releasing the cursor.
+  pub fn open_menu(&mut self) {
+    self.menu.open();
+    self.paused = true;
+    self.stop_moving();
+    mouse::show_cursor(true);
+  }
+
+  /// Close the in-game menu and resume the game.
+  pub fn close_menu(&mut self) {
+    self.menu.close();
+    self.paused = false;
+    self.stop_moving();
+    self.ignore_next_mouse_move = true;
+    mouse::show_cursor(!self.has_focus);
+  }
+
+  // Key presses and releases aren't seen while the menu is open, so the
+  // players' walking and jumping state has to be reset around it.
+  fn stop_moving(&mut self) {
+    self.ctrl_held = false;
+    self.player.stop_walking();
+    self.player.stop_jump();
+    match self.player2 {
+      None => {},
+      Some(ref mut player2) => {
+        player2.stop_walking();
+        player2.stop_jump();
+      },
+    }
+  }
+
+  /// Resize the player's box, keeping it centered where it is. If the resized
+  /// box would collide with something or stick out of the world, nothing
+  /// changes and false is returned.
+  pub fn set_player_size(&mut self, width: GLfloat, height: GLfloat) -> bool {
+    let c = center(self.get_bounds(self.player.id));
+    let half_size = Vec3::new(width, height, width) / (2.0 as GLfloat);
+    let bounds = AABB::new(c - half_size, c + half_size);
+    if self.physics.collides(&bounds, Some(self.player.id)) {
+      return false;
+    }
+
+    // The center doesn't move, so neither does the camera.
+    self.physics.reinsert(self.player.id, bounds)
+  }
+
   fn get_bounds(&self, id: EntityId) -> &AABB {
     self.physics.get_bounds(id).unwrap()
   }
@@ -624,11 +1818,18 @@ impl<'a> App<'a> {
     mobs: &mut HashMap<EntityId, mob::Mob>,
     mob_buffers: &mut mob::MobBuffers,
     id_allocator: &mut IdAllocator<EntityId>,
-    low_corner: Pnt3<GLfloat>,
+    corner: Pnt3<GLfloat>,
+    opposite_corner: Pnt3<GLfloat>,
     behavior: mob::Behavior,
   ) {
     // TODO: mob loader instead of pushing directly to gl buffers
 
+    let bounds = aabb_of_corners(&corner, &opposite_corner);
+    if has_zero_volume(&bounds) {
+      warn!("Not adding a mob with zero-volume bounds {} to {}", bounds.mins(), bounds.maxs());
+      return;
+    }
+
     let id = id_allocator.allocate();
 
     let mob =
@@ -638,7 +1839,6 @@ impl<'a> App<'a> {
         id: id,
       };
 
-    let bounds = AABB::new(low_corner, low_corner + Vec3::new(1.0, 2.0, 1.0 as GLfloat));
     mob_buffers.push(id, to_triangles(&bounds, &Color4::of_rgba(1.0, 0.0, 0.0, 1.0)));
 
     physics.insert(id, &bounds);
@@ -646,8 +1846,12 @@ impl<'a> App<'a> {
   }
 }
 
+/// Add a terrain piece, placed on update `created_at`. Returns its id, or None
+/// if it's outside the world, or if `check_collisions` is set and it would
+/// collide with something. Things pass through it unless its type is solid.
 fn place_terrain(
   physics: &mut Physics<EntityId>,
+  terrain_types: &TerrainTypes,
   terrains: &mut HashMap<EntityId, terrain::TerrainPiece>,
   terrain_loader: &mut Loader<EntityId, EntityId>,
   id_allocator: &mut IdAllocator<EntityId>,
@@ -656,21 +1860,65 @@ fn place_terrain(
   normal: Vec3<GLfloat>,
   typ: terrain::TerrainType,
   check_collisions: bool,
-) {
+  created_at: u64,
+) -> Option<EntityId> {
   // hacky solution to make sure terrain polys have "breathing room" and don't
   // collide with their neighbours.
   let epsilon: GLfloat = 0.00001;
-  if !check_collisions || !physics.octree.intersect(&bounds.tightened(epsilon), None) {
-    let terrain = terrain::TerrainPiece {
-      vertices: vertices,
-      normal: normal,
-      typ: typ as GLuint,
-      id: id_allocator.allocate(),
-    };
+  if !physics.in_world(&bounds) {
+    return None;
+  }
+  if check_collisions && physics.octree.intersect(&bounds.tightened(epsilon), None) {
+    return None;
+  }
+
+  let solid = terrain_types.get(typ).map_or(true, |info| info.solid);
+  let terrain::TerrainType(typ) = typ;
+  let terrain = terrain::TerrainPiece {
+    vertices: vertices,
+    normal: normal,
+    typ: typ,
+    id: id_allocator.allocate(),
+    created_at: created_at,
+  };
+  if solid {
     physics.insert(terrain.id, &bounds);
-    terrains.insert(terrain.id, terrain);
-    terrain_loader.push(Load(terrain.id));
+  } else {
+    physics.insert_passable(terrain.id, &bounds);
   }
+  terrains.insert(terrain.id, terrain);
+  terrain_loader.push(Load(terrain.id));
+  Some(terrain.id)
+}
+
+/// Make a player whose box has its minimum corner at `min`, and whose view has
+/// the given aspect ratio.
+fn make_player(
+  physics: &mut Physics<EntityId>,
+  id_allocator: &mut IdAllocator<EntityId>,
+  min: Pnt3<GLfloat>,
+  settings: &Settings,
+  aspect: GLfloat,
+) -> Player {
+  let up = settings.up_axis.up();
+  let mut player = Player::new(id_allocator.allocate(), settings.up_axis);
+
+  // 1 wide and 2 tall
+  let max = min + Vec3::new(1.0, 1.0, 1.0) + up;
+  let bounds = AABB::new(min, max);
+  physics.insert(player.id, &bounds);
+  player.spawn = center(&bounds);
+
+  // initialize the projection matrix
+  player.camera.translate(center(&bounds).to_vec());
+  player.camera.fov = player_fov(settings, aspect);
+  player.rotate_lateral(PI / 2.0);
+
+  player
+}
+
+fn player_fov(settings: &Settings, aspect: GLfloat) -> Mat4<GLfloat> {
+  camera::perspective(settings.fov, aspect, settings.near, settings.render_distance)
 }
 
 fn make_mobs(
@@ -719,6 +1967,7 @@ fn make_mobs(
     &mut mob_buffers,
     id_allocator,
     Pnt3::new(0.0, 64.0, -1.0),
+    Pnt3::new(1.0, 66.0, 0.0),
     mob_behavior
   );
 
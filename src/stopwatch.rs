@@ -7,9 +7,11 @@ use std::rc::Rc;
 use std::collections::HashMap;
 
 /// A simple stopwatch taht can time events and print stats about them.
-#[deriving(Send)]
+#[deriving(Send, Clone)]
 pub struct Stopwatch {
   pub total_time: u64,
+  // time not spent inside other timers started while this one was running
+  pub self_time: u64,
   // number of time windows we've clocked
   pub number_of_windows: u64,
 }
@@ -20,18 +22,18 @@ impl Stopwatch {
   pub fn new() -> Stopwatch {
     Stopwatch {
       total_time: 0,
+      self_time: 0,
       number_of_windows: 0,
     }
   }
 
   #[inline]
-  /// Times a function, updating stats as necessary.
-  pub fn timed<T>(&mut self, event: | | -> T) -> T {
-    let then = time::precise_time_ns();
-    let ret = event();
-    self.total_time += time::precise_time_ns() - then;
+  /// Adds a time window that took `total_time` ns, `self_time` ns of which
+  /// weren't spent in nested timers.
+  pub fn record(&mut self, total_time: u64, self_time: u64) {
+    self.total_time += total_time;
+    self.self_time += self_time;
     self.number_of_windows += 1;
-    ret
   }
 
   /// Prints out timing statistics of this stopwatch.
@@ -54,12 +56,18 @@ impl Stopwatch {
 /// A set of stopwatches for multiple, named events.
 pub struct TimerSet {
   timers: RefCell<HashMap<String, Rc<RefCell<Stopwatch>>>>,
+  // for each running timer, innermost last, the time spent in timers nested
+  // directly inside it so far.
+  child_times: RefCell<Vec<u64>>,
 }
 
 impl TimerSet {
   /// Creates a new set of timers.
   pub fn new() -> TimerSet {
-    TimerSet { timers: RefCell::new(HashMap::new()) }
+    TimerSet {
+      timers: RefCell::new(HashMap::new()),
+      child_times: RefCell::new(Vec::new()),
+    }
   }
 
   /// Times the execution of a function, and logs it under a timer with
@@ -80,9 +88,49 @@ impl TimerSet {
     let timer : Rc<RefCell<Stopwatch>> =
       self.timers.borrow().find_equiv(&name).unwrap().clone();
 
-    match timer.try_borrow_mut() {
-      None => fail!("timer \"{}\" used recursively", name),
-      Some(mut timer) => timer.timed(f),
+    let mut timer =
+      match timer.try_borrow_mut() {
+        None => fail!("timer \"{}\" used recursively", name),
+        Some(timer) => timer,
+      };
+
+    self.child_times.borrow_mut().push(0);
+    let then = time::precise_time_ns();
+    let ret = f();
+    let elapsed = time::precise_time_ns() - then;
+
+    let child_time = self.child_times.borrow_mut().pop().unwrap();
+    match self.child_times.borrow_mut().last_mut() {
+      None => {},
+      Some(parent_child_time) => *parent_child_time += elapsed,
+    }
+
+    timer.record(elapsed, elapsed - child_time);
+    ret
+  }
+
+  /// Copies of all the timers that aren't currently running, with their
+  /// names.
+  pub fn snapshot(&self) -> Vec<(String, Stopwatch)> {
+    self.timers
+      .borrow()
+      .iter()
+      .filter_map(|(name, sw)| sw.try_borrow().map(|sw| (name.clone(), sw.clone())))
+      .collect()
+  }
+
+  /// A copy of the timer called `name`, if it exists and isn't running.
+  pub fn get(&self, name: &str) -> Option<Stopwatch> {
+    self.timers
+      .borrow()
+      .find_equiv(&name)
+      .and_then(|sw| sw.try_borrow().map(|sw| sw.clone()))
+  }
+
+  /// Zeroes the stats of all the timers that aren't currently running.
+  pub fn reset(&self) {
+    for (_, sw) in self.timers.borrow().iter() {
+      sw.try_borrow_mut().map(|mut sw| *sw = Stopwatch::new());
     }
   }
 
@@ -118,6 +166,42 @@ fn test_nested() {
   });
 }
 
+#[test]
+fn test_self_time() {
+  let ts = TimerSet::new();
+  ts.time("outer", || {
+    ts.time("inner", || {
+      ts.time("innermost", || {});
+    });
+  });
+
+  let snapshot = ts.snapshot();
+  let find = |name: &str| -> Stopwatch {
+    for &(ref n, ref sw) in snapshot.iter() {
+      if n.as_slice() == name {
+        return sw.clone();
+      }
+    }
+    fail!("no timer \"{}\"", name)
+  };
+  let (outer, inner, innermost) = (find("outer"), find("inner"), find("innermost"));
+  assert_eq!(outer.self_time + inner.total_time, outer.total_time);
+  assert_eq!(inner.self_time + innermost.total_time, inner.total_time);
+  assert_eq!(innermost.self_time, innermost.total_time);
+}
+
+#[test]
+fn test_reset() {
+  let ts = TimerSet::new();
+  ts.time("hello", || {});
+  ts.reset();
+  let snapshot = ts.snapshot();
+  assert_eq!(snapshot.len(), 1);
+  let (_, ref sw) = snapshot[0];
+  assert_eq!(sw.number_of_windows, 0);
+  assert_eq!(sw.total_time, 0);
+}
+
 #[macro_export]
 macro_rules! time(
   ($timers:expr, $name:expr, $f:expr) => (
@@ -0,0 +1,124 @@
+//! The sun: a bright disc with a soft halo, drawn in the sky behind
+//! everything else.
+
+use gl::types::*;
+use glw::color::Color4;
+use glw::gl_buffer::{GLArray, GLBuffer, DynamicDraw, Triangles};
+use glw::gl_context::GLContext;
+use glw::shader::Shader;
+use glw::vertex;
+use glw::vertex::ColoredVertex;
+use nalgebra::{Cross, Norm, Pnt3, Vec3};
+use std::cell::RefCell;
+use std::f32::consts::PI;
+use std::rc::Rc;
+
+// triangles around the disc
+const SEGMENTS: uint = 16;
+// one triangle per segment for the disc, and two for the halo around it.
+const VERTICES: uint = SEGMENTS * 3 * 3;
+// the disc's radius, as a fraction of its distance from the camera.
+static RADIUS: GLfloat = 0.05;
+// the halo's outer radius, as a multiple of the disc's.
+static HALO_SCALE: GLfloat = 3.0;
+
+static DISC_COLOR: Color4<GLfloat> = Color4 { r: 1.0, g: 1.0, b: 0.9, a: 1.0 };
+static HALO_COLOR: Color4<GLfloat> = Color4 { r: 1.0, g: 0.95, b: 0.7, a: 0.5 };
+
+pub struct Sun {
+  /// unit vector from the camera toward the sun
+  pub direction: Vec3<GLfloat>,
+  triangles: GLArray<ColoredVertex>,
+}
+
+impl Sun {
+  /// Make a sun in `direction`. Use a color shader.
+  pub fn new(gl: &GLContext, shader: Rc<RefCell<Shader>>, direction: Vec3<GLfloat>) -> Sun {
+    let mut triangles =
+      GLArray::new(
+        gl,
+        shader,
+        [ vertex::AttribData { name: "position", size: 3, unit: vertex::Float },
+          vertex::AttribData { name: "in_color", size: 4, unit: vertex::Float },
+        ],
+        Triangles,
+        GLBuffer::new(VERTICES, DynamicDraw),
+      );
+    let direction = direction.normalize();
+    triangles.push(vertices(&Pnt3::new(0.0, 0.0, 0.0), &direction, 1.0).as_slice());
+    Sun {
+      direction: direction,
+      triangles: triangles,
+    }
+  }
+
+  /// Place the sun `distance` away from `eye`, facing it. Since the sun moves
+  /// with the camera, it looks infinitely far away.
+  pub fn move_to(&mut self, eye: &Pnt3<GLfloat>, distance: GLfloat) {
+    let vs = vertices(eye, &self.direction, distance);
+    self.triangles.buffer.update(0, vs.as_slice());
+  }
+
+  pub fn draw(&self, gl: &GLContext) {
+    self.triangles.draw(gl);
+  }
+}
+
+/// Triangles for a sun disc and halo `distance` from `eye` along the unit
+/// vector `direction`, wound to face `eye`.
+pub fn vertices(eye: &Pnt3<GLfloat>, direction: &Vec3<GLfloat>, distance: GLfloat) -> Vec<ColoredVertex> {
+  // two unit vectors perpendicular to `direction` and each other, with
+  // u x v = -direction, so counterclockwise faces the eye.
+  let not_parallel =
+    if direction.y.abs() < 0.9 {
+      Vec3::new(0.0, 1.0, 0.0)
+    } else {
+      Vec3::new(1.0, 0.0, 0.0)
+    };
+  let u = direction.cross(&not_parallel).normalize();
+  let v = u.cross(direction);
+
+  let center = *eye + *direction * distance;
+  let radius = distance * RADIUS;
+  let at = |i: uint, r: GLfloat| {
+    let angle = 2.0 * PI * (i % SEGMENTS) as GLfloat / SEGMENTS as GLfloat;
+    center + (u * angle.cos() + v * angle.sin()) * r
+  };
+  let vertex = |position: Pnt3<GLfloat>, color: Color4<GLfloat>| {
+    ColoredVertex { position: position, color: color }
+  };
+
+  let mut halo_edge = HALO_COLOR;
+  halo_edge.a = 0.0;
+
+  let mut vs = Vec::with_capacity(VERTICES);
+  for i in range(0, SEGMENTS) {
+    vs.push(vertex(center, DISC_COLOR));
+    vs.push(vertex(at(i, radius), DISC_COLOR));
+    vs.push(vertex(at(i + 1, radius), DISC_COLOR));
+  }
+  for i in range(0, SEGMENTS) {
+    let inner1 = vertex(at(i, radius), HALO_COLOR);
+    let inner2 = vertex(at(i + 1, radius), HALO_COLOR);
+    let outer1 = vertex(at(i, radius * HALO_SCALE), halo_edge);
+    let outer2 = vertex(at(i + 1, radius * HALO_SCALE), halo_edge);
+    vs.push_all([inner1, outer1, outer2, inner1, outer2, inner2]);
+  }
+  vs
+}
+
+#[test]
+fn sun_faces_the_eye() {
+  let eye = Pnt3::new(1.0, 2.0, 3.0);
+  let direction = Vec3::new(0.0, 0.6, -0.8);
+  let vs = vertices(&eye, &direction, 100.0);
+  assert_eq!(vs.len(), VERTICES);
+  assert!((vs[0].position - (eye + direction * 100.0)).norm() < 0.001);
+
+  // every triangle is counterclockwise seen from the eye, i.e. its normal
+  // points back toward it.
+  for t in vs.as_slice().chunks(3) {
+    let normal = (t[1].position - t[0].position).cross(&(t[2].position - t[0].position));
+    assert!(::nalgebra::Dot::dot(&normal, &direction) < 0.0);
+  }
+}
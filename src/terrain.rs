@@ -1,22 +1,96 @@
+use buffer_index::BufferIndex;
 use common::*;
 use gl;
 use gl::types::*;
+use glw::color::Color4;
 use glw::gl_context::GLContext;
 use glw::shader::Shader;
 use glw::texture::BufferTexture;
 use glw::texture::TextureUnit;
 use id_allocator::IdAllocator;
-use nalgebra::{Pnt3, Vec3};
+use nalgebra::{Norm, Pnt3, Vec3};
+use ncollide::bounding_volume::aabb::AABB;
+use physics::Physics;
+use rng::Rng;
 use state::EntityId;
 use std::cell::RefCell;
 use std::collections::HashMap;
+use std::hash;
 use std::rc::Rc;
 
+/// A terrain type's id, i.e. its position in the `TerrainTypes` registry.
 #[deriving(Show, Copy, Clone, PartialEq, Eq, Hash)]
-pub enum TerrainType {
-  Grass,
-  Dirt,
-  Stone,
+pub struct TerrainType(pub GLuint);
+
+pub static GRASS: TerrainType = TerrainType(0);
+pub static DIRT: TerrainType = TerrainType(1);
+pub static STONE: TerrainType = TerrainType(2);
+
+/// The most terrain types the shaders have room for.
+pub static MAX_TERRAIN_TYPES: uint = 16;
+
+static COLOR_UNIFORMS: [&'static str, ..16] = [
+  "terrain_colors[0]", "terrain_colors[1]", "terrain_colors[2]", "terrain_colors[3]",
+  "terrain_colors[4]", "terrain_colors[5]", "terrain_colors[6]", "terrain_colors[7]",
+  "terrain_colors[8]", "terrain_colors[9]", "terrain_colors[10]", "terrain_colors[11]",
+  "terrain_colors[12]", "terrain_colors[13]", "terrain_colors[14]", "terrain_colors[15]",
+];
+
+static EMISSIVE_UNIFORMS: [&'static str, ..16] = [
+  "terrain_emissive[0]", "terrain_emissive[1]", "terrain_emissive[2]", "terrain_emissive[3]",
+  "terrain_emissive[4]", "terrain_emissive[5]", "terrain_emissive[6]", "terrain_emissive[7]",
+  "terrain_emissive[8]", "terrain_emissive[9]", "terrain_emissive[10]", "terrain_emissive[11]",
+  "terrain_emissive[12]", "terrain_emissive[13]", "terrain_emissive[14]", "terrain_emissive[15]",
+];
+
+impl TerrainType {
+  /// The name of the shader uniform holding this type's color.
+  pub fn color_uniform(&self) -> &'static str {
+    let TerrainType(id) = *self;
+    assert!((id as uint) < MAX_TERRAIN_TYPES);
+    COLOR_UNIFORMS[id as uint]
+  }
+
+  /// The name of the shader uniform saying whether this type is emissive.
+  pub fn emissive_uniform(&self) -> &'static str {
+    let TerrainType(id) = *self;
+    assert!((id as uint) < MAX_TERRAIN_TYPES);
+    EMISSIVE_UNIFORMS[id as uint]
+  }
+}
+
+/// A deterministic value in [-1, 1) for each id in a world seeded with `seed`,
+/// used to vary the shade of otherwise identically-colored terrain.
+pub fn color_noise(seed: u32, id: EntityId) -> GLfloat {
+  Rng::new(seed ^ hash::hash(&id) as u32).range_f32(-1.0, 1.0)
+}
+
+// distance between ambient occlusion samples.
+static AO_SAMPLE_DISTANCE: GLfloat = 0.25;
+// how much a fully-surrounded vertex is darkened.
+static AO_STRENGTH: GLfloat = 0.5;
+
+// how close two vertices have to be to count as the same one.
+static SHARED_VERTEX_EPSILON: GLfloat = 0.001;
+
+/// How much of each of `type_count` terrain types' colors a vertex gets when
+/// colors are smoothed: the fraction of the pieces sharing it (including its
+/// own) that are of each type. Types of `type_count` or more don't count.
+pub fn vertex_type_weights(types: &[GLuint], type_count: uint) -> Vec<GLfloat> {
+  let mut counts = Vec::from_elem(type_count, 0u);
+  for &typ in types.iter() {
+    if (typ as uint) < type_count {
+      *counts.get_mut(typ as uint) += 1;
+    }
+  }
+  let total = counts.iter().fold(0, |sum, &c| sum + c);
+  let mut weights = Vec::from_elem(type_count, 0.0);
+  if total > 0 {
+    for (w, &c) in weights.iter_mut().zip(counts.iter()) {
+      *w = c as GLfloat / total as GLfloat;
+    }
+  }
+  weights
 }
 
 pub struct TerrainPiece {
@@ -24,38 +98,124 @@ pub struct TerrainPiece {
   pub normal: Vec3<GLfloat>,
   pub typ: GLuint,
   pub id: EntityId,
+  /// the update this piece was placed on. Terrain made with the world is
+  /// placed on update 0.
+  pub created_at: u64,
+}
+
+impl TerrainPiece {
+  /// Ambient occlusion for each vertex, as a brightness multiplier in
+  /// [1 - AO_STRENGTH, 1].
+  ///
+  /// Each vertex looks at the 8 corners of a small cube just off the surface
+  /// (along the normal), and is darkened by the fraction of them that are
+  /// inside other terrain. Open flat ground isn't darkened at all; a vertex
+  /// in a concave corner is.
+  pub fn ambient_occlusion(&self, physics: &Physics<EntityId>) -> [GLfloat, ..3] {
+    let d = AO_SAMPLE_DISTANCE;
+    let sample_size = Vec3::new(d, d, d) / 4.0;
+    let occlusion = |vertex: &Pnt3<GLfloat>| {
+      let center = *vertex + self.normal * 2.0 * d;
+      let mut occluded = 0u;
+      for &dx in [-d, d].iter() {
+        for &dy in [-d, d].iter() {
+          for &dz in [-d, d].iter() {
+            let sample = center + Vec3::new(dx, dy, dz);
+            let bounds = AABB::new(sample - sample_size, sample + sample_size);
+            if physics.octree.intersect(&bounds, Some(self.id)) {
+              occluded += 1;
+            }
+          }
+        }
+      }
+      1.0 - AO_STRENGTH * occluded as GLfloat / 8.0
+    };
+
+    [
+      occlusion(&self.vertices[0]),
+      occlusion(&self.vertices[1]),
+      occlusion(&self.vertices[2]),
+    ]
+  }
+
+  /// `vertex_type_weights` for each vertex, from the terrain in `terrains`
+  /// that shares it; `type_count` weights per vertex, one vertex after
+  /// another.
+  pub fn type_weights(
+    &self,
+    physics: &Physics<EntityId>,
+    terrains: &HashMap<EntityId, TerrainPiece>,
+    type_count: uint,
+  ) -> Vec<GLfloat> {
+    let e = SHARED_VERTEX_EPSILON;
+    let mut weights = Vec::with_capacity(3 * type_count);
+    for vertex in self.vertices.iter() {
+      let near = AABB::new(*vertex - Vec3::new(e, e, e), *vertex + Vec3::new(e, e, e));
+      let mut types = vec!(self.typ);
+      for id in physics.octree.intersect_details(&near, Some(self.id)).iter() {
+        match terrains.find(id) {
+          None => {},
+          Some(other) => {
+            let shares_vertex = other.vertices.iter().any(|v| (*v - *vertex).norm() < e);
+            if shares_vertex {
+              types.push(other.typ);
+            }
+          },
+        }
+      }
+      weights.push_all(vertex_type_weights(types.as_slice(), type_count).as_slice());
+    }
+    weights
+  }
 }
 
 pub struct TerrainBuffers {
-  id_to_index: HashMap<EntityId, uint>,
-  index_to_id: Vec<EntityId>,
+  index: BufferIndex<EntityId>,
 
   empty_array: GLuint,
-  length: uint,
   // Each position is buffered as 3 separate floats due to image format restrictions.
   vertex_positions: BufferTexture<GLfloat>,
   // Each normal component is buffered separately floats due to image format restrictions.
   normals: BufferTexture<GLfloat>,
   types: BufferTexture<GLuint>,
+  // One `color_noise` value per face; the shader scales it by `color_jitter`.
+  color_noise: BufferTexture<GLfloat>,
+  // One brightness multiplier per vertex.
+  ambient_occlusion: BufferTexture<GLfloat>,
+  // `type_count` per vertex: how much of each type's color it gets when
+  // colors are smoothed.
+  type_weights: BufferTexture<GLfloat>,
+  // how many terrain types there are.
+  type_count: uint,
+  // One per face: how much it's flashing white, from 0 to 1.
+  flash: BufferTexture<GLfloat>,
+  seed: u32,
 }
 
 impl TerrainBuffers {
+  /// Make buffers for terrain of `type_count` different types.
   pub fn new(
     gl: &GLContext,
+    seed: u32,
+    type_count: uint,
   ) -> TerrainBuffers {
     TerrainBuffers {
-      id_to_index: HashMap::new(),
-      index_to_id: Vec::new(),
+      index: BufferIndex::new(),
       empty_array: unsafe {
         let mut empty_array = 0;
         gl::GenVertexArrays(1, &mut empty_array);
         empty_array
       },
-      length: 0,
       // multiply by 3 because there are 3 R32F components
       vertex_positions: BufferTexture::new(gl, gl::R32F, 3 * MAX_WORLD_SIZE * VERTICES_PER_TRIANGLE),
       normals: BufferTexture::new(gl, gl::R32F, 3 * MAX_WORLD_SIZE),
       types: BufferTexture::new(gl, gl::R32UI, MAX_WORLD_SIZE),
+      color_noise: BufferTexture::new(gl, gl::R32F, MAX_WORLD_SIZE),
+      ambient_occlusion: BufferTexture::new(gl, gl::R32F, MAX_WORLD_SIZE * VERTICES_PER_TRIANGLE),
+      type_weights: BufferTexture::new(gl, gl::R32F, type_count * MAX_WORLD_SIZE * VERTICES_PER_TRIANGLE),
+      type_count: type_count,
+      flash: BufferTexture::new(gl, gl::R32F, MAX_WORLD_SIZE),
+      seed: seed,
     }
   }
 
@@ -79,17 +239,38 @@ impl TerrainBuffers {
       bind("normals", self.normals.texture.gl_id);
     }
     bind("terrain_types", self.types.texture.gl_id);
+    bind("color_noise", self.color_noise.texture.gl_id);
+    bind("ambient_occlusion", self.ambient_occlusion.texture.gl_id);
+    bind("type_weights", self.type_weights.texture.gl_id);
+    bind("flash", self.flash.texture.gl_id);
+  }
+
+  /// Like `bind`, but only the vertex positions, for shaders that just need
+  /// the terrain's shape.
+  pub fn bind_positions(
+    &self,
+    gl: &mut GLContext,
+    texture_unit_alloc: &mut IdAllocator<TextureUnit>,
+    shader: Rc<RefCell<Shader>>,
+  ) {
+    let unit = texture_unit_alloc.allocate();
+    gl::ActiveTexture(unit.gl_id());
+    gl::BindTexture(gl::TEXTURE_BUFFER, self.vertex_positions.texture.gl_id);
+    shader.borrow_mut().with_uniform_location(gl, "positions", |loc| {
+      gl::Uniform1i(loc, unit.glsl_id as GLint);
+    });
   }
 
   pub fn push(
     &mut self,
     id: EntityId,
     terrain: &TerrainPiece,
+    ambient_occlusion: [GLfloat, ..3],
+    type_weights: &[GLfloat],
   ) {
-    self.id_to_index.insert(id, self.index_to_id.len());
-    self.index_to_id.push(id);
+    assert_eq!(type_weights.len(), VERTICES_PER_TRIANGLE * self.type_count);
+    self.index.push(id);
 
-    self.length += 3;
     self.vertex_positions.buffer.push([
       terrain.vertices[0].x,
       terrain.vertices[0].y,
@@ -105,30 +286,61 @@ impl TerrainBuffers {
       self.normals.buffer.push([terrain.normal.x, terrain.normal.y, terrain.normal.z]);
     }
     self.types.buffer.push(&[terrain.typ as GLuint]);
+    self.color_noise.buffer.push(&[color_noise(self.seed, id)]);
+    self.ambient_occlusion.buffer.push(&ambient_occlusion);
+    self.type_weights.buffer.push(type_weights);
+    self.flash.buffer.push(&[0.0]);
+  }
+
+  /// Whether `id` has been pushed into the buffers.
+  pub fn contains(&self, id: EntityId) -> bool {
+    self.index.contains(id)
+  }
+
+  /// Set how much `id` is flashing white, from 0 (not at all) to 1. `id` must
+  /// be present in the buffers.
+  pub fn set_flash(&mut self, id: EntityId, amount: GLfloat) {
+    let idx = self.index.find(id).unwrap();
+    self.flash.buffer.update(idx, &[amount]);
   }
 
   // Note: `id` must be present in the buffers.
   pub fn swap_remove(&mut self, id: EntityId) {
-    let idx = *self.id_to_index.find(&id).unwrap();
-    let swapped_id = self.index_to_id[self.index_to_id.len() - 1];
-    self.index_to_id.swap_remove(idx).unwrap();
-    self.id_to_index.remove(&id);
-
-    if id != swapped_id {
-      self.id_to_index.insert(swapped_id, idx);
-    }
+    let idx = self.index.swap_remove(id).unwrap();
 
-    self.length -= 3;
     self.vertex_positions.buffer.swap_remove(idx * 3 * VERTICES_PER_TRIANGLE, 3 * VERTICES_PER_TRIANGLE);
     if USE_LIGHTING {
       self.normals.buffer.swap_remove(3 * idx, 3);
     }
     self.types.buffer.swap_remove(idx, 1);
+    self.color_noise.buffer.swap_remove(idx, 1);
+    self.ambient_occlusion.buffer.swap_remove(idx * VERTICES_PER_TRIANGLE, VERTICES_PER_TRIANGLE);
+    let weights_per_face = self.type_count * VERTICES_PER_TRIANGLE;
+    self.type_weights.buffer.swap_remove(idx * weights_per_face, weights_per_face);
+    self.flash.buffer.swap_remove(idx, 1);
   }
 
   pub fn draw(&self, _gl: &GLContext) {
     gl::BindVertexArray(self.empty_array);
-    gl::DrawArrays(gl::TRIANGLES, 0, self.length as GLint);
+    gl::DrawArrays(gl::TRIANGLES, 0, (VERTICES_PER_TRIANGLE * self.index.len()) as GLint);
   }
 }
 
+#[test]
+fn type_weights_are_fractions_of_sharers() {
+  let (TerrainType(grass), TerrainType(dirt), TerrainType(stone)) = (GRASS, DIRT, STONE);
+  assert_eq!(vertex_type_weights([grass], 3), vec!(1.0, 0.0, 0.0));
+  assert_eq!(
+    vertex_type_weights([grass, stone, grass, stone], 3),
+    vec!(0.5, 0.0, 0.5),
+  );
+  // unknown types are ignored.
+  assert_eq!(vertex_type_weights([dirt, 7], 3), vec!(0.0, 1.0, 0.0));
+  assert_eq!(vertex_type_weights([], 3), vec!(0.0, 0.0, 0.0));
+
+  // types past the first three are weighed too.
+  let weights = vertex_type_weights([grass, 5, 5, 15], MAX_TERRAIN_TYPES);
+  assert_eq!(weights.len(), MAX_TERRAIN_TYPES);
+  assert_eq!((weights[0], weights[5], weights[15]), (0.25, 0.5, 0.25));
+  assert_eq!(weights.iter().fold(0.0, |sum, &w| sum + w), 1.0);
+}
@@ -0,0 +1,270 @@
+//! The registry of terrain types, loaded from a data file so new types can be
+//! added without recompiling.
+//!
+//! Each non-blank line of the file that doesn't start with `#` defines one
+//! type, in order, as its name, color, whether it's solid, and its health,
+//! optionally followed by `emissive` for types that glow:
+//!
+//! ```text
+//! # name   r   g   b    solid  health
+//! grass    0   0.5 0    true   1
+//! lava     1   0.3 0    false  0       emissive
+//! ```
+//!
+//! A type's id is its position in the file. Without a file, the built-in
+//! grass, dirt and stone are used.
+
+use gl::types::*;
+use glw::color::Color4;
+use std::io::fs::File;
+use std::path::Path;
+use terrain::{TerrainType, MAX_TERRAIN_TYPES};
+
+/// Where terrain types are loaded from at startup.
+pub static TERRAIN_TYPES_PATH: &'static str = "terrain_types.cfg";
+
+/// How many updates of digging each point of health takes.
+pub static DIG_TICKS_PER_HEALTH: uint = 5;
+
+#[deriving(Show, Clone, PartialEq)]
+pub struct TypeInfo {
+  /// lowercase, e.g. "grass"; console commands refer to types by this.
+  pub name: String,
+  /// the color it's drawn with until it's recolored
+  pub color: Color4<GLfloat>,
+  /// whether things collide with it
+  pub solid: bool,
+  /// how long it takes to dig out, in units of `DIG_TICKS_PER_HEALTH`
+  pub health: uint,
+  /// whether it's drawn at full brightness, whatever the lighting
+  pub emissive: bool,
+}
+
+impl TypeInfo {
+  /// How far through digging out a piece of this type `ticks` updates of
+  /// digging gets, from 0 to 1. It's removed at 1.
+  pub fn dig_progress(&self, ticks: uint) -> GLfloat {
+    let total = self.health * DIG_TICKS_PER_HEALTH;
+    if ticks >= total {
+      1.0
+    } else {
+      ticks as GLfloat / total as GLfloat
+    }
+  }
+}
+
+#[deriving(Show, Clone, PartialEq)]
+pub struct TerrainTypes {
+  types: Vec<TypeInfo>,
+}
+
+fn parse_line(line: &str) -> Result<TypeInfo, String> {
+  let words: Vec<&str> = line.words().collect();
+  if words.len() != 6 && words.len() != 7 {
+    return Err(format!("expected name r g b solid health [emissive], got \"{}\"", line));
+  }
+
+  let mut rgb = Vec::new();
+  for word in words.slice(1, 4).iter() {
+    match from_str::<GLfloat>(*word) {
+      None => return Err(format!("\"{}\" isn't a number", word)),
+      Some(x) => rgb.push(x),
+    }
+  }
+  let solid =
+    match from_str::<bool>(words[4]) {
+      None => return Err(format!("\"{}\" isn't true or false", words[4])),
+      Some(solid) => solid,
+    };
+  let health =
+    match from_str::<uint>(words[5]) {
+      None => return Err(format!("\"{}\" isn't a health", words[5])),
+      Some(health) => health,
+    };
+  let emissive =
+    match words.as_slice().get(6) {
+      None => false,
+      Some(word) if *word == "emissive" => true,
+      Some(word) => return Err(format!("expected \"emissive\", got \"{}\"", word)),
+    };
+
+  Ok(TypeInfo {
+    name: words[0].to_string(),
+    color: Color4::of_rgba(rgb[0], rgb[1], rgb[2], 1.0),
+    solid: solid,
+    health: health,
+    emissive: emissive,
+  })
+}
+
+impl TerrainTypes {
+  /// The built-in types: grass, dirt and stone, in that order.
+  pub fn defaults() -> TerrainTypes {
+    let info = |name: &str, r: GLfloat, g: GLfloat, b: GLfloat, health: uint| {
+      TypeInfo {
+        name: name.to_string(),
+        color: Color4::of_rgba(r, g, b, 1.0),
+        solid: true,
+        health: health,
+        emissive: false,
+      }
+    };
+    TerrainTypes {
+      types: vec!(
+        info("grass", 0.0, 0.5, 0.0, 1),
+        info("dirt", 0.5, 0.4, 0.2, 1),
+        info("stone", 0.5, 0.5, 0.5, 3),
+      ),
+    }
+  }
+
+  /// Parse a terrain types file. The world is built out of grass, dirt and
+  /// stone, so the file has to define at least those three.
+  pub fn parse(text: &str) -> Result<TerrainTypes, String> {
+    let mut types = TerrainTypes { types: Vec::new() };
+    for (i, line) in text.lines().enumerate() {
+      let line = line.trim();
+      if line.is_empty() || line.starts_with("#") {
+        continue;
+      }
+      let info =
+        match parse_line(line) {
+          Err(e) => return Err(format!("line {}: {}", i + 1, e)),
+          Ok(info) => info,
+        };
+      if types.by_name(info.name.as_slice()).is_some() {
+        return Err(format!("line {}: \"{}\" is defined twice", i + 1, info.name));
+      }
+      if types.types.len() == MAX_TERRAIN_TYPES {
+        return Err(format!("more than {} types", MAX_TERRAIN_TYPES));
+      }
+      types.types.push(info);
+    }
+
+    for name in ["grass", "dirt", "stone"].iter() {
+      if types.by_name(*name).is_none() {
+        return Err(format!("\"{}\" isn't defined", name));
+      }
+    }
+    Ok(types)
+  }
+
+  /// Load terrain types from a file. If there's no file, or it can't be read
+  /// or parsed, the defaults are used.
+  pub fn load(path: &Path) -> TerrainTypes {
+    if !path.exists() {
+      return TerrainTypes::defaults();
+    }
+
+    let parsed =
+      File::open(path)
+        .and_then(|mut f| f.read_to_string())
+        .map_err(|e| e.to_string())
+        .and_then(|text| TerrainTypes::parse(text.as_slice()));
+    match parsed {
+      Ok(types) => types,
+      Err(e) => {
+        warn!("Using the default terrain types; couldn't load \"{}\": {}", path.display(), e);
+        TerrainTypes::defaults()
+      },
+    }
+  }
+
+  pub fn len(&self) -> uint {
+    self.types.len()
+  }
+
+  pub fn get(&self, typ: TerrainType) -> Option<&TypeInfo> {
+    let TerrainType(id) = typ;
+    self.types.as_slice().get(id as uint)
+  }
+
+  /// The type called `name`, if there is one.
+  pub fn by_name(&self, name: &str) -> Option<TerrainType> {
+    self.types.iter()
+      .position(|info| info.name.as_slice() == name)
+      .map(|i| TerrainType(i as GLuint))
+  }
+
+  /// `typ`'s name, or a placeholder if it isn't a known type.
+  pub fn name(&self, typ: TerrainType) -> String {
+    match self.get(typ) {
+      None => format!("{}", typ),
+      Some(info) => info.name.clone(),
+    }
+  }
+
+  /// Every type, with its info.
+  pub fn iter<'a>(&'a self) -> Vec<(TerrainType, &'a TypeInfo)> {
+    self.types.iter()
+      .enumerate()
+      .map(|(i, info)| (TerrainType(i as GLuint), info))
+      .collect()
+  }
+}
+
+#[test]
+fn defaults_are_the_builtin_types() {
+  let types = TerrainTypes::defaults();
+  assert_eq!(types.by_name("grass"), Some(::terrain::GRASS));
+  assert_eq!(types.by_name("dirt"), Some(::terrain::DIRT));
+  assert_eq!(types.by_name("stone"), Some(::terrain::STONE));
+  assert_eq!(types.by_name("lava"), None);
+  assert_eq!(types.name(::terrain::STONE), String::from_str("stone"));
+}
+
+#[test]
+fn parse_types() {
+  let text = "
+    # the usual, plus lava
+    grass 0 0.5 0 true 1
+    dirt 0.5 0.4 0.2 true 1
+    stone 0.5 0.5 0.5 true 3
+    lava 1 0.3 0 false 0 emissive
+  ";
+  let types = TerrainTypes::parse(text).unwrap();
+  assert_eq!(types.len(), 4);
+  let lava = types.get(types.by_name("lava").unwrap()).unwrap();
+  assert_eq!(lava.color, Color4::of_rgba(1.0, 0.3, 0.0, 1.0));
+  assert!(!lava.solid);
+  assert_eq!(lava.health, 0);
+  assert!(lava.emissive);
+  assert!(!types.get(::terrain::STONE).unwrap().emissive);
+}
+
+#[test]
+fn bad_types_are_errors() {
+  let builtins = "grass 0 0.5 0 true 1\ndirt 0.5 0.4 0.2 true 1\nstone 0.5 0.5 0.5 true 3\n";
+  assert!(TerrainTypes::parse(builtins).is_ok());
+  // missing a built-in type
+  assert!(TerrainTypes::parse("grass 0 0.5 0 true 1\n").is_err());
+  // defined twice
+  assert!(TerrainTypes::parse(format!("{}grass 0 1 0 true 1\n", builtins).as_slice()).is_err());
+  // malformed
+  assert!(TerrainTypes::parse(format!("{}lava 1 0.3 0 no 0\n", builtins).as_slice()).is_err());
+  assert!(TerrainTypes::parse(format!("{}lava 1 0.3\n", builtins).as_slice()).is_err());
+  assert!(TerrainTypes::parse(format!("{}lava 1 0.3 0 false 0 glowing\n", builtins).as_slice()).is_err());
+
+  let mut many = String::from_str(builtins);
+  for i in range(0, MAX_TERRAIN_TYPES) {
+    many.push_str(format!("extra{} 0 0 0 true 1\n", i).as_slice());
+  }
+  assert!(TerrainTypes::parse(many.as_slice()).is_err());
+}
+
+#[test]
+fn digging_takes_longer_with_more_health() {
+  let types = TerrainTypes::defaults();
+  let grass = types.get(::terrain::GRASS).unwrap();
+  let stone = types.get(::terrain::STONE).unwrap();
+  assert_eq!(grass.dig_progress(0), 0.0);
+  assert!(stone.dig_progress(1) < grass.dig_progress(1));
+  assert_eq!(grass.dig_progress(DIG_TICKS_PER_HEALTH), 1.0);
+  assert!(stone.dig_progress(DIG_TICKS_PER_HEALTH) < 1.0);
+  assert_eq!(stone.dig_progress(100 * DIG_TICKS_PER_HEALTH), 1.0);
+
+  // no health means it's dug out straight away.
+  let mut nothing = grass.clone();
+  nothing.health = 0;
+  assert_eq!(nothing.dig_progress(0), 1.0);
+}
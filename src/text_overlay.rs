@@ -0,0 +1,184 @@
+//! Lines of text drawn on the HUD. A line's texture is only re-rendered when
+//! its text actually changes, so text can be set every frame. Lines can have
+//! a panel drawn behind them, so they stay readable over busy scenery.
+
+use common::*;
+use gl::types::*;
+use glw::color::Color4;
+use glw::gl_buffer::{GLArray, GLBuffer, DynamicDraw, Triangles};
+use glw::gl_context::GLContext;
+use glw::shader::Shader;
+use glw::texture::Texture;
+use glw::vertex;
+use glw::vertex::{ColoredVertex, TextureVertex};
+use nalgebra::{Pnt2, Vec2};
+use std::cell::RefCell;
+use std::rc::Rc;
+use ttf;
+
+/// How far a line's background panel extends past its text on each side, in
+/// HUD coordinates.
+pub static BACKGROUND_PADDING: GLfloat = 4.0 * HUD_PIXEL;
+
+/// A dark, see-through panel that keeps white text readable.
+pub static TEXT_PANEL_COLOR: Color4<GLfloat> = Color4 { r: 0.0, g: 0.0, b: 0.0, a: 0.5 };
+
+#[deriving(Copy, Clone, PartialEq, Eq, Show)]
+pub struct TextLineId(uint);
+
+struct TextLine {
+  // top left corner, in HUD coordinates
+  position: Pnt2<GLfloat>,
+  color: Color4<u8>,
+  // the color of the panel behind the text, if there is one
+  background: Option<Color4<GLfloat>>,
+  text: String,
+  texture: Option<Texture>,
+}
+
+pub struct TextOverlay {
+  lines: Vec<TextLine>,
+  // one square per line
+  triangles: GLArray<TextureVertex>,
+  // one square per line, empty unless the line has text and a background
+  backgrounds: GLArray<ColoredVertex>,
+}
+
+/// The panel behind `w` by `h` of text with its top left corner at
+/// `position`, padded by `BACKGROUND_PADDING`.
+pub fn background_square(
+  position: Pnt2<GLfloat>,
+  w: GLfloat,
+  h: GLfloat,
+  color: Color4<GLfloat>,
+) -> [ColoredVertex, ..6] {
+  ColoredVertex::square(
+    Pnt2::new(position.x - BACKGROUND_PADDING, position.y - h - BACKGROUND_PADDING),
+    Pnt2::new(position.x + w + BACKGROUND_PADDING, position.y + BACKGROUND_PADDING),
+    color,
+  )
+}
+
+impl TextOverlay {
+  /// Create an overlay with room for `capacity` lines. `shader` draws the
+  /// text, and `background_shader` the panels behind it.
+  pub fn new(
+    gl: &GLContext,
+    shader: Rc<RefCell<Shader>>,
+    background_shader: Rc<RefCell<Shader>>,
+    capacity: uint,
+  ) -> TextOverlay {
+    TextOverlay {
+      lines: Vec::new(),
+      triangles: GLArray::new(
+        gl,
+        shader,
+        [ vertex::AttribData { name: "position", size: 3, unit: vertex::Float },
+          vertex::AttribData { name: "texture_position", size: 2, unit: vertex::Float },
+        ],
+        Triangles,
+        GLBuffer::new(capacity * 6, DynamicDraw),
+      ),
+      backgrounds: GLArray::new(
+        gl,
+        background_shader,
+        [ vertex::AttribData { name: "position", size: 3, unit: vertex::Float },
+          vertex::AttribData { name: "in_color", size: 4, unit: vertex::Float },
+        ],
+        Triangles,
+        GLBuffer::new(capacity * 6, DynamicDraw),
+      ),
+    }
+  }
+
+  /// Add an empty line with its top left corner at `position`. With a
+  /// `background`, a panel of that color is drawn behind whatever text it's
+  /// given.
+  pub fn add_line(
+    &mut self,
+    position: Pnt2<GLfloat>,
+    color: Color4<u8>,
+    background: Option<Color4<GLfloat>>,
+  ) -> TextLineId {
+    let id = TextLineId(self.lines.len());
+    self.lines.push(TextLine {
+      position: position,
+      color: color,
+      background: background,
+      text: String::new(),
+      texture: None,
+    });
+    let origin = Vec2::new(position.x, position.y);
+    self.triangles.push(TextureVertex::square(origin, origin));
+    self.backgrounds.push(ColoredVertex::square(position, position, Color4::of_rgba(0.0, 0.0, 0.0, 0.0)));
+    id
+  }
+
+  /// Change the text of a line. Nothing is rendered if the text is unchanged.
+  pub fn set_text(&mut self, font: &ttf::Font, id: TextLineId, text: &str) {
+    let TextLineId(idx) = id;
+    let line = self.lines.get_mut(idx);
+    if line.text.as_slice() == text {
+      return;
+    }
+
+    line.text = String::from_str(text);
+    if text.is_empty() {
+      line.texture = None;
+      if line.background.is_some() {
+        self.backgrounds.buffer.update(
+          idx * 6,
+          ColoredVertex::square(line.position, line.position, Color4::of_rgba(0.0, 0.0, 0.0, 0.0)),
+        );
+      }
+      return;
+    }
+
+    let (w, h) = font.size_of(text);
+    let (w, h) = (w as GLfloat * HUD_PIXEL, h as GLfloat * HUD_PIXEL);
+    line.texture = Some(font.render(text, line.color));
+    match line.background {
+      None => {},
+      Some(color) =>
+        self.backgrounds.buffer.update(idx * 6, background_square(line.position, w, h, color)),
+    }
+    self.triangles.buffer.update(
+      idx * 6,
+      TextureVertex::square(
+        Vec2::new(line.position.x, line.position.y - h),
+        Vec2::new(line.position.x + w, line.position.y),
+      ),
+    );
+  }
+
+  /// Draw the panels behind the lines that have them. Use a HUD color shader,
+  /// and call this before `draw`.
+  pub fn draw_backgrounds(&self, gl: &GLContext) {
+    self.backgrounds.draw(gl);
+  }
+
+  /// Draw every non-empty line. Use a HUD texture shader.
+  pub fn draw(&self, gl: &GLContext) {
+    for (i, line) in self.lines.iter().enumerate() {
+      match line.texture {
+        None => {},
+        Some(ref texture) => {
+          texture.bind_2d(gl);
+          self.triangles.draw_slice(gl, i * 6, 6);
+        },
+      }
+    }
+  }
+}
+
+#[test]
+fn backgrounds_pad_the_text() {
+  use nalgebra::Pnt3;
+
+  let color = Color4::of_rgba(0.0, 0.0, 0.0, 0.5);
+  let square = background_square(Pnt2::new(-1.0, 0.5), 0.5, 0.25, color);
+  // the first vertex of a square is its bottom left, the second its top right.
+  assert_eq!(square[0].position, Pnt3::new(-1.0 - BACKGROUND_PADDING, 0.25 - BACKGROUND_PADDING, 0.0));
+  assert_eq!(square[1].position, Pnt3::new(-0.5 + BACKGROUND_PADDING, 0.5 + BACKGROUND_PADDING, 0.0));
+  assert!(square.iter().all(|v| v.color == color));
+}
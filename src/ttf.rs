@@ -91,6 +91,15 @@ impl Font {
     Font { p: p }
   }
 
+  /// The size, in pixels, of `txt` when rendered in this font.
+  pub fn size_of(&self, txt: &str) -> (uint, uint) {
+    let (mut w, mut h): (ffi::c_int, ffi::c_int) = (0, 0);
+    txt.with_c_str(|c_txt| unsafe {
+      assert_eq!(ffi::TTF_SizeUTF8(self.p, c_txt, &mut w, &mut h), 0);
+    });
+    (w as uint, h as uint)
+  }
+
   /// Color is rgba
   pub fn render(&self, txt: &str, color: Color4<u8>) -> Texture {
     unsafe {
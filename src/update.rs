@@ -1,28 +1,47 @@
 use common::*;
+use crosshair;
+use fontloader;
 use gl;
 use gl::types::*;
 use glw::color::Color4;
 use input;
 use loader::{Load, Unload};
 use mob;
-use nalgebra::Vec3;
+use nalgebra::{Dot, Vec3};
+use ncollide::ray::RayCast;
+use path::Waypoint;
 use physics::Physics;
 use state::EntityId;
 use state::App;
+use state::{MAX_UNSETTLED, REACH_RING_SEGMENTS};
 use stopwatch;
 use stopwatch::*;
 use std::cmp;
 use std::collections::HashMap;
+use terrain;
 
 // how many terrain polys to load during every update step
 static TERRAIN_LOAD_SPEED: uint = 1 << 10;
 static OCTREE_LOAD_SPEED: uint = 1 << 11;
 
+// how many updates newly-placed terrain flashes for
+static FLASH_TICKS: u64 = 10;
+
+static REACH_RING_COLOR: Color4<GLfloat> = Color4 { r: 1.0, g: 1.0, b: 1.0, a: 0.5 };
+// how far above the player's feet the reach ring is drawn
+static REACH_RING_LIFT: GLfloat = 0.02;
+
+static TARGET_HIGHLIGHT_COLOR: Color4<GLfloat> = Color4 { r: 1.0, g: 1.0, b: 1.0, a: 0.3 };
+
+static DIG_BAR_BACKGROUND: Color4<GLfloat> = Color4 { r: 0.0, g: 0.0, b: 0.0, a: 0.5 };
+static DIG_BAR_FILL: Color4<GLfloat> = Color4 { r: 1.0, g: 1.0, b: 1.0, a: 0.8 };
+
+static OCTREE_OUTLINE_COLOR: Color4<GLfloat> = Color4 { r: 0.0, g: 0.0, b: 0.0, a: 0.1 };
+
 macro_rules! translate_mob(
   ($world:expr, $mob:expr, $v:expr) => (
     translate_mob(
       &mut $world.physics,
-      &mut $world.mob_buffers,
       $mob,
       $v
     );
@@ -31,6 +50,10 @@ macro_rules! translate_mob(
 
 pub fn update<'a>(app: &mut App) {
   time!(app.timers.deref(), "update", || {
+    if app.paused {
+      return;
+    }
+
     // TODO(cgaebel): Ideally, the update thread should not be touching OpenGL.
 
       match gl::GetError() {
@@ -48,11 +71,70 @@ pub fn update<'a>(app: &mut App) {
       load_octree(app);
     });
 
-    time!(app.timers.deref(), "update.player", || {
-      app.player.update(&mut app.physics);
+    tick(app);
+
+    time!(app.timers.deref(), "update.mob_buffers", || {
+      for (_, mob) in app.mobs.iter() {
+        let bounds = app.physics.get_bounds(mob.id).unwrap();
+        app.mob_buffers.update(
+          mob.id,
+          to_triangles(bounds, &Color4::of_rgba(1.0, 0.0, 0.0, 1.0))
+        );
+      }
+    });
+
+    time!(app.timers.deref(), "update.target_info", || {
+      update_target_info(app);
+    });
+
+    time!(app.timers.deref(), "update.target_highlight", || {
+      update_target_highlight(app);
+    });
+
+    time!(app.timers.deref(), "update.reach_ring", || {
+      update_reach_ring(app);
     });
 
-    time!(app.timers.deref(), "update.mobs", || {
+    time!(app.timers.deref(), "update.debug_info", || {
+      update_debug_info(app);
+    });
+
+    time!(app.timers.deref(), "update.flash", || {
+      update_flashes(app);
+    });
+
+    time!(app.timers.deref(), "update.dig_bar", || {
+      update_dig_bar(app);
+    });
+
+    time!(app.timers.deref(), "update.chunk_outlines", || {
+      app.update_chunk_outlines();
+    });
+  })
+}
+
+/// Run one step of the simulation: players, mobs, terrain edits and hooks.
+/// Nothing here touches OpenGL (terrain changes are only queued for loading),
+/// so this can be driven step-by-step without rendering.
+pub fn tick<'a>(app: &mut App<'a>) {
+  time!(app.timers.deref(), "tick", || {
+    time!(app.timers.deref(), "tick.player", || {
+      if app.path.is_some() {
+        follow_path(app);
+      } else if app.tween.is_some() {
+        follow_tween(app);
+      } else if app.nudge_mode {
+        // the camera stays wherever it's been nudged to.
+      } else {
+        app.player.update(&mut app.physics);
+      }
+      match app.player2 {
+        None => {},
+        Some(ref mut player2) => player2.update(&mut app.physics),
+      }
+    });
+
+    time!(app.timers.deref(), "tick.mobs", || {
       // Unsafely mutably borrow the mobs.
       let mobs: *mut HashMap<EntityId, mob:: Mob> = &mut app.mobs;
       for (_, mob) in unsafe { (*mobs).iter_mut() } {
@@ -82,38 +164,247 @@ pub fn update<'a>(app: &mut App) {
 
     // terrain deletion
     if app.is_mouse_pressed(input::mouse::Left) {
-      time!(app.timers.deref(), "update.delete_terrain", || {
-        for id in entities_in_front(app).into_iter() {
-          if app.terrains.contains_key(&id) {
-            remove_terrain(app, id);
-          }
-        }
+      time!(app.timers.deref(), "tick.dig", || {
+        dig(app);
       })
+    } else {
+      app.dig = None;
+    }
+
+    if app.shake_ticks > 0 {
+      app.shake_ticks -= 1;
+      app.update_projection();
     }
+
+    time!(app.timers.deref(), "tick.hooks", || {
+      app.run_hooks();
+    });
+
+    app.ticks += 1;
   })
 }
 
-fn remove_terrain<'a>(app: &mut App<'a>, id: EntityId) {
+/// Move the player one step along `app.path`.
+fn follow_path<'a>(app: &mut App<'a>) {
+  let at = Waypoint {
+    position: app.player.camera.position,
+    yaw: app.player.lateral_rotation,
+    pitch: app.player.vertical_rotation,
+  };
+  match app.path.as_mut().unwrap().step(&at) {
+    None => {
+      app.path = None;
+    },
+    Some(next) => {
+      app.player.teleport(&mut app.physics, next.position);
+      app.player.set_rotation(next.yaw, next.pitch);
+    },
+  }
+}
+
+/// Move the player one step along `app.tween`.
+fn follow_tween<'a>(app: &mut App<'a>) {
+  match app.tween.as_mut().unwrap().step() {
+    None => {
+      app.tween = None;
+    },
+    Some(next) => {
+      app.player.teleport(&mut app.physics, next.position);
+      app.player.set_rotation(next.yaw, next.pitch);
+    },
+  }
+}
+
+/// Queue the terrain piece `id` for removal. Returns false if there's no such
+/// piece.
+fn remove_terrain<'a>(app: &mut App<'a>, id: EntityId) -> bool {
+  if !app.terrains.contains_key(&id) {
+    return false;
+  }
   app.terrain_loader.push(Unload(id));
+  true
+}
+
+/// Dig at the terrain under the crosshair for another update, and remove it
+/// once it's been dug at for long enough (see `TypeInfo::dig_progress`).
+/// Looking at something else, or at terrain further away than the `reach`
+/// setting, starts over.
+fn dig<'a>(app: &mut App<'a>) {
+  let ray = app.player.forward_ray();
+  let target =
+    match entities_in_front(app).into_iter().next() {
+      Some(id) if app.terrains.contains_key(&id) => id,
+      _ => {
+        app.dig = None;
+        return;
+      },
+    };
+  let distance = app.physics.get_bounds(target).unwrap().toi_with_ray(&ray, true);
+  if distance.map_or(true, |d| d > app.settings.reach) {
+    app.dig = None;
+    return;
+  }
+  let ticks =
+    match app.dig {
+      Some((id, ticks)) if id == target => ticks + 1,
+      _ => 1,
+    };
+  app.dig = Some((target, ticks));
+  if app.dig_progress().unwrap_or(1.0) >= 1.0 {
+    remove_terrain(app, target);
+    app.dig = None;
+    app.start_shake();
+  }
 }
 
-fn translate_mob(physics: &mut Physics<EntityId>, mob_buffers: &mut mob::MobBuffers, mob: &mut mob::Mob, delta_p: Vec3<GLfloat>) {
-  if physics.translate(mob.id, delta_p).unwrap() {
+fn translate_mob(physics: &mut Physics<EntityId>, mob: &mut mob::Mob, delta_p: Vec3<GLfloat>) {
+  let (collided, _) = physics.translate(mob.id, delta_p).unwrap();
+  if collided {
     mob.speed = mob.speed - delta_p;
-  } else {
-    let bounds = physics.get_bounds(mob.id).unwrap();
-    mob_buffers.update(
-      mob.id,
-      to_triangles(bounds, &Color4::of_rgba(1.0, 0.0, 0.0, 1.0))
-    );
   }
 }
 
 /// Returns ids of the closest entities in front of the cursor.
-fn entities_in_front<'a>(app: &mut App<'a>) -> Vec<EntityId> {
+pub fn entities_in_front<'a>(app: &mut App<'a>) -> Vec<EntityId> {
   app.physics.octree.cast_ray(&app.player.forward_ray(), app.player.id)
 }
 
+/// Describe whatever's under the crosshair on the HUD, and exactly where the
+/// crosshair hits it.
+fn update_target_info<'a>(app: &mut App<'a>) {
+  let ray = app.player.forward_ray();
+  let text =
+    match entities_in_front(app).into_iter().next() {
+      None => String::from_str("no target"),
+      Some(id) => {
+        let kind =
+          match app.terrains.find(&id) {
+            None => String::from_str("mob"),
+            Some(terrain) =>
+              match app.terrain_types.get(terrain::TerrainType(terrain.typ)) {
+                None => String::from_str("terrain"),
+                Some(info) => info.name.clone(),
+              },
+          };
+        let distance =
+          app.physics.get_bounds(id).unwrap()
+          .toi_with_ray(&ray, true)
+          .unwrap_or(0.0);
+        let hit =
+          match app.pick_point() {
+            None => String::new(),
+            Some((_, _, p)) => format!(", hit at ({:.2}, {:.2}, {:.2})", p.x, p.y, p.z),
+          };
+        format!("{}: {} at {:.2}{}", id, kind, distance, hit)
+      },
+    };
+  app.text_overlay.set_text(app.font.get(fontloader::Mono), app.target_info, text.as_slice());
+}
+
+/// Draw a ring on the ground around the player showing how far they can
+/// reach, if the `reach_ring` setting is on.
+fn update_reach_ring<'a>(app: &mut App<'a>) {
+  let length = app.reach_ring.buffer.length;
+  if length > 0 {
+    app.reach_ring.swap_remove(0, length);
+  }
+  if !app.settings.reach_ring {
+    return;
+  }
+
+  // under the middle of the player, at their feet, and lifted a little so it
+  // doesn't flicker against the ground.
+  let up = app.player.up_axis.up();
+  let bounds = app.physics.get_bounds(app.player.id).unwrap().clone();
+  let middle = center(&bounds);
+  let height = middle.to_vec().dot(&up) - bounds.mins().to_vec().dot(&up);
+  let feet = middle + up * (REACH_RING_LIFT - height);
+
+  let u = app.player.up_axis.to_world(Vec3::new(1.0, 0.0, 0.0));
+  let v = app.player.up_axis.to_world(Vec3::new(0.0, 0.0, 1.0));
+  let vertices =
+    ring_lines(&feet, u, v, app.settings.reach, REACH_RING_SEGMENTS, &REACH_RING_COLOR);
+  app.reach_ring.push(vertices.as_slice());
+}
+
+/// Cover whatever's under the crosshair with a translucent box, if target
+/// highlighting is on.
+fn update_target_highlight<'a>(app: &mut App<'a>) {
+  let length = app.target_highlight.buffer.length;
+  if length > 0 {
+    app.target_highlight.swap_remove(0, length);
+  }
+  if !app.render_target_highlight {
+    return;
+  }
+  match entities_in_front(app).into_iter().next() {
+    None => {},
+    Some(id) => {
+      let bounds = app.physics.get_bounds(id).unwrap().clone();
+      app.target_highlight.push(to_triangles(&bounds, &TARGET_HIGHLIGHT_COLOR));
+    },
+  }
+}
+
+/// Fade out the flash on newly-placed terrain.
+fn update_flashes<'a>(app: &mut App<'a>) {
+  let mut still_flashing = Vec::new();
+  for &id in app.flashing.iter() {
+    let created_at =
+      match app.terrains.find(&id) {
+        // it's been removed since it was placed.
+        None => continue,
+        Some(terrain) => terrain.created_at,
+      };
+    if !app.terrain_buffers.contains(id) {
+      continue;
+    }
+    let age = app.ticks - created_at;
+    if age < FLASH_TICKS {
+      let amount = 1.0 - age as GLfloat / FLASH_TICKS as GLfloat;
+      app.terrain_buffers.set_flash(id, amount);
+      still_flashing.push(id);
+    } else {
+      app.terrain_buffers.set_flash(id, 0.0);
+    }
+  }
+  app.flashing = still_flashing;
+}
+
+/// Show how far along digging is under the crosshair, or nothing if the
+/// player isn't digging.
+fn update_dig_bar<'a>(app: &mut App<'a>) {
+  let length = app.dig_bar.buffer.length;
+  if length > 0 {
+    app.dig_bar.swap_remove(0, length);
+  }
+  match app.dig_progress() {
+    None => {},
+    Some(progress) => {
+      app.dig_bar.push(
+        crosshair::progress_bar(progress, DIG_BAR_BACKGROUND, DIG_BAR_FILL).as_slice()
+      );
+    },
+  }
+}
+
+/// Show the player's horizontal and vertical speed on the HUD, if debugging
+/// readouts are turned on.
+fn update_debug_info<'a>(app: &mut App<'a>) {
+  let text =
+    if app.show_debug {
+      let speed = app.player.speed;
+      let horizontal = (speed.x * speed.x + speed.z * speed.z).sqrt();
+      format!(
+        "speed: {:.2} horizontal, {:.2} vertical; {} blocks in view",
+        horizontal, speed.y, app.visible_blocks().len(),
+      )
+    } else {
+      String::new()
+    };
+  app.text_overlay.set_text(app.font.get(fontloader::Mono), app.speed_info, text.as_slice());
+}
+
 fn load_terrain<'a>(app: &mut App<'a>, max: Option<uint>) {
   time!(app.timers.deref(), "load.terrain", || {
     // terrain loading
@@ -123,16 +414,29 @@ fn load_terrain<'a>(app: &mut App<'a>, max: Option<uint>) {
         let terrains = &mut app.terrains;
         let terrain_buffers = &mut app.terrain_buffers;
         let physics = &mut app.physics;
+        let unsettled = &mut app.unsettled;
+        let flashing = &mut app.flashing;
+        let ticks = app.ticks;
+        let type_count = app.terrain_types.len();
         match *op {
           Load(id) => {
             let terrain = terrains.find(&id).unwrap();
             terrain_buffers.push(
               id,
               terrain,
+              terrain.ambient_occlusion(physics),
+              terrain.type_weights(physics, terrains, type_count).as_slice(),
             );
+            // the world's own terrain doesn't flash as it loads in.
+            if terrain.created_at > 0 && ticks - terrain.created_at < FLASH_TICKS {
+              flashing.push(id);
+            }
           },
           Unload(id) => {
             if terrains.remove(&id) {
+              if unsettled.len() < MAX_UNSETTLED {
+                unsettled.push((id, physics.get_bounds(id).unwrap().clone()));
+              }
               terrain_buffers.swap_remove(id);
               physics.remove(id);
             }
@@ -150,15 +454,21 @@ fn load_octree<'a>(app: &mut App<'a>) {
     // octree loading
     let count = cmp::min(OCTREE_LOAD_SPEED, app.octree_loader.deref().borrow().deref().len());
     if count > 0 {
-      for op in app.octree_loader.borrow().iter(0, count) {
-        match *op {
-          Load((id, bounds)) => {
-            app.octree_buffers.push(id, to_outlines(&bounds));
-          },
-          Unload(id) => {
-            app.octree_buffers.swap_remove(id);
+      // without outline buffers, the ops are just dropped.
+      match app.octree_buffers {
+        None => {},
+        Some(ref mut octree_buffers) => {
+          for op in app.octree_loader.borrow().iter(0, count) {
+            match *op {
+              Load((id, bounds)) => {
+                octree_buffers.push(id, to_outlines(&bounds, &OCTREE_OUTLINE_COLOR));
+              },
+              Unload(id) => {
+                octree_buffers.swap_remove(id);
+              }
+            }
           }
-        }
+        },
       }
 
       app.octree_loader.borrow_mut().pop(count);